@@ -0,0 +1,38 @@
+//! AT commands of the cellular and Wi-Fi modems.
+
+/// Terminator of the commands sent to the modem.
+pub const TERMINATOR: &str = "\r";
+
+/// Whether `line` is a final result code ending the response to a command, `Some(true)` when
+/// the command succeeded.
+pub fn final_result(line: &str) -> Option<bool> {
+    match line {
+        "OK" | "CONNECT" | "SEND OK" => Some(true),
+        "ERROR" | "NO CARRIER" | "BUSY" | "NO ANSWER" | "NO DIALTONE" | "SEND FAIL" => Some(false),
+        _ if line.starts_with("CONNECT ") => Some(true),
+        _ if line.starts_with("+CME ERROR") || line.starts_with("+CMS ERROR") => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn final_results() {
+        assert_eq!(final_result("OK"), Some(true));
+        assert_eq!(final_result("CONNECT 115200"), Some(true));
+        assert_eq!(final_result("ERROR"), Some(false));
+        assert_eq!(final_result("+CME ERROR: 10"), Some(false));
+        assert_eq!(final_result("+CMS ERROR: 500"), Some(false));
+    }
+
+    #[test]
+    fn intermediate_lines() {
+        assert_eq!(final_result(""), None);
+        assert_eq!(final_result("+CSQ: 20,99"), None);
+        assert_eq!(final_result("AT+CSQ"), None);
+        assert_eq!(final_result("OKAY"), None);
+    }
+}
@@ -0,0 +1,273 @@
+//! Lossless captures of a session, each chunk given to the display is recorded with its time and
+//! direction, along with the settings of the port. A capture can be replayed through the display
+//! at its original speed or faster.
+//!
+//! The file starts with `STOOLCAP` and the version byte, followed by the records:
+//!
+//! | Size | Content                                                       |
+//! |------|---------------------------------------------------------------|
+//! | 1    | kind: 0 received bytes, 1 written bytes, 2 settings           |
+//! | 8    | time in microseconds since the Unix epoch, little endian      |
+//! | 4    | size of the content, little endian                            |
+//! | n    | bytes, or settings as a JSON profile                          |
+
+use crate::profile::Profile;
+use crate::serial::{ByteDirection, IO_DATA};
+use bytes::Bytes;
+use druid::{ExtEventSink, Selector, Target};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const REPLAY_SETTINGS: Selector<Profile> = Selector::new("event.replay-settings");
+pub const REPLAY_FINISHED: Selector = Selector::new("event.replay-finished");
+
+const MAGIC: &[u8] = b"STOOLCAP";
+const VERSION: u8 = 1;
+/// Kind, time and size of a record.
+const RECORD_HEADER_SIZE: usize = 13;
+
+const KIND_IN: u8 = 0;
+const KIND_OUT: u8 = 1;
+const KIND_SETTINGS: u8 = 2;
+
+#[derive(Debug, Clone)]
+pub enum Record {
+    Data(ByteDirection, Bytes),
+    Settings(Profile),
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Microseconds since the Unix epoch.
+    pub time: u64,
+    pub record: Record,
+}
+
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+pub struct CaptureWriter {
+    file: File,
+}
+
+impl CaptureWriter {
+    /// Start a capture of a session on a port opened with `settings`.
+    pub fn create(path: &Path, settings: &Profile) -> Result<Self, String> {
+        let mut file = File::create(path).map_err(|e| e.to_string())?;
+        file.write_all(MAGIC)
+            .and_then(|_| file.write_all(&[VERSION]))
+            .map_err(|e| e.to_string())?;
+        let mut writer = CaptureWriter { file };
+        writer.write_settings(settings)?;
+        Ok(writer)
+    }
+
+    /// Each record is written at once so the capture stays readable if stool stops.
+    fn write_record(&mut self, kind: u8, content: &[u8]) -> Result<(), String> {
+        let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + content.len());
+        record.push(kind);
+        record.extend_from_slice(&now_micros().to_le_bytes());
+        record.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        record.extend_from_slice(content);
+        self.file.write_all(&record).map_err(|e| e.to_string())
+    }
+
+    pub fn write_data(&mut self, direction: ByteDirection, data: &[u8]) -> Result<(), String> {
+        let kind = match direction {
+            ByteDirection::In => KIND_IN,
+            ByteDirection::Out => KIND_OUT,
+        };
+        self.write_record(kind, data)
+    }
+
+    /// Record the settings of the port when it is opened again.
+    pub fn write_settings(&mut self, settings: &Profile) -> Result<(), String> {
+        let json = serde_json::to_vec(settings).map_err(|e| e.to_string())?;
+        self.write_record(KIND_SETTINGS, &json)
+    }
+}
+
+/// Records of a capture file, a record cut at the end of the file is ignored.
+pub fn read(path: &Path) -> Result<Vec<Entry>, String> {
+    let content = std::fs::read(path).map_err(|e| e.to_string())?;
+    let records = match content.strip_prefix(MAGIC) {
+        Some([VERSION, records @ ..]) => records,
+        Some(_) => return Err("Unsupported capture version".to_string()),
+        None => return Err("Not a stool capture".to_string()),
+    };
+
+    let mut entries = Vec::new();
+    let mut rest = records;
+    while rest.len() >= RECORD_HEADER_SIZE {
+        let kind = rest[0];
+        let time = u64::from_le_bytes(rest[1..9].try_into().unwrap());
+        let size = u32::from_le_bytes(rest[9..13].try_into().unwrap()) as usize;
+        let content = match rest.get(RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + size) {
+            Some(content) => content,
+            None => break,
+        };
+        let record = match kind {
+            KIND_IN => Record::Data(ByteDirection::In, Bytes::copy_from_slice(content)),
+            KIND_OUT => Record::Data(ByteDirection::Out, Bytes::copy_from_slice(content)),
+            KIND_SETTINGS => Record::Settings(
+                serde_json::from_slice(content)
+                    .map_err(|e| format!("Incorrect settings: {}", e))?,
+            ),
+            _ => return Err(format!("Unknown record kind {}", kind)),
+        };
+        entries.push(Entry { time, record });
+        rest = &rest[RECORD_HEADER_SIZE + size..];
+    }
+    Ok(entries)
+}
+
+/// A capture replayed on its own thread, its records are given to the GUI as if they came from
+/// the serial thread.
+pub struct Replay {
+    stop: Arc<AtomicBool>,
+}
+
+impl Replay {
+    /// Replay `entries` `speed` times faster than recorded, as fast as possible without speed.
+    pub fn start(entries: Vec<Entry>, speed: Option<f64>, event_sink: ExtEventSink) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+
+        thread::spawn(move || {
+            let started = Instant::now();
+            let first = entries.first().map_or(0, |entry| entry.time);
+            for entry in entries {
+                if let Some(speed) = speed {
+                    let offset = Duration::from_micros(entry.time.saturating_sub(first));
+                    let due = started + offset.div_f64(speed);
+                    // Sleep by steps to stop quickly
+                    while Instant::now() < due {
+                        if stopped.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let wait = due.saturating_duration_since(Instant::now());
+                        thread::sleep(wait.min(Duration::from_millis(50)));
+                    }
+                }
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let sent = match entry.record {
+                    Record::Data(direction, data) => {
+                        event_sink.submit_command(IO_DATA, (direction, data), Target::Global)
+                    }
+                    Record::Settings(settings) => {
+                        event_sink.submit_command(REPLAY_SETTINGS, settings, Target::Global)
+                    }
+                };
+                if sent.is_err() {
+                    return;
+                }
+            }
+            let _ = event_sink.submit_command(REPLAY_FINISHED, (), Target::Global);
+        });
+
+        Replay { stop }
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("stool-capture-{}-{}", std::process::id(), name))
+    }
+
+    fn settings() -> Profile {
+        Profile {
+            port_name: "COM3".to_string(),
+            baud_rate: 9600,
+            ..Profile::default()
+        }
+    }
+
+    /// Capture of the settings, received then written bytes, and settings again.
+    fn write_capture(path: &Path) {
+        let mut writer = CaptureWriter::create(path, &settings()).unwrap();
+        writer.write_data(ByteDirection::In, b"ping").unwrap();
+        writer.write_data(ByteDirection::Out, b"").unwrap();
+        writer.write_data(ByteDirection::Out, b"pong").unwrap();
+        writer.write_settings(&Profile::default()).unwrap();
+    }
+
+    fn data(entry: &Entry) -> Option<(ByteDirection, &[u8])> {
+        match &entry.record {
+            Record::Data(direction, data) => Some((*direction, data)),
+            Record::Settings(_) => None,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("round-trip");
+        write_capture(&path);
+        let entries = read(&path);
+        let _ = std::fs::remove_file(&path);
+
+        let entries = entries.unwrap();
+        assert_eq!(entries.len(), 5);
+        assert!(matches!(&entries[0].record, Record::Settings(profile) if *profile == settings()));
+        assert_eq!(data(&entries[1]), Some((ByteDirection::In, &b"ping"[..])));
+        assert_eq!(data(&entries[2]), Some((ByteDirection::Out, &b""[..])));
+        assert_eq!(data(&entries[3]), Some((ByteDirection::Out, &b"pong"[..])));
+        assert!(
+            matches!(&entries[4].record, Record::Settings(profile) if *profile == Profile::default())
+        );
+        assert!(entries.windows(2).all(|pair| pair[0].time <= pair[1].time));
+    }
+
+    #[test]
+    fn truncated_record_ignored() {
+        let path = temp_path("truncated");
+        write_capture(&path);
+        let content = std::fs::read(&path).unwrap();
+        let settings_len = serde_json::to_vec(&Profile::default()).unwrap().len();
+        // Cut in the content then in the header of the last record
+        std::fs::write(&path, &content[..content.len() - settings_len / 2]).unwrap();
+        let cut_content = read(&path);
+        std::fs::write(&path, &content[..content.len() - settings_len - 5]).unwrap();
+        let cut_header = read(&path);
+        let _ = std::fs::remove_file(&path);
+
+        for entries in [cut_content, cut_header] {
+            let entries = entries.unwrap();
+            assert_eq!(entries.len(), 4);
+            assert_eq!(data(&entries[3]), Some((ByteDirection::Out, &b"pong"[..])));
+        }
+    }
+
+    #[test]
+    fn not_a_capture() {
+        let path = temp_path("not-a-capture");
+        std::fs::write(&path, b"STOOLCAQ\x01").unwrap();
+        let wrong_magic = read(&path);
+        std::fs::write(&path, b"STOOLCAP\x02").unwrap();
+        let wrong_version = read(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(wrong_magic.unwrap_err(), "Not a stool capture");
+        assert_eq!(wrong_version.unwrap_err(), "Unsupported capture version");
+    }
+}
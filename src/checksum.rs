@@ -0,0 +1,258 @@
+//! Checksums appended to the frames sent and checked on the frames received.
+
+use druid::Data;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Data, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Algorithm {
+    None,
+    Sum8,
+    Xor8,
+    Crc8,
+    Crc8Maxim,
+    Crc8SaeJ1850,
+    Crc16Modbus,
+    Crc16Ccitt,
+    Crc16Xmodem,
+    Crc16Mcrf4xx,
+    Crc32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Data, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Endianness {
+    Big,
+    Little,
+}
+
+/// Bitwise CRC of at most 32 bits described by its Rocksoft model parameters.
+fn crc(width: u32, poly: u32, init: u32, reflected: bool, xor_out: u32, data: &[u8]) -> u32 {
+    let mask = u32::MAX >> (32 - width);
+    let mut crc = init;
+
+    if reflected {
+        let poly = poly.reverse_bits() >> (32 - width);
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ poly
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+    } else {
+        let top = 1 << (width - 1);
+        for &byte in data {
+            crc ^= (byte as u32) << (width - 8);
+            for _ in 0..8 {
+                crc = if crc & top != 0 {
+                    (crc << 1) ^ poly
+                } else {
+                    crc << 1
+                } & mask;
+            }
+        }
+    }
+
+    (crc ^ xor_out) & mask
+}
+
+impl Algorithm {
+    /// Size of the checksum in bytes.
+    pub fn width(self) -> usize {
+        match self {
+            Algorithm::None => 0,
+            Algorithm::Sum8
+            | Algorithm::Xor8
+            | Algorithm::Crc8
+            | Algorithm::Crc8Maxim
+            | Algorithm::Crc8SaeJ1850 => 1,
+            Algorithm::Crc16Modbus
+            | Algorithm::Crc16Ccitt
+            | Algorithm::Crc16Xmodem
+            | Algorithm::Crc16Mcrf4xx => 2,
+            Algorithm::Crc32 => 4,
+        }
+    }
+
+    pub fn compute(self, data: &[u8]) -> u32 {
+        match self {
+            Algorithm::None => 0,
+            Algorithm::Sum8 => data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) as u32,
+            Algorithm::Xor8 => data.iter().fold(0u8, |xor, &byte| xor ^ byte) as u32,
+            Algorithm::Crc8 => crc(8, 0x07, 0x00, false, 0x00, data),
+            Algorithm::Crc8Maxim => crc(8, 0x31, 0x00, true, 0x00, data),
+            Algorithm::Crc8SaeJ1850 => crc(8, 0x1D, 0xFF, false, 0xFF, data),
+            Algorithm::Crc16Modbus => crc(16, 0x8005, 0xFFFF, true, 0x0000, data),
+            Algorithm::Crc16Ccitt => crc(16, 0x1021, 0xFFFF, false, 0x0000, data),
+            Algorithm::Crc16Xmodem => crc(16, 0x1021, 0x0000, false, 0x0000, data),
+            Algorithm::Crc16Mcrf4xx => crc(16, 0x1021, 0xFFFF, true, 0x0000, data),
+            Algorithm::Crc32 => crc(32, 0x04C1_1DB7, 0xFFFF_FFFF, true, 0xFFFF_FFFF, data),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Algorithm::None => write!(f, "None"),
+            Algorithm::Sum8 => write!(f, "Sum 8"),
+            Algorithm::Xor8 => write!(f, "XOR 8"),
+            Algorithm::Crc8 => write!(f, "CRC-8"),
+            Algorithm::Crc8Maxim => write!(f, "CRC-8/MAXIM"),
+            Algorithm::Crc8SaeJ1850 => write!(f, "CRC-8/SAE-J1850"),
+            Algorithm::Crc16Modbus => write!(f, "CRC-16/MODBUS"),
+            Algorithm::Crc16Ccitt => write!(f, "CRC-16/CCITT"),
+            Algorithm::Crc16Xmodem => write!(f, "CRC-16/XMODEM"),
+            Algorithm::Crc16Mcrf4xx => write!(f, "CRC-16/MCRF4XX"),
+            Algorithm::Crc32 => write!(f, "CRC-32"),
+        }
+    }
+}
+
+/// Checksum of the bytes of a frame, `skip_start` bytes at the beginning and `skip_end`
+/// bytes at the end of the frame are not covered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    pub endianness: Endianness,
+    pub skip_start: usize,
+    pub skip_end: usize,
+}
+
+impl Checksum {
+    fn encode(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let covered = data.get(self.skip_start..data.len().checked_sub(self.skip_end)?)?;
+        let value = self.algorithm.compute(covered).to_be_bytes();
+        let mut bytes = value[4 - self.algorithm.width()..].to_vec();
+        if self.endianness == Endianness::Little {
+            bytes.reverse();
+        }
+        Some(bytes)
+    }
+
+    /// Append the checksum to `frame`, before its trailing `delimiter` if any.
+    pub fn append(&self, frame: &[u8], delimiter: Option<u8>) -> Result<Vec<u8>, &'static str> {
+        let (data, end) = split_delimiter(frame, delimiter);
+        let checksum = self
+            .encode(data)
+            .ok_or("Checksum range larger than the data")?;
+
+        Ok([data, &checksum, end].concat())
+    }
+
+    /// Check the checksum at the end of `frame`, before its trailing `delimiter` if any.
+    pub fn verify(&self, frame: &[u8], delimiter: Option<u8>) -> bool {
+        let (data, _) = split_delimiter(frame, delimiter);
+        match data.len().checked_sub(self.algorithm.width()) {
+            Some(len) => {
+                let (data, checksum) = data.split_at(len);
+                self.encode(data).as_deref() == Some(checksum)
+            }
+            None => false,
+        }
+    }
+}
+
+fn split_delimiter(frame: &[u8], delimiter: Option<u8>) -> (&[u8], &[u8]) {
+    match (frame.last(), delimiter) {
+        (Some(&last), Some(delimiter)) if last == delimiter => frame.split_at(frame.len() - 1),
+        _ => (frame, &[]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checksum(
+        algorithm: Algorithm,
+        endianness: Endianness,
+        skip_start: usize,
+        skip_end: usize,
+    ) -> Checksum {
+        Checksum {
+            algorithm,
+            endianness,
+            skip_start,
+            skip_end,
+        }
+    }
+
+    #[test]
+    fn check_values() {
+        let checks = [
+            (Algorithm::None, 0),
+            (Algorithm::Sum8, 0xDD),
+            (Algorithm::Xor8, 0x31),
+            (Algorithm::Crc8, 0xF4),
+            (Algorithm::Crc8Maxim, 0xA1),
+            (Algorithm::Crc8SaeJ1850, 0x4B),
+            (Algorithm::Crc16Modbus, 0x4B37),
+            (Algorithm::Crc16Ccitt, 0x29B1),
+            (Algorithm::Crc16Xmodem, 0x31C3),
+            (Algorithm::Crc16Mcrf4xx, 0x6F91),
+            (Algorithm::Crc32, 0xCBF4_3926),
+        ];
+        for (algorithm, check) in checks {
+            assert_eq!(algorithm.compute(b"123456789"), check, "{}", algorithm);
+        }
+    }
+
+    #[test]
+    fn append_in_both_endianness() {
+        let big = checksum(Algorithm::Crc16Modbus, Endianness::Big, 0, 0);
+        assert_eq!(
+            big.append(b"123456789", None).unwrap(),
+            b"123456789\x4B\x37"
+        );
+        let little = checksum(Algorithm::Crc16Modbus, Endianness::Little, 0, 0);
+        assert_eq!(
+            little.append(b"123456789", None).unwrap(),
+            b"123456789\x37\x4B"
+        );
+        let none = checksum(Algorithm::None, Endianness::Big, 0, 0);
+        assert_eq!(none.append(b"123", None).unwrap(), b"123");
+    }
+
+    #[test]
+    fn append_before_delimiter() {
+        let sum = checksum(Algorithm::Sum8, Endianness::Big, 0, 0);
+        assert_eq!(
+            sum.append(b"123456789\n", Some(b'\n')).unwrap(),
+            b"123456789\xDD\n"
+        );
+        // Without the delimiter at the end the whole frame is covered
+        assert_eq!(sum.append(b"12", Some(b'\n')).unwrap(), b"12\x63");
+    }
+
+    #[test]
+    fn skipped_bytes() {
+        let crc = checksum(Algorithm::Crc32, Endianness::Big, 2, 1);
+        let frame = crc.append(b"$$123456789*", None).unwrap();
+        assert_eq!(frame, b"$$123456789*\xCB\xF4\x39\x26");
+        assert!(crc.verify(&frame, None));
+
+        let frame = crc.append(b"$$123456789*\r", Some(b'\r')).unwrap();
+        assert_eq!(frame, b"$$123456789*\xCB\xF4\x39\x26\r");
+        assert!(crc.verify(&frame, Some(b'\r')));
+
+        assert!(crc.append(b"$$", None).is_err());
+        assert!(crc.append(b"$", None).is_err());
+    }
+
+    #[test]
+    fn verify_rejects() {
+        let crc = checksum(Algorithm::Crc16Xmodem, Endianness::Big, 1, 0);
+        assert!(crc.verify(b">123456789\x31\xC3", None));
+        assert!(!crc.verify(b">123456789\xC3\x31", None));
+        assert!(!crc.verify(b">123456780\x31\xC3", None));
+        // Too short for the checksum or the skipped bytes
+        assert!(!crc.verify(b"\x31", None));
+        assert!(!crc.verify(b"\x31\xC3", None));
+    }
+}
@@ -0,0 +1,185 @@
+//! Command line arguments, they override the settings of the profile given with `--profile`.
+
+use crate::data::{
+    DruidDataBits, DruidFlowControl, DruidParity, DruidStopBits, Framing, LineEnding, Protocol,
+};
+use crate::profile::{self, Profile};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "Usage: stool [--console] [--open] [--profile <name>] [--port <name>]
+             [--baud <rate>] [--data-bits <5-8>] [--parity <parity>] [--stop-bits <1-2>]
+             [--flow <flow>] [--mode <mode>] [--framing <framing>] [--delimiter <byte>]
+             [--layout <file>] [--line-ending <ending>] [--hex]
+  --console    run in the console instead of the GUI, also --headless
+  --open       open the port when the GUI starts
+  --profile    profile to start from
+  --port       serial port to open
+  --baud       baud rate, 115200 by default
+  --data-bits  5, 6, 7 or 8, 8 by default
+  --parity     none, even or odd, none by default
+  --stop-bits  1 or 2, 1 by default
+  --flow       none, software or hardware, none by default
+  --mode       text, raw, modbus, nmea, mavlink, slcan, at or scpi, text by default in the console
+  --framing    none, silence, delimiter or layout, framing of the received bytes in raw mode
+  --delimiter  byte ending the frames of the delimiter framing, 10 or 0x0A by default
+  --layout     layout file of the layout framing
+  --line-ending
+               none, lf, cr or crlf, appended to the lines written in text mode, lf by default in
+               the console
+  --hex        console only, write the stdin lines as hexadecimal bytes and print the received
+               bytes in hexadecimal";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Args {
+    pub console: bool,
+    pub open: bool,
+    pub hex: bool,
+    pub profile: Option<String>,
+    pub port_name: Option<String>,
+    pub baud_rate: Option<u32>,
+    pub data_bits: Option<DruidDataBits>,
+    pub parity: Option<DruidParity>,
+    pub stop_bits: Option<DruidStopBits>,
+    pub flow_control: Option<DruidFlowControl>,
+    pub protocol: Option<Protocol>,
+    pub framing: Option<Framing>,
+    pub delimiter: Option<u32>,
+    pub layout: Option<PathBuf>,
+    pub line_ending: Option<LineEnding>,
+}
+
+impl Args {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = Args::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .map(|value| value.as_str())
+                    .ok_or_else(|| format!("Missing value after {}", arg))
+            };
+            match arg.as_str() {
+                "--console" | "--headless" => parsed.console = true,
+                "--open" => parsed.open = true,
+                "--hex" => parsed.hex = true,
+                "--profile" => parsed.profile = Some(value()?.to_string()),
+                "--port" => parsed.port_name = Some(value()?.to_string()),
+                "--baud" => {
+                    let baud_rate = value()?.parse().map_err(|_| "Incorrect baud rate")?;
+                    parsed.baud_rate = Some(baud_rate);
+                }
+                "--data-bits" => {
+                    parsed.data_bits = Some(match value()? {
+                        "5" => DruidDataBits::Five,
+                        "6" => DruidDataBits::Six,
+                        "7" => DruidDataBits::Seven,
+                        "8" => DruidDataBits::Eight,
+                        _ => return Err("Incorrect data bits".to_string()),
+                    })
+                }
+                "--parity" => {
+                    parsed.parity = Some(match value()? {
+                        "none" => DruidParity::None,
+                        "even" => DruidParity::Even,
+                        "odd" => DruidParity::Odd,
+                        _ => return Err("Incorrect parity".to_string()),
+                    })
+                }
+                "--stop-bits" => {
+                    parsed.stop_bits = Some(match value()? {
+                        "1" => DruidStopBits::One,
+                        "2" => DruidStopBits::Two,
+                        _ => return Err("Incorrect stop bits".to_string()),
+                    })
+                }
+                "--flow" => {
+                    parsed.flow_control = Some(match value()? {
+                        "none" => DruidFlowControl::None,
+                        "software" => DruidFlowControl::Software,
+                        "hardware" => DruidFlowControl::Hardware,
+                        _ => return Err("Incorrect flow control".to_string()),
+                    })
+                }
+                "--mode" => parsed.protocol = Some(parse_protocol(value()?)?),
+                "--framing" => {
+                    parsed.framing = Some(match value()? {
+                        "none" => Framing::None,
+                        "silence" => Framing::Silence,
+                        "delimiter" => Framing::Delimiter,
+                        "layout" => Framing::Layout,
+                        _ => return Err("Incorrect framing".to_string()),
+                    })
+                }
+                "--delimiter" => {
+                    let delimiter = value()?;
+                    let delimiter = match delimiter.strip_prefix("0x") {
+                        Some(hex) => u8::from_str_radix(hex, 16),
+                        None => delimiter.parse(),
+                    };
+                    parsed.delimiter = Some(delimiter.map_err(|_| "Incorrect delimiter")?.into());
+                }
+                "--layout" => parsed.layout = Some(PathBuf::from(value()?)),
+                "--line-ending" => {
+                    parsed.line_ending = Some(match value()? {
+                        "none" => LineEnding::None,
+                        "lf" => LineEnding::Lf,
+                        "cr" => LineEnding::Cr,
+                        "crlf" => LineEnding::CrLf,
+                        _ => return Err("Incorrect line ending".to_string()),
+                    })
+                }
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Settings of the profile given in the arguments, or of the last profile in the GUI, or the
+    /// default ones, overridden by the arguments.
+    pub fn settings(&self) -> Result<Profile, String> {
+        let mut profile = match &self.profile {
+            Some(name) => Profile::load(name)?,
+            None if self.console => Profile {
+                protocol: Protocol::Text,
+                line_ending: LineEnding::Lf,
+                ..Profile::default()
+            },
+            None => profile::last_name()
+                .and_then(|name| Profile::load(&name).ok())
+                .unwrap_or_default(),
+        };
+
+        if let Some(port_name) = &self.port_name {
+            profile.port_name = port_name.clone();
+        }
+        profile.baud_rate = self.baud_rate.unwrap_or(profile.baud_rate);
+        profile.data_bits = self.data_bits.unwrap_or(profile.data_bits);
+        profile.parity = self.parity.unwrap_or(profile.parity);
+        profile.stop_bits = self.stop_bits.unwrap_or(profile.stop_bits);
+        profile.flow_control = self.flow_control.unwrap_or(profile.flow_control);
+        profile.protocol = self.protocol.unwrap_or(profile.protocol);
+        profile.framing = self.framing.unwrap_or(profile.framing);
+        profile.delimiter = self.delimiter.unwrap_or(profile.delimiter);
+        if let Some(layout) = &self.layout {
+            profile.layout = Some(layout.clone());
+        }
+        profile.line_ending = self.line_ending.unwrap_or(profile.line_ending);
+        Ok(profile)
+    }
+}
+
+fn parse_protocol(mode: &str) -> Result<Protocol, String> {
+    match mode {
+        "text" => Ok(Protocol::Text),
+        "raw" => Ok(Protocol::Raw),
+        "modbus" => Ok(Protocol::Modbus),
+        "nmea" => Ok(Protocol::Nmea),
+        "mavlink" => Ok(Protocol::Mavlink),
+        "slcan" => Ok(Protocol::Slcan),
+        "at" => Ok(Protocol::At),
+        "scpi" => Ok(Protocol::Scpi),
+        _ => Err(format!("Unknown mode {}", mode)),
+    }
+}
@@ -0,0 +1,137 @@
+//! Headless mode running the serial thread without the GUI: the lines read on stdin are
+//! written on the port and the received data is printed on stdout.
+//!
+//! `stool --port /dev/ttyUSB0 --baud 115200 --mode text [--hex]`, see `cli::USAGE`.
+
+use crate::profile::Profile;
+use crate::serial::{self, ByteDirection, ControlLines, IoSink, ModemStatus};
+use crate::stats::Stats;
+use crate::{modbus, GuiMessage};
+use bytes::Bytes;
+use druid::ExtEventError;
+use futures::channel::mpsc;
+use std::io::{self, BufRead, Write};
+use std::thread;
+use std::time::Duration;
+
+/// Print what happens on the port.
+struct Console {
+    hex: bool,
+}
+
+impl IoSink for Console {
+    fn data(&self, direction: ByteDirection, data: Bytes) -> Result<(), ExtEventError> {
+        if direction == ByteDirection::Out {
+            return Ok(());
+        }
+
+        let mut stdout = io::stdout();
+        let written = if self.hex {
+            let line: Vec<_> = data.iter().map(|byte| format!("{:02X}", byte)).collect();
+            writeln!(stdout, "{}", line.join(" "))
+        } else {
+            stdout.write_all(&data)
+        };
+        written
+            .and_then(|_| stdout.flush())
+            .map_err(|_| ExtEventError)
+    }
+
+    fn error(&self, error: &'static str) -> Result<(), ExtEventError> {
+        eprintln!("{}", error);
+        Ok(())
+    }
+
+    fn log_stopped(&self) -> Result<(), ExtEventError> {
+        eprintln!("Cannot write the session log, logging stopped");
+        Ok(())
+    }
+
+    fn open_error(&self) -> Result<(), ExtEventError> {
+        eprintln!("Cannot open the port");
+        // Nothing can be done without a port
+        Err(ExtEventError)
+    }
+
+    fn transfer(&self, status: String) -> Result<(), ExtEventError> {
+        eprintln!("{}", status);
+        Ok(())
+    }
+
+    fn modbus_table(&self, _table: modbus::Table) -> Result<(), ExtEventError> {
+        Ok(())
+    }
+
+    fn stats(&self, _stats: Stats) -> Result<(), ExtEventError> {
+        Ok(())
+    }
+
+    fn modem_status(&self, _status: ModemStatus) -> Result<(), ExtEventError> {
+        Ok(())
+    }
+
+    fn control_lines(&self, _lines: ControlLines) -> Result<(), ExtEventError> {
+        Ok(())
+    }
+
+    fn line_break(&self, duration: Duration) -> Result<(), ExtEventError> {
+        eprintln!("Break of {} ms sent", duration.as_millis());
+        Ok(())
+    }
+
+    fn settings(&self, settings: String) -> Result<(), ExtEventError> {
+        eprintln!("{}", settings);
+        Ok(())
+    }
+}
+
+/// Run until stdin is closed, return the exit code of the process.
+pub fn run(settings: &Profile, hex: bool) -> i32 {
+    let layout = match settings.load_layout() {
+        Ok(layout) => layout,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let line_ending = settings.line_ending;
+    let (sender, receiver) = mpsc::unbounded::<GuiMessage>();
+    sender
+        .unbounded_send(GuiMessage::Open(settings.open_message(layout)))
+        .unwrap();
+
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let data = if hex {
+                let bytes: String = line.split_ascii_whitespace().collect();
+                match hex::decode(bytes) {
+                    Ok(bytes) => bytes,
+                    Err(_) => {
+                        eprintln!("Incorrect hexadecimal bytes");
+                        continue;
+                    }
+                }
+            } else {
+                format!("{}{}", line, line_ending.as_str()).into_bytes()
+            };
+            if sender
+                .unbounded_send(GuiMessage::Write(data.into()))
+                .is_err()
+            {
+                return;
+            }
+        }
+        // The serial thread stops once the port is closed and the sender dropped
+        let _ = sender.unbounded_send(GuiMessage::Close);
+    });
+
+    let async_rt = serial::runtime().expect("runtime failed");
+    match async_rt.block_on(serial::serial_loop(Console { hex }, receiver)) {
+        Ok(()) => 0,
+        Err(_) => 1,
+    }
+}
@@ -1,20 +1,70 @@
+use crate::checksum::{Algorithm, Checksum, Endianness};
+use crate::export::Chunk;
+use crate::layout::Layout;
+use crate::modbus::Function;
+use crate::plot::{Channel, PlotFormat};
+use crate::serial::ModemStatus;
+use crate::slcan::Bitrate;
+use crate::stats::Stats;
 use crate::GuiMessage;
 use druid::text::RichText;
 use druid::{Data, Lens};
 use futures::channel::mpsc::UnboundedSender;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::fmt;
 use std::ops::Range;
 use std::sync::Arc;
 use tokio_serial::{DataBits, FlowControl, Parity, StopBits};
 
-#[derive(Debug, Clone, Copy, PartialEq, Data)]
+#[derive(Debug, Clone, Copy, PartialEq, Data, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Protocol {
     Text,
     Raw,
+    Modbus,
+    ModbusSlave,
+    Nmea,
+    Mavlink,
+    Slcan,
+    At,
+    Scpi,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Data)]
+/// How the received bytes are split into frames.
+#[derive(Debug, Clone, Copy, PartialEq, Data, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Framing {
+    None,
+    Silence,
+    Delimiter,
+    /// Frames described by a layout file.
+    Layout,
+}
+
+/// Appended to the text written in text mode.
+#[derive(Debug, Clone, Copy, PartialEq, Data, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineEnding {
+    None,
+    Lf,
+    Cr,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::None => "",
+            LineEnding::Lf => "\n",
+            LineEnding::Cr => "\r",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Data, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum DruidDataBits {
     Eight,
     Seven,
@@ -44,7 +94,8 @@ impl fmt::Display for DruidDataBits {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Data)]
+#[derive(Debug, Clone, Copy, PartialEq, Data, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum DruidFlowControl {
     Hardware,
     Software,
@@ -71,7 +122,8 @@ impl fmt::Display for DruidFlowControl {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Data)]
+#[derive(Debug, Clone, Copy, PartialEq, Data, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum DruidParity {
     Even,
     Odd,
@@ -98,7 +150,8 @@ impl fmt::Display for DruidParity {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Data)]
+#[derive(Debug, Clone, Copy, PartialEq, Data, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum DruidStopBits {
     One,
     Two,
@@ -131,19 +184,252 @@ pub struct OpenMessage {
     pub parity: DruidParity,
     pub stop_bits: DruidStopBits,
     pub protocol: Protocol,
+    pub framing: Framing,
+    pub delimiter: u8,
+    pub layout: Option<Arc<Layout>>,
+}
+
+impl OpenMessage {
+    /// Number of bits sent on the line for one character, start bit included.
+    pub fn char_bits(&self) -> u32 {
+        let data_bits = match self.data_bits {
+            DruidDataBits::Eight => 8,
+            DruidDataBits::Seven => 7,
+            DruidDataBits::Six => 6,
+            DruidDataBits::Five => 5,
+        };
+        let parity_bits = match self.parity {
+            DruidParity::None => 0,
+            DruidParity::Even | DruidParity::Odd => 1,
+        };
+        let stop_bits = match self.stop_bits {
+            DruidStopBits::One => 1,
+            DruidStopBits::Two => 2,
+        };
+        1 + data_bits + parity_bits + stop_bits
+    }
+
+    /// Apply new line settings, the port, protocol and framing are kept.
+    pub fn reconfigure(&mut self, settings: LineSettings) {
+        self.baud_rate = settings.baud_rate;
+        self.data_bits = settings.data_bits;
+        self.flow_control = settings.flow_control;
+        self.parity = settings.parity;
+        self.stop_bits = settings.stop_bits;
+    }
+
+    pub fn line_settings(&self) -> LineSettings {
+        LineSettings {
+            baud_rate: self.baud_rate,
+            data_bits: self.data_bits,
+            flow_control: self.flow_control,
+            parity: self.parity,
+            stop_bits: self.stop_bits,
+        }
+    }
+
+    /// Short summary of the settings for the status bar.
+    pub fn summary(&self) -> String {
+        format!(
+            "{}, {}, {}, {}, {}, {}",
+            self.port_name,
+            self.baud_rate,
+            self.data_bits,
+            self.flow_control,
+            self.parity,
+            self.stop_bits
+        )
+    }
+}
+
+/// Settings of the line that can be changed without reopening the port.
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub struct LineSettings {
+    pub baud_rate: u32,
+    pub data_bits: DruidDataBits,
+    pub flow_control: DruidFlowControl,
+    pub parity: DruidParity,
+    pub stop_bits: DruidStopBits,
 }
+
 #[derive(Debug, Clone, PartialEq, Data)]
 pub enum OutputTag {
     TextIn,
     TextOut,
     RawIn,
     RawOut,
+    Decoded,
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Data, Lens, Serialize, Deserialize)]
+pub struct ChecksumData {
+    pub algorithm: Algorithm,
+    pub endianness: Endianness,
+    pub skip_start: u32,
+    pub skip_end: u32,
+}
+
+impl From<&ChecksumData> for Checksum {
+    fn from(data: &ChecksumData) -> Self {
+        Checksum {
+            algorithm: data.algorithm,
+            endianness: data.endianness,
+            skip_start: data.skip_start as usize,
+            skip_end: data.skip_end as usize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct ModbusData {
+    pub unit: u32,
+    pub function: Function,
+    pub address: u32,
+    pub quantity: u32,
+    pub values: String,
+    pub slave_unit: u32,
+    pub table: String,
+    /// Table as last applied or changed by the master, `table` is edited when they differ.
+    pub applied_table: String,
+}
+
+/// One decoded field of the last NMEA sentence of its kind.
+#[derive(Debug, Clone, Data, Lens)]
+pub struct NmeaRow {
+    pub sentence: String,
+    pub field: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct NmeaData {
+    pub rows: Arc<Vec<NmeaRow>>,
+    pub invalid: u32,
+}
+
+/// A CAN frame received by the SLCAN adapter.
+#[derive(Debug, Clone, Data, Lens)]
+pub struct CanRow {
+    pub timestamp: String,
+    pub id: String,
+    pub dlc: String,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct SlcanData {
+    pub bitrate: Bitrate,
+    pub timestamps: bool,
+    pub id: String,
+    pub extended: bool,
+    pub remote: bool,
+    pub dlc: u32,
+    pub data: String,
+    pub frames: Arc<Vec<CanRow>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub enum AtStatus {
+    Pending,
+    Ok,
+    Error,
+    Unsolicited,
+}
+
+/// An AT command with its response, or a line sent by the modem on its own.
+#[derive(Debug, Clone, Data, Lens)]
+pub struct AtExchange {
+    pub command: String,
+    pub response: String,
+    pub status: AtStatus,
+    pub duration: String,
+    pub expanded: bool,
+}
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct ScpiData {
+    pub idn: String,
+    pub poll_errors: bool,
+    /// Numeric responses as CSV lines: time, query and values.
+    pub readings: String,
+}
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct ScriptData {
+    pub source: String,
+    pub show: bool,
+    pub running: bool,
+}
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct ProfileData {
+    /// Name of the profile to save.
+    pub name: String,
+    /// Saved profiles.
+    pub names: Arc<Vec<String>>,
+    pub show: bool,
+}
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct LogData {
+    pub directory: String,
+    pub template: String,
+    /// Size of the files in MB, 0 when they are not rotated on their size.
+    pub max_size: u32,
+    /// Age of the files in minutes, 0 when they are not rotated on their age.
+    pub period: u32,
+    pub active: bool,
+    pub show: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub enum ReplaySpeed {
+    Original,
+    Times10,
+    Times100,
+    Max,
+}
+
+impl ReplaySpeed {
+    /// How many times faster than recorded, `None` as fast as possible.
+    pub fn factor(self) -> Option<f64> {
+        match self {
+            ReplaySpeed::Original => Some(1.),
+            ReplaySpeed::Times10 => Some(10.),
+            ReplaySpeed::Times100 => Some(100.),
+            ReplaySpeed::Max => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct CaptureData {
+    pub recording: bool,
+    pub replaying: bool,
+    pub speed: ReplaySpeed,
+    pub show: bool,
+}
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct PlotData {
+    pub format: PlotFormat,
+    pub regex: String,
+    /// Fields of the binary frames, one by line.
+    pub fields: String,
+    pub paused: bool,
+    /// Time shown in seconds.
+    pub window: f64,
+    pub channels: Arc<Vec<Channel>>,
+    pub show: bool,
 }
 
 #[derive(Debug, Clone, Data, Lens)]
 pub struct AppData {
     pub output: RichText,
     pub output_attr: Arc<VecDeque<(Range<usize>, OutputTag)>>,
+    /// Last chunks written and received, for the CSV and JSON Lines exports.
+    pub transcript: Arc<VecDeque<Chunk>>,
     pub port_name: Arc<String>,
     pub baud_rate: u32,
     pub to_write: Arc<String>,
@@ -152,6 +438,31 @@ pub struct AppData {
     pub parity: DruidParity,
     pub stop_bits: DruidStopBits,
     pub protocol: Protocol,
+    pub line_ending: LineEnding,
+    pub framing: Framing,
+    pub delimiter: u32,
+    pub layout: Option<Arc<Layout>>,
+    pub checksum: ChecksumData,
+    pub modbus: ModbusData,
+    pub nmea: NmeaData,
+    pub slcan: SlcanData,
+    pub at_exchanges: Arc<Vec<AtExchange>>,
+    pub scpi: ScpiData,
+    pub script: ScriptData,
+    pub profiles: ProfileData,
+    pub session_log: LogData,
+    pub capture: CaptureData,
+    pub plot: PlotData,
+    pub stats: Stats,
+    pub show_stats: bool,
+    /// Levels of the output control lines.
+    pub rts: bool,
+    pub dtr: bool,
+    pub modem_status: ModemStatus,
+    /// Duration of the break in milliseconds.
+    pub break_duration: u32,
+    /// Settings of the open port, empty when it is closed.
+    pub active_settings: String,
     pub sender: Arc<UnboundedSender<GuiMessage>>,
     pub status: String,
 }
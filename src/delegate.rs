@@ -1,6 +1,15 @@
+use crate::capture;
 use crate::data::AppData;
+use crate::event::{EXPORT_PCAPNG, EXPORT_PLOT, LOAD_LAYOUT, LOAD_PROFILE, SAVE_PROFILE};
+use crate::export;
+use crate::layout::Layout;
+use crate::pcapng;
+use crate::plot;
+use crate::profile::{self, Profile};
+use crate::GuiMessage;
 use druid::Env;
 use druid::{commands, piet::TextStorage, AppDelegate, Command, DelegateCtx, Handled, Target};
+use std::sync::Arc;
 
 pub struct Delegate;
 
@@ -14,8 +23,79 @@ impl AppDelegate<AppData> for Delegate {
         _env: &Env,
     ) -> Handled {
         if let Some(file_info) = cmd.get(commands::SAVE_FILE_AS) {
-            if let Err(e) = std::fs::write(file_info.path(), data.output.as_str()) {
-                println!("Error writing file: {}", e);
+            let path = file_info.path();
+            let content = match path.extension().and_then(|extension| extension.to_str()) {
+                Some("csv") => export::to_csv(data.transcript.iter()),
+                Some("jsonl") => export::to_json_lines(data.transcript.iter()),
+                Some("html") | Some("htm") => {
+                    export::to_html(data.output.as_str(), &data.output_attr)
+                }
+                _ => data.output.as_str().to_string(),
+            };
+            data.status = match std::fs::write(path, content) {
+                Ok(()) => format!("Output saved to {}", path.display()),
+                Err(e) => format!("Cannot save the output: {}", e),
+            };
+            return Handled::Yes;
+        }
+        if let Some(file_info) = cmd.get(commands::OPEN_FILE) {
+            data.sender
+                .unbounded_send(GuiMessage::SendFile(file_info.path().to_path_buf()))
+                .unwrap();
+            return Handled::Yes;
+        }
+        if let Some(file_info) = cmd.get(LOAD_LAYOUT) {
+            match Layout::load(file_info.path()) {
+                Ok(layout) => {
+                    data.status = format!("Frame layout {} loaded", layout.name);
+                    data.layout = Some(Arc::new(layout));
+                }
+                Err(e) => data.status = format!("Cannot load the frame layout: {}", e),
+            }
+            return Handled::Yes;
+        }
+        if let Some(file_info) = cmd.get(EXPORT_PCAPNG) {
+            let path = file_info.path().with_extension("pcapng");
+            let exported =
+                capture::read(file_info.path()).and_then(|entries| pcapng::write(&entries, &path));
+            data.status = match exported {
+                Ok(()) => format!("Capture exported to {}", path.display()),
+                Err(e) => format!("Cannot export the capture: {}", e),
+            };
+            return Handled::Yes;
+        }
+        if let Some(file_info) = cmd.get(EXPORT_PLOT) {
+            let csv = plot::to_csv(&data.plot.channels);
+            data.status = match std::fs::write(file_info.path(), csv) {
+                Ok(()) => format!("Plot exported to {}", file_info.path().display()),
+                Err(e) => format!("Cannot export the plot: {}", e),
+            };
+            return Handled::Yes;
+        }
+        if cmd.is(SAVE_PROFILE) {
+            let name = data.profiles.name.trim().to_string();
+            match Profile::from_data(data).save(&name) {
+                Ok(()) => {
+                    data.status = format!("Profile {} saved", name);
+                    data.profiles.names = Arc::new(profile::names());
+                }
+                Err(e) => data.status = format!("Cannot save the profile: {}", e),
+            }
+            return Handled::Yes;
+        }
+        if let Some(name) = cmd.get(LOAD_PROFILE) {
+            match Profile::load(name) {
+                Ok(profile) => {
+                    let applied = profile.apply(data);
+                    profile.apply_panels(data);
+                    let _ = profile::set_last_name(name);
+                    data.profiles.name = name.clone();
+                    data.status = match applied {
+                        Ok(()) => format!("Profile {} loaded", name),
+                        Err(e) => format!("Profile {} loaded: {}", name, e),
+                    };
+                }
+                Err(e) => data.status = e,
             }
             return Handled::Yes;
         }
@@ -1,29 +1,90 @@
-use crate::data::{AppData, OpenMessage, OutputTag, Protocol};
-use crate::serial::{ByteDirection, IO_DATA, IO_ERROR};
+use crate::capture::{self, CaptureWriter, Replay, REPLAY_FINISHED, REPLAY_SETTINGS};
+use crate::checksum::{Algorithm, Checksum};
+use crate::data::{
+    AppData, AtExchange, AtStatus, CanRow, Framing, LineSettings, NmeaData, NmeaRow, OpenMessage,
+    OutputTag, Protocol, ScpiData, SlcanData,
+};
+use crate::export::Chunk;
+use crate::layout::{Field, Layout};
+use crate::plot::{self, PlotFormat};
+use crate::profile::Profile;
+use crate::script::{Script, ScriptEvent, SCRIPT_MESSAGE};
+use crate::sequence::Sequence;
+use crate::serial::{
+    ByteDirection, ModemStatus, IO_BREAK, IO_CONTROL_LINES, IO_DATA, IO_ERROR, IO_LOG_STOPPED,
+    IO_MODBUS_TABLE, IO_MODEM_STATUS, IO_OPEN_ERROR, IO_SETTINGS, IO_STATS, IO_TRANSFER,
+};
+use crate::session_log::LogConfig;
+use crate::{at, mavlink, modbus, nmea, scpi, slcan};
 use bytes::Bytes;
 use druid::piet::TextStorage;
 use druid::text::{Attribute, RichText};
 use druid::{
-    BoxConstraints, Color, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
-    PaintCtx, Selector, Size, UpdateCtx,
+    BoxConstraints, Color, Data, Env, Event, EventCtx, FileInfo, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Selector, Size, TimerToken, UpdateCtx,
 };
-use std::collections::VecDeque;
+use regex::Regex;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{ops::Range, sync::Arc};
 
 use druid::Widget;
 
 pub const OPEN_PORT: Selector = Selector::new("event.open-port");
 pub const CLOSE_PORT: Selector = Selector::new("event.close-port");
+pub const RECONFIGURE_PORT: Selector = Selector::new("event.reconfigure-port");
 pub const WRITE_PORT: Selector = Selector::new("event.write-port");
 pub const CLEAR_DATA: Selector = Selector::new("event.clear-data");
+pub const APPLY_MODBUS_TABLE: Selector = Selector::new("event.apply-modbus-table");
+pub const OPEN_CAN_CHANNEL: Selector = Selector::new("event.open-can-channel");
+pub const CLOSE_CAN_CHANNEL: Selector = Selector::new("event.close-can-channel");
+pub const LOAD_LAYOUT: Selector<FileInfo> = Selector::new("event.load-layout");
+pub const RUN_SCRIPT: Selector = Selector::new("event.run-script");
+pub const STOP_SCRIPT: Selector = Selector::new("event.stop-script");
+pub const RUN_SEQUENCE: Selector<FileInfo> = Selector::new("event.run-sequence");
+pub const SAVE_PROFILE: Selector = Selector::new("event.save-profile");
+pub const LOAD_PROFILE: Selector<String> = Selector::new("event.load-profile");
+pub const TOGGLE_SESSION_LOG: Selector = Selector::new("event.toggle-session-log");
+pub const START_CAPTURE: Selector<FileInfo> = Selector::new("event.start-capture");
+pub const STOP_CAPTURE: Selector = Selector::new("event.stop-capture");
+pub const REPLAY_CAPTURE: Selector<FileInfo> = Selector::new("event.replay-capture");
+pub const STOP_REPLAY: Selector = Selector::new("event.stop-replay");
+pub const EXPORT_PCAPNG: Selector<FileInfo> = Selector::new("event.export-pcapng");
+pub const EXPORT_PLOT: Selector<FileInfo> = Selector::new("event.export-plot");
+pub const TOGGLE_RTS: Selector = Selector::new("event.toggle-rts");
+pub const TOGGLE_DTR: Selector = Selector::new("event.toggle-dtr");
+pub const SEND_BREAK: Selector = Selector::new("event.send-break");
 
 const MAX_VIEW_SIZE: usize = 1024 * 180;
+const MAX_TRANSCRIPT_CHUNKS: usize = 10_000;
+const MAX_CAN_ROWS: usize = 1000;
+const MAX_PLOT_LINE: usize = 4096;
+const MAX_AT_EXCHANGES: usize = 500;
+const MAX_SCPI_READINGS: usize = 1000;
+/// Period of the checks of the SCPI queries timeouts and error polling.
+const SCPI_TICK: Duration = Duration::from_millis(100);
+/// Period of the checks of the logs and end of the running script.
+const SCRIPT_TICK: Duration = Duration::from_millis(50);
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum GuiMessage {
     Open(OpenMessage),
     Close,
     Write(Bytes),
+    SendFile(PathBuf),
+    ModbusSlave(modbus::Slave),
+    /// Start logging the session with the config, or stop without.
+    SessionLog(Option<LogConfig>),
+    /// Level of the RTS output line.
+    Rts(bool),
+    /// Level of the DTR output line.
+    Dtr(bool),
+    /// Hold the line in the break condition for the duration.
+    Break(Duration),
+    /// Apply the settings to the open port without reopening it.
+    Reconfigure(LineSettings),
 }
 
 pub fn get_tag_color(tag: OutputTag) -> Color {
@@ -32,7 +93,490 @@ pub fn get_tag_color(tag: OutputTag) -> Color {
         OutputTag::TextOut => Color::rgb8(50, 190, 220),
         OutputTag::RawIn => Color::rgb8(25, 155, 35),
         OutputTag::RawOut => Color::rgb8(240, 160, 25),
+        OutputTag::Decoded => Color::grey(0.75),
+        OutputTag::Invalid => Color::rgb8(230, 40, 40),
+    }
+}
+
+fn spaced_hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// UTC time of the day as `hh:mm:ss.mmm`.
+fn time_of_day() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = now.as_secs() % 86400;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        now.subsec_millis()
+    )
+}
+
+/// Clear the output and the tables of the protocols.
+fn clear_output(data: &mut AppData) {
+    data.output = RichText::new("".into());
+    Arc::make_mut(&mut data.output_attr).clear();
+    Arc::make_mut(&mut data.transcript).clear();
+    Arc::make_mut(&mut data.nmea.rows).clear();
+    data.nmea.invalid = 0;
+    Arc::make_mut(&mut data.slcan.frames).clear();
+    Arc::make_mut(&mut data.at_exchanges).clear();
+    data.scpi.readings.clear();
+}
+
+/// Append `line` on its own line at the end of the output.
+fn append_line(
+    line: &str,
+    tag: OutputTag,
+    output: &mut RichText,
+    output_attr: &mut Arc<VecDeque<(Range<usize>, OutputTag)>>,
+) {
+    let curr_output = output.as_str();
+    let before_insert_len = curr_output.len();
+
+    if curr_output.is_empty() || curr_output.ends_with('\n') {
+        *output = RichText::new(format!("{}{}\n", curr_output, line).into());
+    } else {
+        *output = RichText::new(format!("{}\n{}\n", curr_output, line).into());
+    }
+
+    Arc::make_mut(output_attr).push_back((before_insert_len..output.len(), tag));
+}
+
+/// Display a whole Modbus frame followed by its decoding, `request` is the last request seen
+/// and is used to decode the responses. As a `slave` the requests are the received frames.
+pub fn display_modbus(
+    io_data: &(ByteDirection, Bytes),
+    request: Option<&[u8]>,
+    slave: bool,
+    output: &mut RichText,
+    output_attr: &mut Arc<VecDeque<(Range<usize>, OutputTag)>>,
+) {
+    let frame_tag = match io_data.0 {
+        ByteDirection::Out => OutputTag::RawOut,
+        ByteDirection::In => OutputTag::RawIn,
+    };
+    let decoded = if (io_data.0 == ByteDirection::Out) != slave {
+        modbus::describe_request(&io_data.1)
+    } else {
+        modbus::describe_response(request, &io_data.1)
+    };
+
+    append_line(&spaced_hex(&io_data.1), frame_tag, output, output_attr);
+    match decoded {
+        Ok(decoded) => append_line(&decoded, OutputTag::Decoded, output, output_attr),
+        Err(error) => append_line(&error, OutputTag::Invalid, output, output_attr),
+    }
+}
+
+/// Display a received frame on its own line, in a distinct color if its checksum is wrong.
+pub fn display_frame(
+    io_data: &(ByteDirection, Bytes),
+    checksum: &Checksum,
+    delimiter: Option<u8>,
+    output: &mut RichText,
+    output_attr: &mut Arc<VecDeque<(Range<usize>, OutputTag)>>,
+) {
+    let tag = if checksum.algorithm == Algorithm::None || checksum.verify(&io_data.1, delimiter) {
+        OutputTag::RawIn
+    } else {
+        OutputTag::Invalid
+    };
+
+    append_line(&spaced_hex(&io_data.1), tag, output, output_attr);
+}
+
+/// Display a received frame on its own line followed by its fields decoded from `layout`, or
+/// why it does not match the layout.
+pub fn display_layout(
+    io_data: &(ByteDirection, Bytes),
+    layout: &Layout,
+    output: &mut RichText,
+    output_attr: &mut Arc<VecDeque<(Range<usize>, OutputTag)>>,
+) {
+    match layout.describe(&io_data.1) {
+        Ok(fields) => {
+            append_line(
+                &spaced_hex(&io_data.1),
+                OutputTag::RawIn,
+                output,
+                output_attr,
+            );
+            for (name, value) in fields {
+                let field = format!("  {} = {}", name, value);
+                append_line(&field, OutputTag::Decoded, output, output_attr);
+            }
+        }
+        Err(error) => {
+            let frame = format!("{}  {}", spaced_hex(&io_data.1), error);
+            append_line(&frame, OutputTag::Invalid, output, output_attr);
+        }
+    }
+}
+
+/// Display a received NMEA sentence on its own line, in a distinct color if it is invalid, and
+/// update the table of the decoded fields.
+pub fn display_nmea(
+    io_data: &(ByteDirection, Bytes),
+    table: &mut NmeaData,
+    output: &mut RichText,
+    output_attr: &mut Arc<VecDeque<(Range<usize>, OutputTag)>>,
+) {
+    let line = String::from_utf8_lossy(&io_data.1);
+    let line = line.trim_end();
+    if line.is_empty() {
+        return;
+    }
+
+    let sentence = match nmea::parse(line) {
+        Ok(sentence) => sentence,
+        Err(_) => {
+            table.invalid += 1;
+            append_line(line, OutputTag::Invalid, output, output_attr);
+            return;
+        }
+    };
+    append_line(line, OutputTag::TextIn, output, output_attr);
+
+    if let Some(fields) = nmea::describe(&sentence) {
+        let id = sentence.id();
+        let rows = Arc::make_mut(&mut table.rows);
+        if !sentence.is_continuation() {
+            rows.retain(|row| row.sentence != id);
+        }
+        rows.extend(fields.into_iter().map(|(field, value)| NmeaRow {
+            sentence: id.clone(),
+            field,
+            value,
+        }));
+        // Group the rows by kind of sentence then by talker, the sort keeps the fields in order
+        rows.sort_by(|a, b| {
+            let (a_talker, a_kind) = a.sentence.split_at(a.sentence.len() - 3);
+            let (b_talker, b_kind) = b.sentence.split_at(b.sentence.len() - 3);
+            (a_kind, a_talker).cmp(&(b_kind, b_talker))
+        });
+    }
+}
+
+/// Display a MAVLink frame followed by its decoding. `sequences` holds the last sequence number
+/// received from each system and component to report the lost messages.
+pub fn display_mavlink(
+    io_data: &(ByteDirection, Bytes),
+    sequences: &mut HashMap<(u8, u8), u8>,
+    output: &mut RichText,
+    output_attr: &mut Arc<VecDeque<(Range<usize>, OutputTag)>>,
+) {
+    let frame_tag = match io_data.0 {
+        ByteDirection::Out => OutputTag::RawOut,
+        ByteDirection::In => OutputTag::RawIn,
+    };
+    append_line(&spaced_hex(&io_data.1), frame_tag, output, output_attr);
+
+    let frame = match mavlink::parse(&io_data.1) {
+        Ok(frame) => frame,
+        Err(error) => {
+            append_line(&error, OutputTag::Invalid, output, output_attr);
+            return;
+        }
+    };
+
+    if io_data.0 == ByteDirection::In {
+        let source = (frame.system, frame.component);
+        if let Some(last) = sequences.insert(source, frame.sequence) {
+            let lost = frame.sequence.wrapping_sub(last).wrapping_sub(1);
+            if lost != 0 {
+                let gap = format!(
+                    "{} messages lost from system {} component {}",
+                    lost, frame.system, frame.component
+                );
+                append_line(&gap, OutputTag::Invalid, output, output_attr);
+            }
+        }
+    }
+
+    let decoded = format!(
+        "v{}{} seq {} sys {} comp {} {}",
+        frame.version,
+        if frame.signed { " signed" } else { "" },
+        frame.sequence,
+        frame.system,
+        frame.component,
+        mavlink::describe(&frame)
+    );
+    append_line(&decoded, OutputTag::Decoded, output, output_attr);
+}
+
+/// Display the SLCAN commands sent and the answers of the adapter, the received CAN frames are
+/// also added to the frame table.
+pub fn display_slcan(
+    io_data: &(ByteDirection, Bytes),
+    table: &mut SlcanData,
+    output: &mut RichText,
+    output_attr: &mut Arc<VecDeque<(Range<usize>, OutputTag)>>,
+) {
+    let text = String::from_utf8_lossy(&io_data.1);
+
+    if io_data.0 == ByteDirection::Out {
+        for command in text.split('\r').filter(|command| !command.is_empty()) {
+            append_line(command, OutputTag::TextOut, output, output_attr);
+        }
+        return;
+    }
+
+    let line = text.trim_end_matches(|c| c == '\r' || c == '\x07');
+    if io_data.1.last() == Some(&slcan::BELL) {
+        append_line("Error", OutputTag::Invalid, output, output_attr);
+        return;
+    }
+
+    match slcan::parse_frame(line) {
+        Some(Ok(frame)) => {
+            append_line(line, OutputTag::TextIn, output, output_attr);
+
+            // The last frame received is at the top of the table
+            let frames = Arc::make_mut(&mut table.frames);
+            frames.truncate(MAX_CAN_ROWS - 1);
+            frames.insert(
+                0,
+                CanRow {
+                    timestamp: match frame.timestamp {
+                        Some(timestamp) => format!("{} ms", timestamp),
+                        None => time_of_day(),
+                    },
+                    id: if frame.extended {
+                        format!("{:08X}", frame.id)
+                    } else {
+                        format!("{:03X}", frame.id)
+                    },
+                    dlc: frame.dlc.to_string(),
+                    data: if frame.remote {
+                        "RTR".to_string()
+                    } else {
+                        spaced_hex(&frame.data)
+                    },
+                },
+            );
+        }
+        Some(Err(error)) => append_line(
+            &format!("{} ({})", line, error),
+            OutputTag::Invalid,
+            output,
+            output_attr,
+        ),
+        // Acknowledge of a command
+        None if line.is_empty() => append_line("OK", OutputTag::Decoded, output, output_attr),
+        None => append_line(line, OutputTag::TextIn, output, output_attr),
+    }
+}
+
+/// Display the AT commands and the lines received, and group each command with its response
+/// until the final result code. `started` is the time the pending command was sent.
+pub fn display_at(
+    io_data: &(ByteDirection, Bytes),
+    exchanges: &mut Arc<Vec<AtExchange>>,
+    started: &mut Option<Instant>,
+    output: &mut RichText,
+    output_attr: &mut Arc<VecDeque<(Range<usize>, OutputTag)>>,
+) {
+    let text = String::from_utf8_lossy(&io_data.1);
+    let line = text.trim();
+    if line.is_empty() {
+        return;
+    }
+    let exchanges = Arc::make_mut(exchanges);
+    let pending = exchanges
+        .last_mut()
+        .filter(|exchange| exchange.status == AtStatus::Pending);
+
+    if io_data.0 == ByteDirection::Out {
+        append_line(line, OutputTag::TextOut, output, output_attr);
+
+        if let Some(exchange) = pending {
+            exchange.status = AtStatus::Error;
+            exchange.duration = "no response".to_string();
+        }
+        push_exchange(
+            exchanges,
+            AtExchange {
+                command: line.to_string(),
+                response: "".to_string(),
+                status: AtStatus::Pending,
+                duration: "".to_string(),
+                expanded: true,
+            },
+        );
+        *started = Some(Instant::now());
+        return;
+    }
+
+    let result = at::final_result(line);
+    let tag = if result == Some(false) {
+        OutputTag::Invalid
+    } else {
+        OutputTag::TextIn
+    };
+    append_line(line, tag, output, output_attr);
+
+    match pending {
+        // The modem echoes the commands by default
+        Some(exchange) if exchange.response.is_empty() && line == exchange.command => (),
+        Some(exchange) => {
+            if !exchange.response.is_empty() {
+                exchange.response.push('\n');
+            }
+            exchange.response.push_str(line);
+
+            if let Some(success) = result {
+                exchange.status = if success {
+                    AtStatus::Ok
+                } else {
+                    AtStatus::Error
+                };
+                if let Some(started) = started.take() {
+                    exchange.duration = format!("{} ms", started.elapsed().as_millis());
+                }
+            }
+        }
+        None => push_exchange(
+            exchanges,
+            AtExchange {
+                command: "".to_string(),
+                response: line.to_string(),
+                status: AtStatus::Unsolicited,
+                duration: time_of_day(),
+                expanded: true,
+            },
+        ),
+    }
+}
+
+/// Add an exchange, the oldest is dropped once there are `MAX_AT_EXCHANGES`.
+fn push_exchange(exchanges: &mut Vec<AtExchange>, exchange: AtExchange) {
+    if exchanges.len() >= MAX_AT_EXCHANGES {
+        exchanges.remove(0);
     }
+    exchanges.push(exchange);
+}
+
+/// Display the SCPI commands and pair each response with its query. The numeric responses are
+/// added to the readings, the polled error queue is only shown when it holds an error.
+///
+/// `late` counts the responses still expected for the queries that timed out, the next
+/// responses are shown as late instead of being paired with the following queries.
+pub fn display_scpi(
+    io_data: &(ByteDirection, Bytes),
+    queries: &mut VecDeque<scpi::Query>,
+    polls: &mut usize,
+    late: &mut usize,
+    table: &mut ScpiData,
+    output: &mut RichText,
+    output_attr: &mut Arc<VecDeque<(Range<usize>, OutputTag)>>,
+) {
+    let text = String::from_utf8_lossy(&io_data.1);
+    let line = text.trim();
+    if line.is_empty() {
+        return;
+    }
+
+    if io_data.0 == ByteDirection::Out {
+        let polled = *polls > 0 && line == scpi::ERROR_QUERY;
+        if polled {
+            *polls -= 1;
+        } else {
+            append_line(line, OutputTag::TextOut, output, output_attr);
+        }
+        if scpi::is_query(line) {
+            queries.push_back(scpi::Query {
+                command: line.to_string(),
+                sent: Instant::now(),
+                polled,
+                after_late: false,
+            });
+        }
+        return;
+    }
+
+    if *late > 0 {
+        *late -= 1;
+        if let Some(query) = queries.front_mut() {
+            query.after_late = true;
+        }
+        let response = format!("Late response: {}", line);
+        append_line(&response, OutputTag::Invalid, output, output_attr);
+        return;
+    }
+
+    let query = match queries.pop_front() {
+        Some(query) => query,
+        None => {
+            append_line(line, OutputTag::TextIn, output, output_attr);
+            return;
+        }
+    };
+    if !query.polled {
+        append_line(line, OutputTag::TextIn, output, output_attr);
+    }
+
+    if query.command == scpi::IDN_QUERY {
+        table.idn = line.to_string();
+    } else if query.command == scpi::ERROR_QUERY {
+        match scpi::parse_error(line) {
+            Some((0, _)) => (),
+            Some((code, message)) => {
+                let error = format!("Instrument error {}: {}", code, message);
+                append_line(&error, OutputTag::Invalid, output, output_attr);
+            }
+            None => append_line(line, OutputTag::Invalid, output, output_attr),
+        }
+    } else if let Some(values) = scpi::parse_numbers(line) {
+        let values = values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let decoded = format!("{} = {}", query.command, values);
+        append_line(&decoded, OutputTag::Decoded, output, output_attr);
+
+        if table.readings.lines().count() >= MAX_SCPI_READINGS {
+            if let Some(end) = table.readings.find('\n') {
+                table.readings.drain(..=end);
+            }
+        }
+        table.readings += &format!("{},{},{}\n", time_of_day(), query.command, values);
+    }
+}
+
+/// Drop the SCPI queries the instrument has not answered in time, return whether one has been
+/// dropped.
+fn expire_scpi_queries(
+    queries: &mut VecDeque<scpi::Query>,
+    late: &mut usize,
+    output: &mut RichText,
+    output_attr: &mut Arc<VecDeque<(Range<usize>, OutputTag)>>,
+) -> bool {
+    let mut expired = false;
+    while let Some(query) = queries.front() {
+        if query.sent.elapsed() < scpi::TIMEOUT {
+            break;
+        }
+        // Its response may still come
+        if !query.after_late {
+            *late += 1;
+        }
+        let timeout = format!("{} timed out", query.command);
+        append_line(&timeout, OutputTag::Invalid, output, output_attr);
+        queries.pop_front();
+        expired = true;
+    }
+    expired
 }
 
 pub fn display_raw(
@@ -224,117 +768,805 @@ pub fn display_text(
     }
 }
 
-pub struct EventHandler;
+/// Delimiter ending the received frames, if they are framed on one.
+fn frame_delimiter(data: &AppData) -> Option<u8> {
+    match data.framing {
+        Framing::Delimiter => u8::try_from(data.delimiter).ok(),
+        Framing::None | Framing::Silence | Framing::Layout => None,
+    }
+}
+
+/// Whether the serial thread gives the bytes received with `config` as frames.
+fn receives_frames(config: &OpenMessage) -> bool {
+    match config.protocol {
+        Protocol::Raw => config.framing != Framing::None,
+        Protocol::Text => false,
+        Protocol::Modbus
+        | Protocol::ModbusSlave
+        | Protocol::Nmea
+        | Protocol::Mavlink
+        | Protocol::Slcan
+        | Protocol::At
+        | Protocol::Scpi => true,
+    }
+}
+
+/// Port settings edited in the GUI.
+fn open_message(data: &AppData) -> Result<OpenMessage, &'static str> {
+    let delimiter = u8::try_from(data.delimiter).map_err(|_| "Incorrect frame delimiter")?;
+    if data.protocol == Protocol::Raw && data.framing == Framing::Layout && data.layout.is_none() {
+        return Err("No frame layout loaded");
+    }
+
+    Ok(OpenMessage {
+        port_name: (*data.port_name).clone(),
+        baud_rate: data.baud_rate,
+        data_bits: data.data_bits,
+        flow_control: data.flow_control,
+        parity: data.parity,
+        stop_bits: data.stop_bits,
+        protocol: data.protocol,
+        framing: data.framing,
+        delimiter,
+        layout: data.layout.clone(),
+    })
+}
+
+/// Send the unit and table edited in the GUI to the Modbus slave.
+fn apply_modbus_table(data: &mut AppData) {
+    let unit = match u8::try_from(data.modbus.slave_unit) {
+        Ok(unit) => unit,
+        Err(_) => {
+            data.status = "Incorrect Modbus unit".to_string();
+            return;
+        }
+    };
+
+    match modbus::Table::parse(&data.modbus.table) {
+        Ok(table) => {
+            data.sender
+                .unbounded_send(GuiMessage::ModbusSlave(modbus::Slave::new(unit, table)))
+                .unwrap();
+            data.modbus.applied_table = data.modbus.table.clone();
+        }
+        Err(error) => data.status = error,
+    }
+}
+
+/// Keep the output under `MAX_VIEW_SIZE` and color it from its tags.
+fn refresh_output(data: &mut AppData) {
+    // FIXME not efficient to do this on Vec/String
+    let output_len = data.output.as_str().len();
+    if output_len > MAX_VIEW_SIZE {
+        let keep_output = data
+            .output
+            .as_str()
+            .to_string()
+            .split_off(output_len - MAX_VIEW_SIZE);
+        data.output = RichText::new(keep_output.into());
+
+        let out_attr = Arc::make_mut(&mut data.output_attr);
+
+        for attr in out_attr.iter_mut() {
+            attr.0.start = attr.0.start.saturating_sub(output_len - MAX_VIEW_SIZE);
+            attr.0.end = attr.0.end.saturating_sub(output_len - MAX_VIEW_SIZE);
+        }
+
+        loop {
+            if out_attr[0].0.start > 0 {
+                out_attr.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    for attr in data.output_attr.iter() {
+        data.output.add_attribute(
+            attr.0.clone(),
+            Attribute::text_color(get_tag_color(attr.1.clone())),
+        );
+    }
+}
+
+pub struct EventHandler {
+    modbus_request: Option<Bytes>,
+    mavlink_sequences: HashMap<(u8, u8), u8>,
+    at_started: Option<Instant>,
+    scpi_queries: VecDeque<scpi::Query>,
+    /// Error queries sent by the poller and not yet written on the port.
+    scpi_polls: usize,
+    /// Responses still expected for the SCPI queries that timed out.
+    scpi_late: usize,
+    scpi_last_poll: Instant,
+    scpi_timer: TimerToken,
+    scpi_active: bool,
+    /// The instrument is identified once the serial thread confirms the opening.
+    scpi_opening: bool,
+    script: Option<Script>,
+    script_timer: TimerToken,
+    capture: Option<CaptureWriter>,
+    replay: Option<Replay>,
+    /// Settings of the GUI, restored when the replay that changes them finishes.
+    replay_saved: Option<Profile>,
+    /// Received text not yet ended by a new line.
+    plot_line: String,
+    plot_regex: Option<Regex>,
+    /// Fields of the binary frames with the list they are parsed from.
+    plot_fields: Option<(String, Vec<Field>)>,
+    /// The open port gives its received bytes as frames, the fields are read from them.
+    plot_frames: bool,
+    plot_started: Instant,
+    open_on_start: bool,
+}
 
 impl EventHandler {
-    pub fn new() -> Self {
-        EventHandler {}
+    pub fn new(open_on_start: bool) -> Self {
+        EventHandler {
+            modbus_request: None,
+            mavlink_sequences: HashMap::new(),
+            at_started: None,
+            scpi_queries: VecDeque::new(),
+            scpi_polls: 0,
+            scpi_late: 0,
+            scpi_last_poll: Instant::now(),
+            scpi_timer: TimerToken::INVALID,
+            scpi_active: false,
+            scpi_opening: false,
+            script: None,
+            script_timer: TimerToken::INVALID,
+            capture: None,
+            replay: None,
+            replay_saved: None,
+            plot_line: String::new(),
+            plot_regex: None,
+            plot_fields: None,
+            plot_frames: false,
+            plot_started: Instant::now(),
+            open_on_start,
+        }
+    }
+
+    /// Open the port with the settings of the GUI, from its button or a script.
+    fn open_port(&mut self, data: &mut AppData) {
+        if self.replay.is_some() {
+            data.status = "Stop the replay before opening the port".to_string();
+            return;
+        }
+        let config = match open_message(data) {
+            Ok(config) => config,
+            Err(error) => {
+                data.status = error.to_string();
+                return;
+            }
+        };
+
+        self.plot_frames = receives_frames(&config);
+        data.sender
+            .unbounded_send(GuiMessage::Open(config))
+            .unwrap();
+        data.modem_status = ModemStatus::default();
+        if let Some(capture) = &mut self.capture {
+            if let Err(e) = capture.write_settings(&Profile::from_data(data)) {
+                data.status = format!("Capture stopped: {}", e);
+                self.capture = None;
+                data.capture.recording = false;
+                return;
+            }
+        }
+
+        data.status = "".to_string();
+        data.active_settings = "".to_string();
+
+        if data.protocol == Protocol::ModbusSlave {
+            apply_modbus_table(data);
+        }
+        self.mavlink_sequences.clear();
+
+        self.scpi_queries.clear();
+        self.scpi_polls = 0;
+        self.scpi_late = 0;
+        self.scpi_active = false;
+        self.scpi_opening = data.protocol == Protocol::Scpi;
+    }
+
+    fn close_port(&mut self, data: &mut AppData) {
+        self.scpi_active = false;
+        self.scpi_opening = false;
+        data.sender.unbounded_send(GuiMessage::Close).unwrap();
+        data.status = "".to_string();
+        data.active_settings = "".to_string();
+        data.modem_status = ModemStatus::default();
+    }
+
+    /// Plot the samples of the lines ended by `received`, or of the frame `received`.
+    fn plot_received(&mut self, received: &[u8], data: &mut AppData) {
+        let time = self.plot_started.elapsed().as_secs_f64();
+        if data.plot.format == PlotFormat::Fields {
+            if !self.plot_frames {
+                data.status = "Plotting frame fields needs a framing or a layout".to_string();
+                return;
+            }
+            if self.plot_fields.as_ref().map(|fields| &fields.0) != Some(&data.plot.fields) {
+                let fields = plot::parse_fields(&data.plot.fields).unwrap_or_else(|error| {
+                    data.status = error;
+                    Vec::new()
+                });
+                self.plot_fields = Some((data.plot.fields.clone(), fields));
+            }
+            let samples = match (&self.plot_fields, &data.layout) {
+                (Some((_, fields)), _) if !fields.is_empty() => plot::parse_frame(fields, received),
+                // Without a list of fields, the frames matching the layout give its fields
+                (_, Some(layout)) if layout.describe(received).is_ok() => {
+                    plot::parse_frame(&layout.fields, received)
+                }
+                _ => Vec::new(),
+            };
+            plot::add_samples(&mut data.plot.channels, time, samples);
+            return;
+        }
+
+        self.plot_line.push_str(&String::from_utf8_lossy(received));
+        if data.plot.format == PlotFormat::Regex
+            && self.plot_regex.as_ref().map(Regex::as_str) != Some(data.plot.regex.as_str())
+        {
+            self.plot_regex = Regex::new(&data.plot.regex).ok();
+        }
+
+        while let Some(end) = self.plot_line.find('\n') {
+            let line: String = self.plot_line.drain(..=end).collect();
+            let samples = plot::parse_line(data.plot.format, &line, self.plot_regex.as_ref());
+            plot::add_samples(&mut data.plot.channels, time, samples);
+        }
+        // A line without end is not text to plot
+        if self.plot_line.len() > MAX_PLOT_LINE {
+            self.plot_line.clear();
+        }
     }
 }
 
 impl Widget<AppData> for EventHandler {
-    fn event(&mut self, _ctx: &mut EventCtx, event: &Event, data: &mut AppData, _env: &Env) {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut AppData, _env: &Env) {
         match event {
             Event::Command(cmd) if cmd.is(IO_DATA) => {
                 let io_data = cmd.get_unchecked(IO_DATA);
+                if let Some(script) = &self.script {
+                    script.receive(io_data.0, &io_data.1);
+                }
+                if data.plot.show && !data.plot.paused && io_data.0 == ByteDirection::In {
+                    self.plot_received(&io_data.1, data);
+                }
+
+                let transcript = Arc::make_mut(&mut data.transcript);
+                transcript.push_back(Chunk {
+                    time: SystemTime::now(),
+                    direction: io_data.0,
+                    data: io_data.1.clone(),
+                });
+                if transcript.len() > MAX_TRANSCRIPT_CHUNKS {
+                    transcript.pop_front();
+                }
+                // The replayed data is not recorded again
+                if let (Some(capture), None) = (&mut self.capture, &self.replay) {
+                    if let Err(e) = capture.write_data(io_data.0, &io_data.1) {
+                        data.status = format!("Capture stopped: {}", e);
+                        self.capture = None;
+                        data.capture.recording = false;
+                    }
+                }
 
                 match data.protocol {
+                    Protocol::Raw
+                        if data.framing == Framing::Layout && io_data.0 == ByteDirection::In =>
+                    {
+                        if let Some(layout) = &data.layout {
+                            display_layout(io_data, layout, &mut data.output, &mut data.output_attr)
+                        }
+                    }
+                    Protocol::Raw
+                        if data.framing != Framing::None && io_data.0 == ByteDirection::In =>
+                    {
+                        display_frame(
+                            io_data,
+                            &Checksum::from(&data.checksum),
+                            frame_delimiter(data),
+                            &mut data.output,
+                            &mut data.output_attr,
+                        )
+                    }
                     Protocol::Raw => display_raw(io_data, &mut data.output, &mut data.output_attr),
                     Protocol::Text => {
                         display_text(io_data, &mut data.output, &mut data.output_attr)
                     }
+                    Protocol::Nmea if io_data.0 == ByteDirection::In => display_nmea(
+                        io_data,
+                        &mut data.nmea,
+                        &mut data.output,
+                        &mut data.output_attr,
+                    ),
+                    Protocol::Nmea => {
+                        display_text(io_data, &mut data.output, &mut data.output_attr)
+                    }
+                    Protocol::Scpi => display_scpi(
+                        io_data,
+                        &mut self.scpi_queries,
+                        &mut self.scpi_polls,
+                        &mut self.scpi_late,
+                        &mut data.scpi,
+                        &mut data.output,
+                        &mut data.output_attr,
+                    ),
+                    Protocol::At => display_at(
+                        io_data,
+                        &mut data.at_exchanges,
+                        &mut self.at_started,
+                        &mut data.output,
+                        &mut data.output_attr,
+                    ),
+                    Protocol::Slcan => display_slcan(
+                        io_data,
+                        &mut data.slcan,
+                        &mut data.output,
+                        &mut data.output_attr,
+                    ),
+                    Protocol::Mavlink => display_mavlink(
+                        io_data,
+                        &mut self.mavlink_sequences,
+                        &mut data.output,
+                        &mut data.output_attr,
+                    ),
+                    Protocol::Modbus | Protocol::ModbusSlave => {
+                        let slave = data.protocol == Protocol::ModbusSlave;
+                        if (io_data.0 == ByteDirection::Out) != slave {
+                            self.modbus_request = Some(io_data.1.clone());
+                        }
+                        display_modbus(
+                            io_data,
+                            self.modbus_request.as_deref(),
+                            slave,
+                            &mut data.output,
+                            &mut data.output_attr,
+                        )
+                    }
                 }
 
-                // FIXME not efficient to do this on Vec/String
-                let output_len = data.output.as_str().len();
-                if output_len > MAX_VIEW_SIZE {
-                    let keep_output = data
-                        .output
-                        .as_str()
-                        .to_string()
-                        .split_off(output_len - MAX_VIEW_SIZE);
-                    data.output = RichText::new(keep_output.into());
+                refresh_output(data);
+            }
+            Event::Timer(token) if *token == self.scpi_timer && self.scpi_active => {
+                let expired = expire_scpi_queries(
+                    &mut self.scpi_queries,
+                    &mut self.scpi_late,
+                    &mut data.output,
+                    &mut data.output_attr,
+                );
+                if expired {
+                    refresh_output(data);
+                }
 
-                    let out_attr = Arc::make_mut(&mut data.output_attr);
+                let idle = self.scpi_queries.is_empty() && self.scpi_polls == 0;
+                if data.scpi.poll_errors
+                    && idle
+                    && self.scpi_last_poll.elapsed() >= scpi::POLL_PERIOD
+                {
+                    self.scpi_polls += 1;
+                    self.scpi_last_poll = Instant::now();
+                    let query = format!("{}{}", scpi::ERROR_QUERY, scpi::TERMINATOR);
+                    data.sender
+                        .unbounded_send(GuiMessage::Write(query.into()))
+                        .unwrap();
+                }
 
-                    for attr in out_attr.iter_mut() {
-                        attr.0.start = attr.0.start.saturating_sub(output_len - MAX_VIEW_SIZE);
-                        attr.0.end = attr.0.end.saturating_sub(output_len - MAX_VIEW_SIZE);
+                self.scpi_timer = ctx.request_timer(SCPI_TICK);
+            }
+            Event::Timer(token) if *token == self.script_timer => {
+                if let Some(script) = &self.script {
+                    let mut finished = false;
+                    for event in script.events() {
+                        match event {
+                            ScriptEvent::Log(message) => append_line(
+                                &message,
+                                OutputTag::Decoded,
+                                &mut data.output,
+                                &mut data.output_attr,
+                            ),
+                            ScriptEvent::Finished(result) => {
+                                data.status = match result {
+                                    Ok(message) | Err(message) => message,
+                                };
+                                finished = true;
+                            }
+                        }
+                    }
+                    refresh_output(data);
+
+                    if finished {
+                        self.script = None;
+                        data.script.running = false;
+                    } else {
+                        self.script_timer = ctx.request_timer(SCRIPT_TICK);
+                    }
+                }
+            }
+            Event::Command(cmd) if cmd.is(RUN_SCRIPT) => {
+                if self.script.is_some() {
+                    data.status = "A script is already running".to_string();
+                    return;
+                }
+                let config = match open_message(data) {
+                    Ok(config) => config,
+                    Err(error) => {
+                        data.status = error.to_string();
+                        return;
+                    }
+                };
+
+                self.script = Some(Script::run(
+                    data.script.source.clone(),
+                    config,
+                    ctx.get_external_handle(),
+                ));
+                data.script.running = true;
+                data.status = "Script running".to_string();
+                self.script_timer = ctx.request_timer(SCRIPT_TICK);
+            }
+            Event::Command(cmd) if cmd.is(RUN_SEQUENCE) => {
+                if self.script.is_some() {
+                    data.status = "A script is already running".to_string();
+                    return;
+                }
+                let path = cmd.get_unchecked(RUN_SEQUENCE).path().to_path_buf();
+                let sequence = match Sequence::load(&path) {
+                    Ok(sequence) => sequence,
+                    Err(error) => {
+                        data.status = format!("Cannot load the test sequence: {}", error);
+                        return;
                     }
+                };
 
-                    loop {
-                        if out_attr[0].0.start > 0 {
-                            out_attr.pop_front();
+                let report_path = sequence.report_path(&path);
+                data.status = format!("Test sequence {} running", sequence.name);
+                self.script = Some(Script::spawn(
+                    ctx.get_external_handle(),
+                    move |mut session| {
+                        let report = sequence.run(&mut session);
+                        report.write(sequence.report, &report_path)?;
+                        let summary = format!(
+                            "{}: {} passed, {} failed, {} skipped, report in {}",
+                            sequence.name,
+                            report.passed,
+                            report.failed,
+                            report.skipped,
+                            report_path.display()
+                        );
+                        if report.failed == 0 {
+                            Ok(summary)
                         } else {
-                            break;
+                            Err(summary)
                         }
+                    },
+                ));
+                data.script.running = true;
+                self.script_timer = ctx.request_timer(SCRIPT_TICK);
+            }
+            Event::Command(cmd) if cmd.is(TOGGLE_SESSION_LOG) => {
+                let log = &mut data.session_log;
+                let config = if log.active {
+                    data.status = "Session logging stopped".to_string();
+                    None
+                } else if log.template.trim().is_empty() {
+                    data.status = "Missing log file name".to_string();
+                    return;
+                } else {
+                    data.status = format!("Logging the session in {}", log.directory);
+                    Some(LogConfig {
+                        directory: PathBuf::from(&log.directory),
+                        template: log.template.trim().to_string(),
+                        max_size: Some(log.max_size as u64 * 1024 * 1024)
+                            .filter(|&max_size| max_size > 0),
+                        period: Some(Duration::from_secs(log.period as u64 * 60))
+                            .filter(|period| !period.is_zero()),
+                    })
+                };
+                log.active = config.is_some();
+                data.sender
+                    .unbounded_send(GuiMessage::SessionLog(config))
+                    .unwrap();
+            }
+            Event::Command(cmd) if cmd.is(START_CAPTURE) => {
+                let path = cmd.get_unchecked(START_CAPTURE).path();
+                match CaptureWriter::create(path, &Profile::from_data(data)) {
+                    Ok(capture) => {
+                        self.capture = Some(capture);
+                        data.capture.recording = true;
+                        data.status = format!("Recording the capture {}", path.display());
                     }
+                    Err(e) => data.status = format!("Cannot create the capture: {}", e),
                 }
-
-                for attr in data.output_attr.iter() {
-                    data.output.add_attribute(
-                        attr.0.clone(),
-                        Attribute::text_color(get_tag_color(attr.1.clone())),
-                    );
+            }
+            Event::Command(cmd) if cmd.is(STOP_CAPTURE) => {
+                self.capture = None;
+                data.capture.recording = false;
+                data.status = "Capture stopped".to_string();
+            }
+            Event::Command(cmd) if cmd.is(REPLAY_CAPTURE) => {
+                if self.replay.is_some() {
+                    data.status = "A capture is already replayed".to_string();
+                    return;
+                }
+                // The replayed data would be mixed with the received one
+                if !data.active_settings.is_empty() {
+                    data.status = "Close the port before replaying a capture".to_string();
+                    return;
+                }
+                let path = cmd.get_unchecked(REPLAY_CAPTURE).path();
+                match capture::read(path) {
+                    Ok(entries) => {
+                        clear_output(data);
+                        self.replay = Some(Replay::start(
+                            entries,
+                            data.capture.speed.factor(),
+                            ctx.get_external_handle(),
+                        ));
+                        self.replay_saved = Some(Profile::from_data(data));
+                        data.capture.replaying = true;
+                        data.status = format!("Replaying {}", path.display());
+                    }
+                    Err(e) => data.status = format!("Cannot read the capture: {}", e),
+                }
+            }
+            Event::Command(cmd) if cmd.is(REPLAY_SETTINGS) => {
+                if let Err(e) = cmd.get_unchecked(REPLAY_SETTINGS).apply(data) {
+                    data.status = e;
                 }
             }
-            Event::Command(cmd) if cmd.is(OPEN_PORT) => {
+            Event::Command(cmd) if cmd.is(STOP_REPLAY) => {
+                if let Some(replay) = &self.replay {
+                    replay.stop();
+                }
+            }
+            Event::Command(cmd) if cmd.is(REPLAY_FINISHED) => {
+                self.replay = None;
+                data.capture.replaying = false;
+                data.status = "Replay finished".to_string();
+                if let Some(saved) = self.replay_saved.take() {
+                    if let Err(e) = saved.apply(data) {
+                        data.status = format!("Replay finished: {}", e);
+                    }
+                }
+            }
+            Event::Command(cmd) if cmd.is(STOP_SCRIPT) => {
+                if let Some(script) = &self.script {
+                    script.stop();
+                }
+            }
+            Event::Command(cmd) if cmd.is(OPEN_PORT) => self.open_port(data),
+            Event::Command(cmd) if cmd.is(CLOSE_PORT) => self.close_port(data),
+            Event::Command(cmd) if cmd.is(SCRIPT_MESSAGE) => {
+                match cmd.get_unchecked(SCRIPT_MESSAGE).take() {
+                    // The script opens the port with its own name and baud rate
+                    Some(GuiMessage::Open(config)) => {
+                        data.port_name = Arc::new(config.port_name);
+                        data.baud_rate = config.baud_rate;
+                        self.open_port(data);
+                    }
+                    Some(GuiMessage::Close) => self.close_port(data),
+                    Some(msg) => data.sender.unbounded_send(msg).unwrap(),
+                    None => (),
+                }
+            }
+            Event::Command(cmd) if cmd.is(RECONFIGURE_PORT) => {
+                let settings = LineSettings {
+                    baud_rate: data.baud_rate,
+                    data_bits: data.data_bits,
+                    flow_control: data.flow_control,
+                    parity: data.parity,
+                    stop_bits: data.stop_bits,
+                };
                 data.sender
-                    .unbounded_send(GuiMessage::Open(OpenMessage {
-                        port_name: (*data.port_name).clone(),
-                        baud_rate: data.baud_rate,
-                        data_bits: data.data_bits,
-                        flow_control: data.flow_control,
-                        parity: data.parity,
-                        stop_bits: data.stop_bits,
-                        protocol: data.protocol,
-                    }))
+                    .unbounded_send(GuiMessage::Reconfigure(settings))
+                    .unwrap();
+            }
+            Event::Command(cmd) if cmd.is(IO_SETTINGS) => {
+                data.active_settings = cmd.get_unchecked(IO_SETTINGS).clone();
+                if self.scpi_opening {
+                    // Identify the instrument
+                    self.scpi_opening = false;
+                    self.scpi_active = true;
+                    let query = format!("{}{}", scpi::IDN_QUERY, scpi::TERMINATOR);
+                    data.sender
+                        .unbounded_send(GuiMessage::Write(query.into()))
+                        .unwrap();
+                    self.scpi_timer = ctx.request_timer(SCPI_TICK);
+                }
+            }
+            Event::Command(cmd) if cmd.is(TOGGLE_RTS) => {
+                // Shown once the serial thread has applied it
+                data.sender
+                    .unbounded_send(GuiMessage::Rts(!data.rts))
                     .unwrap();
-
-                data.status = format!(
-                    "{}, {}, {}, {}, {}",
-                    (*data.port_name).clone(),
-                    data.baud_rate,
-                    data.flow_control,
-                    data.parity,
-                    data.stop_bits
-                );
             }
-            Event::Command(cmd) if cmd.is(CLOSE_PORT) => {
-                data.sender.unbounded_send(GuiMessage::Close).unwrap();
-                data.status = "".to_string();
+            Event::Command(cmd) if cmd.is(TOGGLE_DTR) => {
+                data.sender
+                    .unbounded_send(GuiMessage::Dtr(!data.dtr))
+                    .unwrap();
             }
             Event::Command(cmd) if cmd.is(WRITE_PORT) => match data.protocol {
                 Protocol::Raw => {
                     let bytes: String = data.to_write.as_str().split_ascii_whitespace().collect();
                     if let Ok(bytes) = hex::decode(bytes) {
-                        data.sender
-                            .unbounded_send(GuiMessage::Write(bytes.into()))
-                            .unwrap();
+                        let checksum = Checksum::from(&data.checksum);
+                        let bytes = if checksum.algorithm == Algorithm::None {
+                            Ok(bytes)
+                        } else {
+                            checksum.append(&bytes, frame_delimiter(data))
+                        };
+
+                        match bytes {
+                            Ok(bytes) => data
+                                .sender
+                                .unbounded_send(GuiMessage::Write(bytes.into()))
+                                .unwrap(),
+                            Err(error) => data.status = error.to_string(),
+                        }
                     } else {
                         data.status = "Incorrect data doesn't respect protocol format".to_string();
                     }
                 }
-                Protocol::Text => {
-                    let bytes = data.to_write.as_bytes().to_owned();
+                Protocol::Text | Protocol::Nmea => {
+                    let text = format!("{}{}", data.to_write, data.line_ending.as_str());
+                    let bytes = text.into_bytes();
                     data.sender
                         .unbounded_send(GuiMessage::Write(bytes.into()))
                         .unwrap();
                 }
+                Protocol::Scpi => {
+                    let command = format!("{}{}", data.to_write.trim_end(), scpi::TERMINATOR);
+                    data.sender
+                        .unbounded_send(GuiMessage::Write(command.into()))
+                        .unwrap();
+                }
+                Protocol::At => {
+                    let command = format!("{}{}", data.to_write.trim_end(), at::TERMINATOR);
+                    data.sender
+                        .unbounded_send(GuiMessage::Write(command.into()))
+                        .unwrap();
+                }
+                Protocol::Slcan => {
+                    let form = &data.slcan;
+                    let bytes: String = form.data.split_ascii_whitespace().collect();
+                    let frame = hex::decode(bytes)
+                        .map_err(|_| "Incorrect CAN data")
+                        .and_then(|bytes| {
+                            let id = u32::from_str_radix(form.id.trim(), 16)
+                                .map_err(|_| "Incorrect CAN identifier")?;
+                            let dlc = u8::try_from(form.dlc).map_err(|_| "Incorrect DLC")?;
+                            slcan::CanFrame::new(id, form.extended, form.remote, dlc, bytes)
+                        });
+
+                    match frame {
+                        Ok(frame) => data
+                            .sender
+                            .unbounded_send(GuiMessage::Write(frame.encode().into()))
+                            .unwrap(),
+                        Err(error) => data.status = error.to_string(),
+                    }
+                }
+                Protocol::Mavlink => {
+                    let bytes: String = data.to_write.as_str().split_ascii_whitespace().collect();
+                    match hex::decode(bytes) {
+                        Ok(bytes) => data
+                            .sender
+                            .unbounded_send(GuiMessage::Write(bytes.into()))
+                            .unwrap(),
+                        Err(_) => {
+                            data.status =
+                                "Incorrect data doesn't respect protocol format".to_string()
+                        }
+                    }
+                }
+                Protocol::Modbus => {
+                    let form = &data.modbus;
+                    let request = modbus::parse_values(&form.values).and_then(|values| {
+                        let unit = u8::try_from(form.unit).map_err(|_| "Incorrect Modbus unit")?;
+                        let address =
+                            u16::try_from(form.address).map_err(|_| "Incorrect Modbus address")?;
+                        let quantity = u16::try_from(form.quantity)
+                            .map_err(|_| "Incorrect Modbus quantity")?;
+                        modbus::build_request(unit, form.function, address, quantity, &values)
+                    });
+
+                    match request {
+                        Ok(bytes) => data
+                            .sender
+                            .unbounded_send(GuiMessage::Write(bytes.into()))
+                            .unwrap(),
+                        Err(error) => data.status = error.to_string(),
+                    }
+                }
+                // The slave only answers to the requests received
+                Protocol::ModbusSlave => (),
             },
+            Event::Command(cmd) if cmd.is(IO_OPEN_ERROR) => {
+                // Nothing to query without a port
+                self.scpi_active = false;
+                self.scpi_opening = false;
+            }
             Event::Command(cmd) if cmd.is(IO_ERROR) => {
                 let error_msg = cmd.get_unchecked(IO_ERROR);
+                if let Some(script) = &self.script {
+                    script.error(error_msg);
+                }
                 data.status = error_msg.to_string();
             }
-            Event::Command(cmd) if cmd.is(CLEAR_DATA) => {
-                data.output = RichText::new("".into());
-                Arc::make_mut(&mut data.output_attr).clear();
+            Event::Command(cmd) if cmd.is(IO_LOG_STOPPED) => {
+                data.session_log.active = false;
+                data.status = "Cannot write the session log, logging stopped".to_string();
+            }
+            Event::Command(cmd) if cmd.is(IO_TRANSFER) => {
+                data.status = cmd.get_unchecked(IO_TRANSFER).clone();
             }
+            Event::Command(cmd) if cmd.is(APPLY_MODBUS_TABLE) => apply_modbus_table(data),
+            Event::Command(cmd) if cmd.is(IO_STATS) => {
+                data.stats = cmd.get_unchecked(IO_STATS).clone();
+            }
+            Event::Command(cmd) if cmd.is(SEND_BREAK) => {
+                let duration = Duration::from_millis(data.break_duration as u64);
+                data.sender
+                    .unbounded_send(GuiMessage::Break(duration))
+                    .unwrap();
+            }
+            Event::Command(cmd) if cmd.is(IO_BREAK) => {
+                let duration = cmd.get_unchecked(IO_BREAK);
+                let marker = format!("BREAK {} ms", duration.as_millis());
+                append_line(
+                    &marker,
+                    OutputTag::Decoded,
+                    &mut data.output,
+                    &mut data.output_attr,
+                );
+                refresh_output(data);
+            }
+            Event::Command(cmd) if cmd.is(IO_CONTROL_LINES) => {
+                let lines = cmd.get_unchecked(IO_CONTROL_LINES);
+                data.rts = lines.rts;
+                data.dtr = lines.dtr;
+            }
+            Event::Command(cmd) if cmd.is(IO_MODEM_STATUS) => {
+                data.modem_status = *cmd.get_unchecked(IO_MODEM_STATUS);
+            }
+            Event::Command(cmd) if cmd.is(IO_MODBUS_TABLE) => {
+                let table = cmd.get_unchecked(IO_MODBUS_TABLE).to_string();
+                // Keep the edits not applied yet
+                if data.modbus.table == data.modbus.applied_table {
+                    data.modbus.table = table.clone();
+                } else {
+                    data.status =
+                        "The slave table changed, applying the edits overwrites it".to_string();
+                }
+                data.modbus.applied_table = table;
+            }
+            Event::Command(cmd) if cmd.is(OPEN_CAN_CHANNEL) => {
+                let commands = slcan::open_commands(data.slcan.bitrate, data.slcan.timestamps);
+                data.sender
+                    .unbounded_send(GuiMessage::Write(commands.into()))
+                    .unwrap();
+            }
+            Event::Command(cmd) if cmd.is(CLOSE_CAN_CHANNEL) => {
+                data.sender
+                    .unbounded_send(GuiMessage::Write(slcan::close_command().into()))
+                    .unwrap();
+            }
+            Event::Command(cmd) if cmd.is(CLEAR_DATA) => clear_output(data),
             _ => {}
         }
     }
 
-    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &AppData, _: &Env) {
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &AppData, _: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            if self.open_on_start {
+                ctx.submit_command(OPEN_PORT);
+            }
+        }
     }
 
     fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppData, data: &AppData, _: &Env) {
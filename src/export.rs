@@ -0,0 +1,112 @@
+//! Export of the session as CSV, JSON Lines or a colored HTML transcript.
+
+use crate::data::OutputTag;
+use crate::event::get_tag_color;
+use crate::serial::ByteDirection;
+use crate::session_log::timestamp;
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::time::SystemTime;
+
+/// Bytes written or received, as given to the display.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub time: SystemTime,
+    pub direction: ByteDirection,
+    pub data: Bytes,
+}
+
+impl Chunk {
+    fn direction(&self) -> &'static str {
+        match self.direction {
+            ByteDirection::In => "in",
+            ByteDirection::Out => "out",
+        }
+    }
+
+    fn hex(&self) -> String {
+        self.data
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.data).to_string()
+    }
+}
+
+/// A row by chunk with its time, direction, bytes in hexadecimal and text.
+pub fn to_csv<'a>(chunks: impl IntoIterator<Item = &'a Chunk>) -> String {
+    let mut csv = String::from("timestamp,direction,hex,text\n");
+    for chunk in chunks {
+        csv.push_str(&format!(
+            "{},{},{},\"{}\"\n",
+            timestamp(chunk.time),
+            chunk.direction(),
+            chunk.hex(),
+            chunk.text().replace('"', "\"\"")
+        ));
+    }
+    csv
+}
+
+/// A JSON object by line with the same fields as the CSV.
+pub fn to_json_lines<'a>(chunks: impl IntoIterator<Item = &'a Chunk>) -> String {
+    chunks
+        .into_iter()
+        .map(|chunk| {
+            let line = serde_json::json!({
+                "timestamp": timestamp(chunk.time),
+                "direction": chunk.direction(),
+                "hex": chunk.hex(),
+                "text": chunk.text(),
+            });
+            format!("{}\n", line)
+        })
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The output with the colors of its tags, as shown in the GUI.
+pub fn to_html(output: &str, tags: &VecDeque<(Range<usize>, OutputTag)>) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Stool session</title>\n\
+         </head>\n<body style=\"background: #1a1a1a; color: #f0f0ea\">\n<pre>",
+    );
+
+    let mut position = 0;
+    for (range, tag) in tags {
+        // Skip the tags cut by the truncation of the output
+        if range.start < position || range.end <= range.start {
+            continue;
+        }
+        let (before, tagged) = match (output.get(position..range.start), output.get(range.clone()))
+        {
+            (Some(before), Some(tagged)) => (before, tagged),
+            _ => continue,
+        };
+        let (red, green, blue, _) = get_tag_color(tag.clone()).as_rgba8();
+        html.push_str(&escape_html(before));
+        html.push_str(&format!(
+            "<span style=\"color: #{:02x}{:02x}{:02x}\">{}</span>",
+            red,
+            green,
+            blue,
+            escape_html(tagged)
+        ));
+        position = range.end;
+    }
+    html.push_str(&escape_html(output.get(position..).unwrap_or_default()));
+
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
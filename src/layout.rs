@@ -0,0 +1,522 @@
+//! Frame layouts described in a TOML file, used to split the received bytes into frames and
+//! to decode their fields.
+//!
+//! ```toml
+//! name = "Telemetry"
+//! sync = "AA 55"
+//!
+//! [length]
+//! offset = 2
+//! size = 1
+//! adjust = 5          # sync, length and checksum bytes not counted by the field
+//!
+//! [checksum]
+//! algorithm = "crc16-modbus"
+//! endianness = "little"
+//! skip_start = 2
+//!
+//! [[fields]]
+//! name = "temperature"
+//! type = "i16"
+//! offset = 3
+//! endianness = "little"
+//! ```
+
+use crate::checksum::{Algorithm, Checksum, Endianness};
+use serde::Deserialize;
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+    Bytes,
+    Ascii,
+}
+
+impl FromStr for FieldType {
+    type Err = String;
+
+    /// Names of the layout files, or the longer `int16`, `uint32`, `float32`...
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "u8" | "uint8" => Ok(FieldType::U8),
+            "i8" | "int8" => Ok(FieldType::I8),
+            "u16" | "uint16" => Ok(FieldType::U16),
+            "i16" | "int16" => Ok(FieldType::I16),
+            "u32" | "uint32" => Ok(FieldType::U32),
+            "i32" | "int32" => Ok(FieldType::I32),
+            "u64" | "uint64" => Ok(FieldType::U64),
+            "i64" | "int64" => Ok(FieldType::I64),
+            "f32" | "float32" => Ok(FieldType::F32),
+            "f64" | "float64" => Ok(FieldType::F64),
+            "bytes" => Ok(FieldType::Bytes),
+            "ascii" => Ok(FieldType::Ascii),
+            _ => Err(format!("Unknown field type {}", name)),
+        }
+    }
+}
+
+impl FieldType {
+    /// Size of the numeric types, the others take their size from the field.
+    fn size(self) -> Option<usize> {
+        match self {
+            FieldType::U8 | FieldType::I8 => Some(1),
+            FieldType::U16 | FieldType::I16 => Some(2),
+            FieldType::U32 | FieldType::I32 | FieldType::F32 => Some(4),
+            FieldType::U64 | FieldType::I64 | FieldType::F64 => Some(8),
+            FieldType::Bytes | FieldType::Ascii => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: FieldType,
+    /// Position of the field from the start of the frame.
+    pub offset: usize,
+    /// Size of the `bytes` and `ascii` fields, they end before the checksum when it is missing.
+    pub size: Option<usize>,
+    #[serde(default = "big_endian")]
+    pub endianness: Endianness,
+}
+
+/// Field giving the size of the frame.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LengthField {
+    pub offset: usize,
+    /// Size of the field, from 1 to 4 bytes.
+    pub size: usize,
+    #[serde(default = "big_endian")]
+    pub endianness: Endianness,
+    /// Added to the value of the field to get the size of the whole frame.
+    #[serde(default)]
+    pub adjust: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChecksumField {
+    algorithm: Algorithm,
+    #[serde(default = "big_endian")]
+    endianness: Endianness,
+    #[serde(default)]
+    skip_start: usize,
+    #[serde(default)]
+    skip_end: usize,
+}
+
+fn big_endian() -> Endianness {
+    Endianness::Big
+}
+
+/// Content of a layout file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LayoutFile {
+    name: Option<String>,
+    /// Bytes starting every frame, in hexadecimal.
+    #[serde(default)]
+    sync: String,
+    /// Size of the frames when they have no length field.
+    size: Option<usize>,
+    length: Option<LengthField>,
+    checksum: Option<ChecksumField>,
+    #[serde(default)]
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameSize {
+    Fixed(usize),
+    Field(LengthField),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    pub name: String,
+    pub sync: Vec<u8>,
+    pub size: FrameSize,
+    /// Checksum at the end of the frame.
+    pub checksum: Option<Checksum>,
+    pub fields: Vec<Field>,
+    /// File the layout was read from, kept in the profiles.
+    pub path: Option<PathBuf>,
+}
+
+impl Layout {
+    /// Read a layout file, the layout is named after the file when it has no name.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut layout = Layout::parse(&text)?;
+        if layout.name.is_empty() {
+            if let Some(stem) = path.file_stem() {
+                layout.name = stem.to_string_lossy().to_string();
+            }
+        }
+        layout.path = Some(path.to_path_buf());
+        Ok(layout)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: LayoutFile = toml::from_str(text).map_err(|e| e.to_string())?;
+
+        let sync = hex::decode(file.sync.replace(' ', ""))
+            .map_err(|_| "Incorrect sync bytes".to_string())?;
+        let size = match (file.size, file.length) {
+            (Some(0), None) => return Err("Incorrect frame size".to_string()),
+            (Some(size), None) => FrameSize::Fixed(size),
+            (None, Some(length)) if length.size == 0 || length.size > 4 => {
+                return Err("The length field takes 1 to 4 bytes".to_string())
+            }
+            (None, Some(length)) => FrameSize::Field(length),
+            _ => return Err("A layout needs either a frame size or a length field".to_string()),
+        };
+        let checksum = file.checksum.map(|checksum| Checksum {
+            algorithm: checksum.algorithm,
+            endianness: checksum.endianness,
+            skip_start: checksum.skip_start,
+            skip_end: checksum.skip_end,
+        });
+
+        Ok(Layout {
+            name: file.name.unwrap_or_default(),
+            sync,
+            size,
+            checksum,
+            fields: file.fields,
+            path: None,
+        })
+    }
+
+    /// Size of the frame starting `buf`, `None` while its length field is not received and
+    /// `Some(Err)` when the length field is not possible.
+    fn frame_len(&self, buf: &[u8]) -> Option<Result<usize, ()>> {
+        let length = match &self.size {
+            FrameSize::Fixed(size) => return Some(Ok(*size)),
+            FrameSize::Field(length) => length,
+        };
+
+        let value = read_uint(
+            buf.get(length.offset..length.offset + length.size)?,
+            length.endianness,
+        );
+        let len = value as i64 + length.adjust;
+        let checksum_width = self
+            .checksum
+            .map_or(0, |checksum| checksum.algorithm.width());
+        let min_len = (length.offset + length.size).max(self.sync.len()) + checksum_width;
+        if len < min_len as i64 {
+            Some(Err(()))
+        } else {
+            Some(Ok(len as usize))
+        }
+    }
+
+    /// Number of bytes at the start of `buf` making a frame, or some bytes to give alone as
+    /// they are not the start of a frame. `None` when more bytes are needed.
+    pub fn split(&self, buf: &[u8]) -> Option<usize> {
+        if !self.sync.is_empty() {
+            match buf
+                .windows(self.sync.len())
+                .position(|window| window == self.sync.as_slice())
+            {
+                Some(0) => (),
+                Some(start) => return Some(start),
+                // Keep the end which may be the beginning of the sync bytes
+                None => {
+                    let garbage = buf.len().saturating_sub(self.sync.len() - 1);
+                    return if garbage == 0 { None } else { Some(garbage) };
+                }
+            }
+        }
+
+        // Without sync bytes a wrong frame cannot be skipped, the whole buffer is given
+        let resync = if self.sync.is_empty() { buf.len() } else { 1 };
+        match self.frame_len(buf)? {
+            Err(()) => Some(resync),
+            Ok(len) if buf.len() >= len => match self.checksum {
+                Some(checksum) if !checksum.verify(&buf[..len], None) => Some(resync.min(len)),
+                _ => Some(len),
+            },
+            Ok(_) => None,
+        }
+    }
+
+    /// Name and value of each field of `frame`, or why the frame does not match the layout.
+    pub fn describe(&self, frame: &[u8]) -> Result<Vec<(String, String)>, &'static str> {
+        if !frame.starts_with(&self.sync) {
+            return Err("Wrong sync bytes");
+        }
+        match self.frame_len(frame) {
+            Some(Ok(len)) if len == frame.len() => (),
+            _ => return Err("Wrong length"),
+        }
+        if let Some(checksum) = self.checksum {
+            if !checksum.verify(frame, None) {
+                return Err("Wrong checksum");
+            }
+        }
+
+        let data_end = frame.len()
+            - self
+                .checksum
+                .map_or(0, |checksum| checksum.algorithm.width());
+        Ok(self
+            .fields
+            .iter()
+            .map(|field| {
+                let value = read_field(field, &frame[..data_end])
+                    .unwrap_or_else(|| "out of the frame".to_string());
+                (field.name.clone(), value)
+            })
+            .collect())
+    }
+}
+
+fn read_uint(bytes: &[u8], endianness: Endianness) -> u64 {
+    let fold = |value: u64, &byte: &u8| value << 8 | byte as u64;
+    match endianness {
+        Endianness::Big => bytes.iter().fold(0, fold),
+        Endianness::Little => bytes.iter().rev().fold(0, fold),
+    }
+}
+
+fn read_field(field: &Field, data: &[u8]) -> Option<String> {
+    let end = match (field.kind.size(), field.size) {
+        (Some(size), _) | (None, Some(size)) => field.offset.checked_add(size)?,
+        (None, None) => data.len(),
+    };
+    let bytes = data.get(field.offset..end)?;
+
+    let mut ordered = bytes.to_vec();
+    if field.endianness == Endianness::Little {
+        ordered.reverse();
+    }
+    let value = match field.kind {
+        FieldType::U8 => ordered[0].to_string(),
+        FieldType::I8 => (ordered[0] as i8).to_string(),
+        FieldType::U16 => u16::from_be_bytes(ordered.try_into().ok()?).to_string(),
+        FieldType::I16 => i16::from_be_bytes(ordered.try_into().ok()?).to_string(),
+        FieldType::U32 => u32::from_be_bytes(ordered.try_into().ok()?).to_string(),
+        FieldType::I32 => i32::from_be_bytes(ordered.try_into().ok()?).to_string(),
+        FieldType::U64 => u64::from_be_bytes(ordered.try_into().ok()?).to_string(),
+        FieldType::I64 => i64::from_be_bytes(ordered.try_into().ok()?).to_string(),
+        FieldType::F32 => f32::from_be_bytes(ordered.try_into().ok()?).to_string(),
+        FieldType::F64 => f64::from_be_bytes(ordered.try_into().ok()?).to_string(),
+        FieldType::Bytes => bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(" "),
+        FieldType::Ascii => String::from_utf8_lossy(bytes)
+            .trim_end_matches('\0')
+            .to_string(),
+    };
+    Some(value)
+}
+
+/// Value of a numeric field in `data`, `None` for the other fields or when it is out of `data`.
+pub fn read_number(field: &Field, data: &[u8]) -> Option<f64> {
+    let size = field.kind.size()?;
+    let end = field.offset.checked_add(size)?;
+    let mut ordered = data.get(field.offset..end)?.to_vec();
+    if field.endianness == Endianness::Little {
+        ordered.reverse();
+    }
+    let value = match field.kind {
+        FieldType::U8 => ordered[0] as f64,
+        FieldType::I8 => ordered[0] as i8 as f64,
+        FieldType::U16 => u16::from_be_bytes(ordered.try_into().ok()?) as f64,
+        FieldType::I16 => i16::from_be_bytes(ordered.try_into().ok()?) as f64,
+        FieldType::U32 => u32::from_be_bytes(ordered.try_into().ok()?) as f64,
+        FieldType::I32 => i32::from_be_bytes(ordered.try_into().ok()?) as f64,
+        FieldType::U64 => u64::from_be_bytes(ordered.try_into().ok()?) as f64,
+        FieldType::I64 => i64::from_be_bytes(ordered.try_into().ok()?) as f64,
+        FieldType::F32 => f32::from_be_bytes(ordered.try_into().ok()?) as f64,
+        FieldType::F64 => f64::from_be_bytes(ordered.try_into().ok()?),
+        FieldType::Bytes | FieldType::Ascii => return None,
+    };
+    Some(value).filter(|value| value.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TELEMETRY: &str = r#"
+        sync = "AA 55"
+
+        [length]
+        offset = 2
+        size = 1
+        adjust = 3
+
+        [checksum]
+        algorithm = "sum8"
+        skip_start = 2
+
+        [[fields]]
+        name = "value"
+        type = "u16"
+        offset = 3
+        endianness = "little"
+
+        [[fields]]
+        name = "missing"
+        type = "u8"
+        offset = 5
+    "#;
+
+    const FRAME: [u8; 6] = [0xAA, 0x55, 0x03, 0x34, 0x12, 0x49];
+
+    #[test]
+    fn split_frames() {
+        let layout = Layout::parse(TELEMETRY).unwrap();
+        assert_eq!(layout.split(&FRAME), Some(6));
+        assert_eq!(layout.split(&[&FRAME[..], &[0xAA]].concat()), Some(6));
+        assert_eq!(layout.split(&FRAME[..2]), None);
+        assert_eq!(layout.split(&FRAME[..5]), None);
+    }
+
+    #[test]
+    fn split_garbage() {
+        let layout = Layout::parse(TELEMETRY).unwrap();
+        assert_eq!(layout.split(&[&[0x00, 0x01], &FRAME[..]].concat()), Some(2));
+        assert_eq!(layout.split(&[0x00, 0x01, 0x02]), Some(2));
+        assert_eq!(layout.split(&[0x01, 0xAA]), Some(1));
+        assert_eq!(layout.split(&[0xAA]), None);
+        // Length below the header and checksum, then wrong checksum
+        assert_eq!(layout.split(&[0xAA, 0x55, 0x00, 0x00]), Some(1));
+        assert_eq!(layout.split(&[0xAA, 0x55, 0x03, 0x34, 0x12, 0x00]), Some(1));
+    }
+
+    #[test]
+    fn split_fixed_size() {
+        let layout = Layout::parse("size = 4").unwrap();
+        assert_eq!(layout.split(&[1, 2, 3]), None);
+        assert_eq!(layout.split(&[1, 2, 3, 4, 5]), Some(4));
+    }
+
+    #[test]
+    fn describe_frame() {
+        let layout = Layout::parse(TELEMETRY).unwrap();
+        assert_eq!(
+            layout.describe(&FRAME),
+            Ok(vec![
+                ("value".to_string(), "4660".to_string()),
+                ("missing".to_string(), "out of the frame".to_string()),
+            ])
+        );
+        assert_eq!(layout.describe(&FRAME[1..]), Err("Wrong sync bytes"));
+        assert_eq!(layout.describe(&FRAME[..5]), Err("Wrong length"));
+        let mut corrupted = FRAME;
+        corrupted[3] = 0;
+        assert_eq!(layout.describe(&corrupted), Err("Wrong checksum"));
+    }
+
+    fn field(kind: FieldType, offset: usize, endianness: Endianness) -> Field {
+        Field {
+            name: String::new(),
+            kind,
+            offset,
+            size: None,
+            endianness,
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        let data = [0xFF, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08];
+        let checks = [
+            (FieldType::U8, 0, Endianness::Big, 255.0),
+            (FieldType::I8, 0, Endianness::Big, -1.0),
+            (FieldType::U16, 1, Endianness::Big, 258.0),
+            (FieldType::U16, 1, Endianness::Little, 513.0),
+            (FieldType::I16, 0, Endianness::Big, -255.0),
+            (FieldType::I16, 0, Endianness::Little, 511.0),
+            (FieldType::U32, 1, Endianness::Big, 16_909_060.0),
+            (FieldType::U32, 1, Endianness::Little, 67_305_985.0),
+            (FieldType::I32, 0, Endianness::Big, -16_711_165.0),
+            (FieldType::U64, 1, Endianness::Big, 72_623_859_790_382_856.0),
+            (
+                FieldType::I64,
+                0,
+                Endianness::Little,
+                506_097_522_914_230_783.0,
+            ),
+        ];
+        for &(kind, offset, endianness, value) in &checks {
+            assert_eq!(
+                read_number(&field(kind, offset, endianness), &data),
+                Some(value),
+                "{:?} {:?}",
+                kind,
+                endianness
+            );
+        }
+    }
+
+    #[test]
+    fn floats() {
+        let data = [0x3F, 0xC0, 0x00, 0x00];
+        assert_eq!(
+            read_number(&field(FieldType::F32, 0, Endianness::Big), &data),
+            Some(1.5)
+        );
+        let data = [0, 0, 0, 0, 0, 0, 0xF8, 0xBF];
+        assert_eq!(
+            read_number(&field(FieldType::F64, 0, Endianness::Little), &data),
+            Some(-1.5)
+        );
+        let nan = f32::NAN.to_be_bytes();
+        assert_eq!(
+            read_number(&field(FieldType::F32, 0, Endianness::Big), &nan),
+            None
+        );
+    }
+
+    #[test]
+    fn numbers_out_of_range() {
+        let data = [1, 2, 3, 4];
+        assert_eq!(
+            read_number(&field(FieldType::U32, 1, Endianness::Big), &data),
+            None
+        );
+        assert_eq!(
+            read_number(&field(FieldType::U8, 4, Endianness::Big), &data),
+            None
+        );
+        assert_eq!(
+            read_number(&field(FieldType::U16, usize::MAX, Endianness::Big), &data),
+            None
+        );
+        assert_eq!(
+            read_number(&field(FieldType::Bytes, 0, Endianness::Big), &data),
+            None
+        );
+    }
+
+    #[test]
+    fn incorrect_layouts() {
+        assert!(Layout::parse("size = 0").is_err());
+        assert!(Layout::parse("sync = \"AZ\"\nsize = 2").is_err());
+        assert!(Layout::parse("[length]\noffset = 0\nsize = 5").is_err());
+        assert!(Layout::parse("name = \"Empty\"").is_err());
+    }
+}
@@ -1,15 +1,39 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod at;
+mod capture;
+mod checksum;
+mod cli;
+mod console;
 mod data;
 mod delegate;
 mod event;
+mod export;
+mod layout;
+mod mavlink;
+mod modbus;
+mod nmea;
+mod pcapng;
+mod plot;
+mod profile;
+mod scpi;
+mod script;
+mod sequence;
 mod serial;
+mod session_log;
+mod slcan;
+mod stats;
 mod ui;
 mod widgets;
+mod zmodem;
 
-use crate::data::{AppData, DruidDataBits, DruidFlowControl, DruidParity, DruidStopBits};
+use crate::data::{
+    AppData, CaptureData, LogData, ModbusData, NmeaData, PlotData, ProfileData, ReplaySpeed,
+    ScpiData, ScriptData, SlcanData,
+};
+use crate::serial::ModemStatus;
+use crate::stats::Stats;
 use crate::ui::make_ui;
-use data::Protocol;
 use delegate::Delegate;
 use druid::text::RichText;
 use druid::{AppLauncher, LocalizedString, WindowDesc};
@@ -17,13 +41,44 @@ use event::GuiMessage;
 use futures::channel::mpsc;
 use std::collections::VecDeque;
 use std::{sync::Arc, thread};
-use tokio::runtime::Builder;
 
 fn main() {
-    let window = WindowDesc::new(make_ui())
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (args, settings) = match cli::Args::parse(&args).and_then(|parsed| {
+        let settings = parsed.settings()?;
+        Ok((parsed, settings))
+    }) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            eprintln!("{}\n{}", error, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if args.console {
+        if settings.port_name.is_empty() {
+            eprintln!("Missing --port\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+        std::process::exit(console::run(&settings, args.hex));
+    }
+
+    let profile_name = args
+        .profile
+        .clone()
+        .or_else(profile::last_name)
+        .unwrap_or_default();
+
+    // The GUI starts without layout when it cannot be read
+    let (layout, status) = match settings.load_layout() {
+        Ok(layout) => (layout, "".to_string()),
+        Err(e) => (None, e),
+    };
+
+    let window = WindowDesc::new(make_ui(args.open))
         .title(LocalizedString::new("Serial tool").with_placeholder("Stool"))
         .with_min_size((164., 775.))
-        .window_size((500., 775.));
+        .window_size((900., 775.));
 
     let launcher = AppLauncher::with_window(window);
 
@@ -33,10 +88,7 @@ fn main() {
 
     let rt_thread = thread::spawn(move || {
         // Create the runtime
-        let async_rt = Builder::new_current_thread()
-            .enable_io()
-            .build()
-            .expect("runtime failed");
+        let async_rt = serial::runtime().expect("runtime failed");
         let _ = async_rt.block_on(serial::serial_loop(event_sink, receiver));
     });
 
@@ -45,16 +97,96 @@ fn main() {
         .launch(AppData {
             output: RichText::new("".into()),
             output_attr: Arc::new(VecDeque::new()),
-            port_name: Arc::new("".to_string()),
-            baud_rate: 115_200,
+            transcript: Arc::new(VecDeque::new()),
+            port_name: Arc::new(settings.port_name),
+            baud_rate: settings.baud_rate,
             to_write: Arc::new("".to_string()),
-            data_bits: DruidDataBits::Eight,
-            flow_control: DruidFlowControl::None,
-            parity: DruidParity::None,
-            stop_bits: DruidStopBits::One,
-            protocol: Protocol::Raw,
+            data_bits: settings.data_bits,
+            flow_control: settings.flow_control,
+            parity: settings.parity,
+            stop_bits: settings.stop_bits,
+            protocol: settings.protocol,
+            line_ending: settings.line_ending,
+            framing: settings.framing,
+            delimiter: settings.delimiter,
+            layout,
+            checksum: settings.checksum,
+            modbus: ModbusData {
+                unit: 1,
+                function: modbus::Function::ReadHoldingRegisters,
+                address: 0,
+                quantity: 1,
+                values: "".to_string(),
+                slave_unit: 1,
+                table: "".to_string(),
+                applied_table: "".to_string(),
+            },
+            nmea: NmeaData {
+                rows: Arc::new(Vec::new()),
+                invalid: 0,
+            },
+            slcan: SlcanData {
+                bitrate: slcan::Bitrate::K500,
+                timestamps: false,
+                id: "".to_string(),
+                extended: false,
+                remote: false,
+                dlc: 0,
+                data: "".to_string(),
+                frames: Arc::new(Vec::new()),
+            },
+            at_exchanges: Arc::new(Vec::new()),
+            scpi: ScpiData {
+                idn: "".to_string(),
+                poll_errors: false,
+                readings: "".to_string(),
+            },
+            script: ScriptData {
+                source: "".to_string(),
+                show: settings.show_script,
+                running: false,
+            },
+            profiles: ProfileData {
+                name: profile_name,
+                names: Arc::new(profile::names()),
+                show: false,
+            },
+            session_log: LogData {
+                directory: dirs::document_dir()
+                    .unwrap_or_default()
+                    .join("stool")
+                    .to_string_lossy()
+                    .to_string(),
+                template: session_log::DEFAULT_TEMPLATE.to_string(),
+                max_size: 10,
+                period: 0,
+                active: false,
+                show: settings.show_log,
+            },
+            capture: CaptureData {
+                recording: false,
+                replaying: false,
+                speed: ReplaySpeed::Original,
+                show: settings.show_capture,
+            },
+            plot: PlotData {
+                format: plot::PlotFormat::KeyValue,
+                regex: "".to_string(),
+                fields: "".to_string(),
+                paused: false,
+                window: 30.,
+                channels: Arc::new(Vec::new()),
+                show: settings.show_plot,
+            },
+            stats: Stats::default(),
+            show_stats: settings.show_stats,
+            rts: true,
+            dtr: true,
+            modem_status: ModemStatus::default(),
+            break_duration: 250,
+            active_settings: "".to_string(),
             sender: Arc::new(sender),
-            status: "".to_string(),
+            status,
         })
         .expect("launch failed");
 
@@ -0,0 +1,532 @@
+//! MAVLink v1 and v2 frames: CRC validation and decoding of the messages of the common dialect.
+
+use crate::checksum::Algorithm;
+use std::convert::TryInto;
+
+pub const STX_V1: u8 = 0xFE;
+pub const STX_V2: u8 = 0xFD;
+
+const SIGNATURE_LEN: usize = 13;
+const INCOMPAT_SIGNED: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    Char,
+}
+
+impl Kind {
+    fn size(self) -> usize {
+        match self {
+            Kind::U8 | Kind::I8 | Kind::Char => 1,
+            Kind::U16 | Kind::I16 => 2,
+            Kind::U32 | Kind::I32 | Kind::F32 => 4,
+            Kind::U64 | Kind::I64 => 8,
+        }
+    }
+}
+
+/// Field name, type and number of elements, in the order of the payload.
+struct Field(&'static str, Kind, usize);
+
+struct Message {
+    id: u32,
+    name: &'static str,
+    crc_extra: u8,
+    fields: &'static [Field],
+}
+
+use Kind::*;
+
+/// Messages of the common dialect sorted by ID, the fields are only given for the most used.
+#[rustfmt::skip]
+const MESSAGES: &[Message] = &[
+    Message { id: 0, name: "HEARTBEAT", crc_extra: 50, fields: &[
+        Field("custom_mode", U32, 1), Field("type", U8, 1), Field("autopilot", U8, 1),
+        Field("base_mode", U8, 1), Field("system_status", U8, 1), Field("mavlink_version", U8, 1),
+    ] },
+    Message { id: 1, name: "SYS_STATUS", crc_extra: 124, fields: &[
+        Field("sensors_present", U32, 1), Field("sensors_enabled", U32, 1),
+        Field("sensors_health", U32, 1), Field("load", U16, 1), Field("voltage_battery", U16, 1),
+        Field("current_battery", I16, 1), Field("drop_rate_comm", U16, 1),
+        Field("errors_comm", U16, 1), Field("errors_count", U16, 4),
+        Field("battery_remaining", I8, 1),
+    ] },
+    Message { id: 2, name: "SYSTEM_TIME", crc_extra: 137, fields: &[
+        Field("time_unix_usec", U64, 1), Field("time_boot_ms", U32, 1),
+    ] },
+    Message { id: 4, name: "PING", crc_extra: 237, fields: &[
+        Field("time_usec", U64, 1), Field("seq", U32, 1), Field("target_system", U8, 1),
+        Field("target_component", U8, 1),
+    ] },
+    Message { id: 5, name: "CHANGE_OPERATOR_CONTROL", crc_extra: 217, fields: &[] },
+    Message { id: 6, name: "CHANGE_OPERATOR_CONTROL_ACK", crc_extra: 104, fields: &[] },
+    Message { id: 7, name: "AUTH_KEY", crc_extra: 119, fields: &[] },
+    Message { id: 11, name: "SET_MODE", crc_extra: 89, fields: &[
+        Field("custom_mode", U32, 1), Field("target_system", U8, 1), Field("base_mode", U8, 1),
+    ] },
+    Message { id: 20, name: "PARAM_REQUEST_READ", crc_extra: 214, fields: &[
+        Field("param_index", I16, 1), Field("target_system", U8, 1),
+        Field("target_component", U8, 1), Field("param_id", Char, 16),
+    ] },
+    Message { id: 21, name: "PARAM_REQUEST_LIST", crc_extra: 159, fields: &[
+        Field("target_system", U8, 1), Field("target_component", U8, 1),
+    ] },
+    Message { id: 22, name: "PARAM_VALUE", crc_extra: 220, fields: &[
+        Field("param_value", F32, 1), Field("param_count", U16, 1), Field("param_index", U16, 1),
+        Field("param_id", Char, 16), Field("param_type", U8, 1),
+    ] },
+    Message { id: 23, name: "PARAM_SET", crc_extra: 168, fields: &[
+        Field("param_value", F32, 1), Field("target_system", U8, 1),
+        Field("target_component", U8, 1), Field("param_id", Char, 16), Field("param_type", U8, 1),
+    ] },
+    Message { id: 24, name: "GPS_RAW_INT", crc_extra: 24, fields: &[
+        Field("time_usec", U64, 1), Field("lat", I32, 1), Field("lon", I32, 1),
+        Field("alt", I32, 1), Field("eph", U16, 1), Field("epv", U16, 1), Field("vel", U16, 1),
+        Field("cog", U16, 1), Field("fix_type", U8, 1), Field("satellites_visible", U8, 1),
+    ] },
+    Message { id: 25, name: "GPS_STATUS", crc_extra: 23, fields: &[] },
+    Message { id: 26, name: "SCALED_IMU", crc_extra: 170, fields: &[
+        Field("time_boot_ms", U32, 1), Field("acc", I16, 3), Field("gyro", I16, 3),
+        Field("mag", I16, 3),
+    ] },
+    Message { id: 27, name: "RAW_IMU", crc_extra: 144, fields: &[
+        Field("time_usec", U64, 1), Field("acc", I16, 3), Field("gyro", I16, 3),
+        Field("mag", I16, 3),
+    ] },
+    Message { id: 28, name: "RAW_PRESSURE", crc_extra: 67, fields: &[] },
+    Message { id: 29, name: "SCALED_PRESSURE", crc_extra: 115, fields: &[
+        Field("time_boot_ms", U32, 1), Field("press_abs", F32, 1), Field("press_diff", F32, 1),
+        Field("temperature", I16, 1),
+    ] },
+    Message { id: 30, name: "ATTITUDE", crc_extra: 39, fields: &[
+        Field("time_boot_ms", U32, 1), Field("roll", F32, 1), Field("pitch", F32, 1),
+        Field("yaw", F32, 1), Field("rollspeed", F32, 1), Field("pitchspeed", F32, 1),
+        Field("yawspeed", F32, 1),
+    ] },
+    Message { id: 31, name: "ATTITUDE_QUATERNION", crc_extra: 246, fields: &[
+        Field("time_boot_ms", U32, 1), Field("q", F32, 4), Field("rollspeed", F32, 1),
+        Field("pitchspeed", F32, 1), Field("yawspeed", F32, 1),
+    ] },
+    Message { id: 32, name: "LOCAL_POSITION_NED", crc_extra: 185, fields: &[
+        Field("time_boot_ms", U32, 1), Field("x", F32, 1), Field("y", F32, 1), Field("z", F32, 1),
+        Field("vx", F32, 1), Field("vy", F32, 1), Field("vz", F32, 1),
+    ] },
+    Message { id: 33, name: "GLOBAL_POSITION_INT", crc_extra: 104, fields: &[
+        Field("time_boot_ms", U32, 1), Field("lat", I32, 1), Field("lon", I32, 1),
+        Field("alt", I32, 1), Field("relative_alt", I32, 1), Field("vx", I16, 1),
+        Field("vy", I16, 1), Field("vz", I16, 1), Field("hdg", U16, 1),
+    ] },
+    Message { id: 34, name: "RC_CHANNELS_SCALED", crc_extra: 237, fields: &[] },
+    Message { id: 35, name: "RC_CHANNELS_RAW", crc_extra: 244, fields: &[
+        Field("time_boot_ms", U32, 1), Field("chan_raw", U16, 8), Field("port", U8, 1),
+        Field("rssi", U8, 1),
+    ] },
+    Message { id: 36, name: "SERVO_OUTPUT_RAW", crc_extra: 222, fields: &[
+        Field("time_usec", U32, 1), Field("servo_raw", U16, 8), Field("port", U8, 1),
+    ] },
+    Message { id: 37, name: "MISSION_REQUEST_PARTIAL_LIST", crc_extra: 212, fields: &[] },
+    Message { id: 38, name: "MISSION_WRITE_PARTIAL_LIST", crc_extra: 9, fields: &[] },
+    Message { id: 39, name: "MISSION_ITEM", crc_extra: 254, fields: &[] },
+    Message { id: 40, name: "MISSION_REQUEST", crc_extra: 230, fields: &[] },
+    Message { id: 41, name: "MISSION_SET_CURRENT", crc_extra: 28, fields: &[] },
+    Message { id: 42, name: "MISSION_CURRENT", crc_extra: 28, fields: &[
+        Field("seq", U16, 1),
+    ] },
+    Message { id: 43, name: "MISSION_REQUEST_LIST", crc_extra: 132, fields: &[] },
+    Message { id: 44, name: "MISSION_COUNT", crc_extra: 221, fields: &[
+        Field("count", U16, 1), Field("target_system", U8, 1), Field("target_component", U8, 1),
+    ] },
+    Message { id: 45, name: "MISSION_CLEAR_ALL", crc_extra: 232, fields: &[] },
+    Message { id: 46, name: "MISSION_ITEM_REACHED", crc_extra: 11, fields: &[] },
+    Message { id: 47, name: "MISSION_ACK", crc_extra: 153, fields: &[
+        Field("target_system", U8, 1), Field("target_component", U8, 1), Field("type", U8, 1),
+    ] },
+    Message { id: 48, name: "SET_GPS_GLOBAL_ORIGIN", crc_extra: 41, fields: &[] },
+    Message { id: 49, name: "GPS_GLOBAL_ORIGIN", crc_extra: 39, fields: &[] },
+    Message { id: 50, name: "PARAM_MAP_RC", crc_extra: 78, fields: &[] },
+    Message { id: 51, name: "MISSION_REQUEST_INT", crc_extra: 196, fields: &[] },
+    Message { id: 54, name: "SAFETY_SET_ALLOWED_AREA", crc_extra: 15, fields: &[] },
+    Message { id: 55, name: "SAFETY_ALLOWED_AREA", crc_extra: 3, fields: &[] },
+    Message { id: 61, name: "ATTITUDE_QUATERNION_COV", crc_extra: 167, fields: &[] },
+    Message { id: 62, name: "NAV_CONTROLLER_OUTPUT", crc_extra: 183, fields: &[
+        Field("nav_roll", F32, 1), Field("nav_pitch", F32, 1), Field("alt_error", F32, 1),
+        Field("aspd_error", F32, 1), Field("xtrack_error", F32, 1), Field("nav_bearing", I16, 1),
+        Field("target_bearing", I16, 1), Field("wp_dist", U16, 1),
+    ] },
+    Message { id: 63, name: "GLOBAL_POSITION_INT_COV", crc_extra: 119, fields: &[] },
+    Message { id: 64, name: "LOCAL_POSITION_NED_COV", crc_extra: 191, fields: &[] },
+    Message { id: 65, name: "RC_CHANNELS", crc_extra: 118, fields: &[
+        Field("time_boot_ms", U32, 1), Field("chan_raw", U16, 18), Field("chancount", U8, 1),
+        Field("rssi", U8, 1),
+    ] },
+    Message { id: 66, name: "REQUEST_DATA_STREAM", crc_extra: 148, fields: &[
+        Field("req_message_rate", U16, 1), Field("target_system", U8, 1),
+        Field("target_component", U8, 1), Field("req_stream_id", U8, 1),
+        Field("start_stop", U8, 1),
+    ] },
+    Message { id: 67, name: "DATA_STREAM", crc_extra: 21, fields: &[] },
+    Message { id: 69, name: "MANUAL_CONTROL", crc_extra: 243, fields: &[
+        Field("x", I16, 1), Field("y", I16, 1), Field("z", I16, 1), Field("r", I16, 1),
+        Field("buttons", U16, 1), Field("target", U8, 1),
+    ] },
+    Message { id: 70, name: "RC_CHANNELS_OVERRIDE", crc_extra: 124, fields: &[] },
+    Message { id: 73, name: "MISSION_ITEM_INT", crc_extra: 38, fields: &[] },
+    Message { id: 74, name: "VFR_HUD", crc_extra: 20, fields: &[
+        Field("airspeed", F32, 1), Field("groundspeed", F32, 1), Field("alt", F32, 1),
+        Field("climb", F32, 1), Field("heading", I16, 1), Field("throttle", U16, 1),
+    ] },
+    Message { id: 75, name: "COMMAND_INT", crc_extra: 158, fields: &[] },
+    Message { id: 76, name: "COMMAND_LONG", crc_extra: 152, fields: &[
+        Field("param", F32, 7), Field("command", U16, 1), Field("target_system", U8, 1),
+        Field("target_component", U8, 1), Field("confirmation", U8, 1),
+    ] },
+    Message { id: 77, name: "COMMAND_ACK", crc_extra: 143, fields: &[
+        Field("command", U16, 1), Field("result", U8, 1),
+    ] },
+    Message { id: 81, name: "MANUAL_SETPOINT", crc_extra: 106, fields: &[] },
+    Message { id: 82, name: "SET_ATTITUDE_TARGET", crc_extra: 49, fields: &[] },
+    Message { id: 83, name: "ATTITUDE_TARGET", crc_extra: 22, fields: &[] },
+    Message { id: 84, name: "SET_POSITION_TARGET_LOCAL_NED", crc_extra: 143, fields: &[] },
+    Message { id: 85, name: "POSITION_TARGET_LOCAL_NED", crc_extra: 140, fields: &[] },
+    Message { id: 86, name: "SET_POSITION_TARGET_GLOBAL_INT", crc_extra: 5, fields: &[] },
+    Message { id: 87, name: "POSITION_TARGET_GLOBAL_INT", crc_extra: 150, fields: &[] },
+    Message { id: 89, name: "LOCAL_POSITION_NED_SYSTEM_GLOBAL_OFFSET", crc_extra: 231, fields: &[] },
+    Message { id: 90, name: "HIL_STATE", crc_extra: 183, fields: &[] },
+    Message { id: 91, name: "HIL_CONTROLS", crc_extra: 63, fields: &[] },
+    Message { id: 92, name: "HIL_RC_INPUTS_RAW", crc_extra: 54, fields: &[] },
+    Message { id: 93, name: "HIL_ACTUATOR_CONTROLS", crc_extra: 47, fields: &[] },
+    Message { id: 100, name: "OPTICAL_FLOW", crc_extra: 175, fields: &[] },
+    Message { id: 101, name: "GLOBAL_VISION_POSITION_ESTIMATE", crc_extra: 102, fields: &[] },
+    Message { id: 102, name: "VISION_POSITION_ESTIMATE", crc_extra: 158, fields: &[] },
+    Message { id: 103, name: "VISION_SPEED_ESTIMATE", crc_extra: 208, fields: &[] },
+    Message { id: 104, name: "VICON_POSITION_ESTIMATE", crc_extra: 56, fields: &[] },
+    Message { id: 105, name: "HIGHRES_IMU", crc_extra: 93, fields: &[] },
+    Message { id: 106, name: "OPTICAL_FLOW_RAD", crc_extra: 138, fields: &[] },
+    Message { id: 107, name: "HIL_SENSOR", crc_extra: 108, fields: &[] },
+    Message { id: 108, name: "SIM_STATE", crc_extra: 32, fields: &[] },
+    Message { id: 109, name: "RADIO_STATUS", crc_extra: 185, fields: &[
+        Field("rxerrors", U16, 1), Field("fixed", U16, 1), Field("rssi", U8, 1),
+        Field("remrssi", U8, 1), Field("txbuf", U8, 1), Field("noise", U8, 1),
+        Field("remnoise", U8, 1),
+    ] },
+    Message { id: 110, name: "FILE_TRANSFER_PROTOCOL", crc_extra: 84, fields: &[] },
+    Message { id: 111, name: "TIMESYNC", crc_extra: 34, fields: &[
+        Field("tc1", I64, 1), Field("ts1", I64, 1),
+    ] },
+    Message { id: 112, name: "CAMERA_TRIGGER", crc_extra: 174, fields: &[] },
+    Message { id: 113, name: "HIL_GPS", crc_extra: 124, fields: &[] },
+    Message { id: 114, name: "HIL_OPTICAL_FLOW", crc_extra: 237, fields: &[] },
+    Message { id: 115, name: "HIL_STATE_QUATERNION", crc_extra: 4, fields: &[] },
+    Message { id: 116, name: "SCALED_IMU2", crc_extra: 76, fields: &[] },
+    Message { id: 117, name: "LOG_REQUEST_LIST", crc_extra: 128, fields: &[] },
+    Message { id: 118, name: "LOG_ENTRY", crc_extra: 56, fields: &[] },
+    Message { id: 119, name: "LOG_REQUEST_DATA", crc_extra: 116, fields: &[] },
+    Message { id: 120, name: "LOG_DATA", crc_extra: 134, fields: &[] },
+    Message { id: 121, name: "LOG_ERASE", crc_extra: 237, fields: &[] },
+    Message { id: 122, name: "LOG_REQUEST_END", crc_extra: 203, fields: &[] },
+    Message { id: 123, name: "GPS_INJECT_DATA", crc_extra: 250, fields: &[] },
+    Message { id: 124, name: "GPS2_RAW", crc_extra: 87, fields: &[] },
+    Message { id: 125, name: "POWER_STATUS", crc_extra: 203, fields: &[
+        Field("Vcc", U16, 1), Field("Vservo", U16, 1), Field("flags", U16, 1),
+    ] },
+    Message { id: 126, name: "SERIAL_CONTROL", crc_extra: 220, fields: &[] },
+    Message { id: 127, name: "GPS_RTK", crc_extra: 25, fields: &[] },
+    Message { id: 128, name: "GPS2_RTK", crc_extra: 226, fields: &[] },
+    Message { id: 129, name: "SCALED_IMU3", crc_extra: 46, fields: &[] },
+    Message { id: 130, name: "DATA_TRANSMISSION_HANDSHAKE", crc_extra: 29, fields: &[] },
+    Message { id: 131, name: "ENCAPSULATED_DATA", crc_extra: 223, fields: &[] },
+    Message { id: 132, name: "DISTANCE_SENSOR", crc_extra: 85, fields: &[
+        Field("time_boot_ms", U32, 1), Field("min_distance", U16, 1),
+        Field("max_distance", U16, 1), Field("current_distance", U16, 1), Field("type", U8, 1),
+        Field("id", U8, 1), Field("orientation", U8, 1), Field("covariance", U8, 1),
+    ] },
+    Message { id: 133, name: "TERRAIN_REQUEST", crc_extra: 6, fields: &[] },
+    Message { id: 134, name: "TERRAIN_DATA", crc_extra: 229, fields: &[] },
+    Message { id: 135, name: "TERRAIN_CHECK", crc_extra: 203, fields: &[] },
+    Message { id: 136, name: "TERRAIN_REPORT", crc_extra: 1, fields: &[] },
+    Message { id: 137, name: "SCALED_PRESSURE2", crc_extra: 195, fields: &[] },
+    Message { id: 138, name: "ATT_POS_MOCAP", crc_extra: 109, fields: &[] },
+    Message { id: 139, name: "SET_ACTUATOR_CONTROL_TARGET", crc_extra: 168, fields: &[] },
+    Message { id: 140, name: "ACTUATOR_CONTROL_TARGET", crc_extra: 181, fields: &[] },
+    Message { id: 141, name: "ALTITUDE", crc_extra: 47, fields: &[] },
+    Message { id: 142, name: "RESOURCE_REQUEST", crc_extra: 72, fields: &[] },
+    Message { id: 143, name: "SCALED_PRESSURE3", crc_extra: 131, fields: &[] },
+    Message { id: 144, name: "FOLLOW_TARGET", crc_extra: 127, fields: &[] },
+    Message { id: 146, name: "CONTROL_SYSTEM_STATE", crc_extra: 103, fields: &[] },
+    Message { id: 147, name: "BATTERY_STATUS", crc_extra: 154, fields: &[
+        Field("current_consumed", I32, 1), Field("energy_consumed", I32, 1),
+        Field("temperature", I16, 1), Field("voltages", U16, 10),
+        Field("current_battery", I16, 1), Field("id", U8, 1), Field("battery_function", U8, 1),
+        Field("type", U8, 1), Field("battery_remaining", I8, 1),
+    ] },
+    Message { id: 148, name: "AUTOPILOT_VERSION", crc_extra: 178, fields: &[
+        Field("capabilities", U64, 1), Field("uid", U64, 1), Field("flight_sw_version", U32, 1),
+        Field("middleware_sw_version", U32, 1), Field("os_sw_version", U32, 1),
+        Field("board_version", U32, 1), Field("vendor_id", U16, 1), Field("product_id", U16, 1),
+        Field("flight_custom_version", U8, 8), Field("middleware_custom_version", U8, 8),
+        Field("os_custom_version", U8, 8),
+    ] },
+    Message { id: 149, name: "LANDING_TARGET", crc_extra: 200, fields: &[] },
+    Message { id: 230, name: "ESTIMATOR_STATUS", crc_extra: 163, fields: &[] },
+    Message { id: 231, name: "WIND_COV", crc_extra: 105, fields: &[] },
+    Message { id: 232, name: "GPS_INPUT", crc_extra: 151, fields: &[] },
+    Message { id: 233, name: "GPS_RTCM_DATA", crc_extra: 35, fields: &[] },
+    Message { id: 234, name: "HIGH_LATENCY", crc_extra: 150, fields: &[] },
+    Message { id: 241, name: "VIBRATION", crc_extra: 90, fields: &[
+        Field("time_usec", U64, 1), Field("vibration_x", F32, 1), Field("vibration_y", F32, 1),
+        Field("vibration_z", F32, 1), Field("clipping", U32, 3),
+    ] },
+    Message { id: 242, name: "HOME_POSITION", crc_extra: 104, fields: &[
+        Field("latitude", I32, 1), Field("longitude", I32, 1), Field("altitude", I32, 1),
+        Field("x", F32, 1), Field("y", F32, 1), Field("z", F32, 1), Field("q", F32, 4),
+        Field("approach_x", F32, 1), Field("approach_y", F32, 1), Field("approach_z", F32, 1),
+    ] },
+    Message { id: 243, name: "SET_HOME_POSITION", crc_extra: 85, fields: &[] },
+    Message { id: 244, name: "MESSAGE_INTERVAL", crc_extra: 95, fields: &[] },
+    Message { id: 245, name: "EXTENDED_SYS_STATE", crc_extra: 130, fields: &[
+        Field("vtol_state", U8, 1), Field("landed_state", U8, 1),
+    ] },
+    Message { id: 246, name: "ADSB_VEHICLE", crc_extra: 184, fields: &[] },
+    Message { id: 247, name: "COLLISION", crc_extra: 81, fields: &[] },
+    Message { id: 248, name: "V2_EXTENSION", crc_extra: 8, fields: &[] },
+    Message { id: 249, name: "MEMORY_VECT", crc_extra: 204, fields: &[] },
+    Message { id: 250, name: "DEBUG_VECT", crc_extra: 49, fields: &[] },
+    Message { id: 251, name: "NAMED_VALUE_FLOAT", crc_extra: 170, fields: &[
+        Field("time_boot_ms", U32, 1), Field("value", F32, 1), Field("name", Char, 10),
+    ] },
+    Message { id: 252, name: "NAMED_VALUE_INT", crc_extra: 44, fields: &[
+        Field("time_boot_ms", U32, 1), Field("value", I32, 1), Field("name", Char, 10),
+    ] },
+    Message { id: 253, name: "STATUSTEXT", crc_extra: 83, fields: &[
+        Field("severity", U8, 1), Field("text", Char, 50),
+    ] },
+    Message { id: 254, name: "DEBUG", crc_extra: 46, fields: &[] },
+];
+
+fn find_message(id: u32) -> Option<&'static Message> {
+    MESSAGES
+        .binary_search_by_key(&id, |message| message.id)
+        .ok()
+        .map(|index| &MESSAGES[index])
+}
+
+/// Length of the frame starting at the beginning of `buf`, once enough of its header is there.
+pub fn frame_len(buf: &[u8]) -> Option<usize> {
+    match buf {
+        [STX_V1, len, ..] => Some(*len as usize + 8),
+        [STX_V2, len, incompat_flags, ..] if incompat_flags & INCOMPAT_SIGNED != 0 => {
+            Some(*len as usize + 12 + SIGNATURE_LEN)
+        }
+        [STX_V2, len, ..] => Some(*len as usize + 12),
+        _ => None,
+    }
+}
+
+/// Length of the frame at the beginning of `buf`, `None` until it is complete. The bytes before
+/// a start of frame are given alone.
+pub fn split(buf: &[u8]) -> Option<usize> {
+    match buf
+        .iter()
+        .position(|&byte| byte == STX_V1 || byte == STX_V2)
+    {
+        Some(0) => {
+            let len = frame_len(buf).filter(|&len| buf.len() >= len)?;
+            match parse(&buf[..len]) {
+                // The frames of unknown messages have no CRC to check, they keep their length
+                Ok(_) => Some(len),
+                // A start byte found in the middle of some garbage gives a wrong CRC, give it
+                // alone to look for the next one
+                Err(_) => Some(1),
+            }
+        }
+        Some(start) => Some(start),
+        None => Some(buf.len()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame<'a> {
+    pub version: u8,
+    pub sequence: u8,
+    pub system: u8,
+    pub component: u8,
+    pub message: u32,
+    pub payload: &'a [u8],
+    pub signed: bool,
+    /// The CRC can only be checked for the messages of the common dialect.
+    pub crc_checked: bool,
+}
+
+/// Parse a whole v1 or v2 frame and check its CRC.
+pub fn parse(frame: &[u8]) -> Result<Frame<'_>, String> {
+    let len = frame_len(frame).ok_or_else(|| "Not a MAVLink frame".to_string())?;
+    if frame.len() != len {
+        return Err(format!(
+            "Incomplete frame, {} bytes out of {}",
+            frame.len(),
+            len
+        ));
+    }
+
+    let payload_len = frame[1] as usize;
+    let (header_len, signed) = match frame[0] {
+        STX_V1 => (6, false),
+        _ => (10, frame[2] & INCOMPAT_SIGNED != 0),
+    };
+    let (version, sequence, system, component, message) = match frame[0] {
+        STX_V1 => (1, frame[2], frame[3], frame[4], frame[5] as u32),
+        _ => (
+            2,
+            frame[4],
+            frame[5],
+            frame[6],
+            u32::from_le_bytes([frame[7], frame[8], frame[9], 0]),
+        ),
+    };
+
+    let crc_end = header_len + payload_len;
+    let crc_checked = match find_message(message) {
+        Some(definition) => {
+            let mut covered = frame[1..crc_end].to_vec();
+            covered.push(definition.crc_extra);
+            let crc = Algorithm::Crc16Mcrf4xx.compute(&covered) as u16;
+            if crc.to_le_bytes() != frame[crc_end..crc_end + 2] {
+                return Err("Wrong CRC".to_string());
+            }
+            true
+        }
+        None => false,
+    };
+
+    Ok(Frame {
+        version,
+        sequence,
+        system,
+        component,
+        message,
+        payload: &frame[header_len..crc_end],
+        signed,
+        crc_checked,
+    })
+}
+
+fn format_value(kind: Kind, bytes: &[u8]) -> String {
+    match kind {
+        U8 | Char => bytes[0].to_string(),
+        I8 => (bytes[0] as i8).to_string(),
+        U16 => u16::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        I16 => i16::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        U32 => u32::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        I32 => i32::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        U64 => u64::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        I64 => i64::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        F32 => f32::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+    }
+}
+
+/// Name of the message and its decoded fields, or the payload in hexadecimal when the fields
+/// of the message are not known.
+pub fn describe(frame: &Frame) -> String {
+    let definition = match find_message(frame.message) {
+        Some(definition) => definition,
+        None => {
+            return format!(
+                "Unknown message {}, CRC not checked: {}",
+                frame.message,
+                hex::encode_upper(frame.payload)
+            )
+        }
+    };
+    if definition.fields.is_empty() {
+        return format!(
+            "{} ({}): {}",
+            definition.name,
+            frame.message,
+            hex::encode_upper(frame.payload)
+        );
+    }
+
+    // MAVLink 2 removes the trailing zeros of the payload
+    let len = definition
+        .fields
+        .iter()
+        .map(|Field(_, kind, count)| kind.size() * count)
+        .sum();
+    let mut payload = frame.payload.to_vec();
+    if payload.len() < len {
+        payload.resize(len, 0);
+    }
+
+    let mut offset = 0;
+    let mut fields = Vec::new();
+    for Field(name, kind, count) in definition.fields {
+        let bytes = &payload[offset..offset + kind.size() * count];
+        offset += bytes.len();
+
+        let value = match kind {
+            Char => {
+                let end = bytes
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(bytes.len());
+                format!("\"{}\"", String::from_utf8_lossy(&bytes[..end]))
+            }
+            _ if *count == 1 => format_value(*kind, bytes),
+            _ => format!(
+                "[{}]",
+                bytes
+                    .chunks(kind.size())
+                    .map(|element| format_value(*kind, element))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        fields.push(format!("{}={}", name, value));
+    }
+
+    format!(
+        "{} ({}): {}",
+        definition.name,
+        frame.message,
+        fields.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MAVLink 2 frame of the message 300, not in the common dialect known here.
+    const UNKNOWN_FRAME: [u8; 14] = [
+        STX_V2, 2, 0, 0, 7, 1, 1, 0x2C, 0x01, 0x00, 0xAA, 0xBB, 0x12, 0x34,
+    ];
+
+    #[test]
+    fn unknown_message_keeps_its_length() {
+        let mut buf = UNKNOWN_FRAME.to_vec();
+        buf.extend_from_slice(&UNKNOWN_FRAME);
+        assert_eq!(split(&buf), Some(UNKNOWN_FRAME.len()));
+        assert_eq!(split(&buf[..UNKNOWN_FRAME.len() - 1]), None);
+
+        let frame = parse(&UNKNOWN_FRAME).unwrap();
+        assert!(!frame.crc_checked);
+        assert!(describe(&frame).starts_with("Unknown message 300"));
+    }
+
+    #[test]
+    fn garbage_before_frame() {
+        let mut buf = vec![0x00, 0x11];
+        buf.extend_from_slice(&UNKNOWN_FRAME);
+        assert_eq!(split(&buf), Some(2));
+        assert_eq!(split(&[0x00, 0x11]), Some(2));
+    }
+}
@@ -0,0 +1,848 @@
+//! Modbus RTU frames: building requests and describing the frames seen on the line.
+
+use crate::checksum::Algorithm;
+use druid::Data;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub enum Function {
+    ReadCoils,
+    ReadDiscreteInputs,
+    ReadHoldingRegisters,
+    ReadInputRegisters,
+    WriteSingleCoil,
+    WriteSingleRegister,
+    WriteMultipleCoils,
+    WriteMultipleRegisters,
+}
+
+impl Function {
+    pub fn code(self) -> u8 {
+        match self {
+            Function::ReadCoils => 0x01,
+            Function::ReadDiscreteInputs => 0x02,
+            Function::ReadHoldingRegisters => 0x03,
+            Function::ReadInputRegisters => 0x04,
+            Function::WriteSingleCoil => 0x05,
+            Function::WriteSingleRegister => 0x06,
+            Function::WriteMultipleCoils => 0x0F,
+            Function::WriteMultipleRegisters => 0x10,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x01 => Some(Function::ReadCoils),
+            0x02 => Some(Function::ReadDiscreteInputs),
+            0x03 => Some(Function::ReadHoldingRegisters),
+            0x04 => Some(Function::ReadInputRegisters),
+            0x05 => Some(Function::WriteSingleCoil),
+            0x06 => Some(Function::WriteSingleRegister),
+            0x0F => Some(Function::WriteMultipleCoils),
+            0x10 => Some(Function::WriteMultipleRegisters),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Function::ReadCoils => write!(f, "Read coils"),
+            Function::ReadDiscreteInputs => write!(f, "Read discrete inputs"),
+            Function::ReadHoldingRegisters => write!(f, "Read holding registers"),
+            Function::ReadInputRegisters => write!(f, "Read input registers"),
+            Function::WriteSingleCoil => write!(f, "Write single coil"),
+            Function::WriteSingleRegister => write!(f, "Write single register"),
+            Function::WriteMultipleCoils => write!(f, "Write multiple coils"),
+            Function::WriteMultipleRegisters => write!(f, "Write multiple registers"),
+        }
+    }
+}
+
+pub fn crc16(data: &[u8]) -> u16 {
+    Algorithm::Crc16Modbus.compute(data) as u16
+}
+
+/// Append the CRC-16 to a frame, low byte first.
+pub fn append_crc(frame: &mut Vec<u8>) {
+    let crc = crc16(frame);
+    frame.extend_from_slice(&crc.to_le_bytes());
+}
+
+/// Check the trailing CRC-16 of a frame and return the frame without it.
+pub fn check_crc(frame: &[u8]) -> Option<&[u8]> {
+    if frame.len() < 4 {
+        return None;
+    }
+    let (pdu, crc) = frame.split_at(frame.len() - 2);
+    if crc16(pdu).to_le_bytes() == crc {
+        Some(pdu)
+    } else {
+        None
+    }
+}
+
+/// Silence of 3.5 characters delimiting two frames, fixed to 1.75 ms above 19200 bauds.
+pub fn frame_silence(baud_rate: u32, char_bits: u32) -> Duration {
+    if baud_rate > 19_200 || baud_rate == 0 {
+        Duration::from_micros(1_750)
+    } else {
+        Duration::from_micros(3_500_000 * char_bits as u64 / baud_rate as u64)
+    }
+}
+
+/// Parse a list of values separated by commas or spaces, in decimal or `0x` hexadecimal.
+pub fn parse_values(values: &str) -> Result<Vec<u16>, &'static str> {
+    values
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            if let Some(hex) = value
+                .strip_prefix("0x")
+                .or_else(|| value.strip_prefix("0X"))
+            {
+                u16::from_str_radix(hex, 16)
+            } else {
+                value.parse::<u16>()
+            }
+            .map_err(|_| "Incorrect Modbus value")
+        })
+        .collect()
+}
+
+fn pack_bits(bits: &[bool]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (i, &bit)| byte | ((bit as u8) << i))
+        })
+        .collect()
+}
+
+fn unpack_bits(bytes: &[u8], count: usize) -> Vec<bool> {
+    (0..count)
+        .map(|i| bytes[i / 8] & (1 << (i % 8)) != 0)
+        .collect()
+}
+
+fn registers(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]))
+        .collect()
+}
+
+fn format_bits(bits: &[bool]) -> String {
+    bits.iter()
+        .map(|&bit| if bit { "1" } else { "0" })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn format_registers(registers: &[u16]) -> String {
+    registers
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Build a request frame, CRC included. `quantity` is ignored by the write functions, they
+/// use `values` instead.
+pub fn build_request(
+    unit: u8,
+    function: Function,
+    address: u16,
+    quantity: u16,
+    values: &[u16],
+) -> Result<Vec<u8>, &'static str> {
+    let mut frame = vec![unit, function.code()];
+    frame.extend_from_slice(&address.to_be_bytes());
+
+    match function {
+        Function::ReadCoils | Function::ReadDiscreteInputs => {
+            if quantity == 0 || quantity > 2000 {
+                return Err("Modbus quantity must be between 1 and 2000");
+            }
+            frame.extend_from_slice(&quantity.to_be_bytes());
+        }
+        Function::ReadHoldingRegisters | Function::ReadInputRegisters => {
+            if quantity == 0 || quantity > 125 {
+                return Err("Modbus quantity must be between 1 and 125");
+            }
+            frame.extend_from_slice(&quantity.to_be_bytes());
+        }
+        Function::WriteSingleCoil => match values {
+            [value] => frame.extend_from_slice(if *value != 0 { &[0xFF, 0] } else { &[0, 0] }),
+            _ => return Err("Modbus single write expects one value"),
+        },
+        Function::WriteSingleRegister => match values {
+            [value] => frame.extend_from_slice(&value.to_be_bytes()),
+            _ => return Err("Modbus single write expects one value"),
+        },
+        Function::WriteMultipleCoils => {
+            if values.is_empty() || values.len() > 1968 {
+                return Err("Modbus write expects between 1 and 1968 coils");
+            }
+            let bits: Vec<bool> = values.iter().map(|&value| value != 0).collect();
+            let bytes = pack_bits(&bits);
+            frame.extend_from_slice(&(values.len() as u16).to_be_bytes());
+            frame.push(bytes.len() as u8);
+            frame.extend_from_slice(&bytes);
+        }
+        Function::WriteMultipleRegisters => {
+            if values.is_empty() || values.len() > 123 {
+                return Err("Modbus write expects between 1 and 123 registers");
+            }
+            frame.extend_from_slice(&(values.len() as u16).to_be_bytes());
+            frame.push((values.len() * 2) as u8);
+            for value in values {
+                frame.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+    }
+
+    append_crc(&mut frame);
+    Ok(frame)
+}
+
+pub fn exception_name(code: u8) -> &'static str {
+    match code {
+        0x01 => "Illegal function",
+        0x02 => "Illegal data address",
+        0x03 => "Illegal data value",
+        0x04 => "Server device failure",
+        0x05 => "Acknowledge",
+        0x06 => "Server device busy",
+        0x08 => "Memory parity error",
+        0x0A => "Gateway path unavailable",
+        0x0B => "Gateway target device failed to respond",
+        _ => "Unknown exception",
+    }
+}
+
+fn read_u16(pdu: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*pdu.get(at)?, *pdu.get(at + 1)?]))
+}
+
+/// Describe a request frame, `Err` is returned for frames that are not valid requests.
+pub fn describe_request(frame: &[u8]) -> Result<String, String> {
+    let pdu = check_crc(frame).ok_or_else(|| "CRC error".to_string())?;
+    let unit = pdu[0];
+    let function = Function::from_code(pdu[1])
+        .ok_or_else(|| format!("Unit {}: unknown function 0x{:02X}", unit, pdu[1]))?;
+    let malformed = || format!("Unit {}: malformed {} request", unit, function);
+    let address = read_u16(pdu, 2).ok_or_else(malformed)?;
+    let value = read_u16(pdu, 4).ok_or_else(malformed)?;
+
+    match function {
+        Function::ReadCoils
+        | Function::ReadDiscreteInputs
+        | Function::ReadHoldingRegisters
+        | Function::ReadInputRegisters => Ok(format!(
+            "Unit {}: {} at {}, quantity {}",
+            unit, function, address, value
+        )),
+        Function::WriteSingleCoil => Ok(format!(
+            "Unit {}: {} {} = {}",
+            unit,
+            function,
+            address,
+            if value == 0xFF00 { "ON" } else { "OFF" }
+        )),
+        Function::WriteSingleRegister => Ok(format!(
+            "Unit {}: {} {} = {}",
+            unit, function, address, value
+        )),
+        Function::WriteMultipleCoils => {
+            let bytes = pdu.get(7..).ok_or_else(malformed)?;
+            if bytes.len() * 8 < value as usize {
+                return Err(malformed());
+            }
+            Ok(format!(
+                "Unit {}: {} at {}: {}",
+                unit,
+                function,
+                address,
+                format_bits(&unpack_bits(bytes, value as usize))
+            ))
+        }
+        Function::WriteMultipleRegisters => {
+            let bytes = pdu.get(7..).ok_or_else(malformed)?;
+            Ok(format!(
+                "Unit {}: {} at {}: {}",
+                unit,
+                function,
+                address,
+                format_registers(&registers(bytes))
+            ))
+        }
+    }
+}
+
+/// Describe a response frame, using the `request` it answers when known to name the
+/// addresses read. Exceptions and invalid frames are returned as `Err`.
+pub fn describe_response(request: Option<&[u8]>, frame: &[u8]) -> Result<String, String> {
+    let pdu = check_crc(frame).ok_or_else(|| "CRC error".to_string())?;
+    let unit = pdu[0];
+
+    if pdu[1] & 0x80 != 0 {
+        let code = pdu.get(2).copied().unwrap_or(0);
+        return Err(format!(
+            "Unit {}: exception 0x{:02X} on function 0x{:02X}: {}",
+            unit,
+            code,
+            pdu[1] & 0x7F,
+            exception_name(code)
+        ));
+    }
+
+    let function = Function::from_code(pdu[1])
+        .ok_or_else(|| format!("Unit {}: unknown function 0x{:02X}", unit, pdu[1]))?;
+    let malformed = || format!("Unit {}: malformed {} response", unit, function);
+    // Address and quantity of the request when it matches this response
+    let request = request
+        .and_then(check_crc)
+        .filter(|request| request[0] == unit && request[1] == pdu[1])
+        .and_then(|request| Some((read_u16(request, 2)?, read_u16(request, 4)?)));
+
+    match function {
+        Function::ReadCoils | Function::ReadDiscreteInputs => {
+            let count = *pdu.get(2).ok_or_else(malformed)? as usize;
+            let bytes = pdu.get(3..3 + count).ok_or_else(malformed)?;
+            match request {
+                Some((address, quantity)) if quantity as usize <= count * 8 => Ok(format!(
+                    "Unit {}: {} at {}: {}",
+                    unit,
+                    function,
+                    address,
+                    format_bits(&unpack_bits(bytes, quantity as usize))
+                )),
+                _ => Ok(format!(
+                    "Unit {}: {}: {}",
+                    unit,
+                    function,
+                    format_bits(&unpack_bits(bytes, count * 8))
+                )),
+            }
+        }
+        Function::ReadHoldingRegisters | Function::ReadInputRegisters => {
+            let count = *pdu.get(2).ok_or_else(malformed)? as usize;
+            let bytes = pdu.get(3..3 + count).ok_or_else(malformed)?;
+            match request {
+                Some((address, _)) => Ok(format!(
+                    "Unit {}: {} at {}: {}",
+                    unit,
+                    function,
+                    address,
+                    format_registers(&registers(bytes))
+                )),
+                None => Ok(format!(
+                    "Unit {}: {}: {}",
+                    unit,
+                    function,
+                    format_registers(&registers(bytes))
+                )),
+            }
+        }
+        Function::WriteSingleCoil | Function::WriteSingleRegister => {
+            let address = read_u16(pdu, 2).ok_or_else(malformed)?;
+            let value = read_u16(pdu, 4).ok_or_else(malformed)?;
+            let value = match function {
+                Function::WriteSingleCoil if value == 0xFF00 => "ON".to_string(),
+                Function::WriteSingleCoil => "OFF".to_string(),
+                _ => value.to_string(),
+            };
+            Ok(format!(
+                "Unit {}: {} {} = {} done",
+                unit, function, address, value
+            ))
+        }
+        Function::WriteMultipleCoils | Function::WriteMultipleRegisters => {
+            let address = read_u16(pdu, 2).ok_or_else(malformed)?;
+            let quantity = read_u16(pdu, 4).ok_or_else(malformed)?;
+            Ok(format!(
+                "Unit {}: {} at {}, quantity {} done",
+                unit, function, address, quantity
+            ))
+        }
+    }
+}
+
+/// Data served by the slave, only the addresses present can be accessed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub coils: BTreeMap<u16, bool>,
+    pub discrete_inputs: BTreeMap<u16, bool>,
+    pub holding_registers: BTreeMap<u16, u16>,
+    pub input_registers: BTreeMap<u16, u16>,
+}
+
+impl Table {
+    /// Parse a table written as one `<coil|discrete|holding|input> <address> = <value>` per
+    /// line, empty lines and lines starting with `#` are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut table = Table::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = || format!("Incorrect Modbus table line {}", number + 1);
+
+            let mut parts = line.splitn(2, '=');
+            let mut entry = parts.next().unwrap_or("").split_whitespace();
+            let (kind, address) = match (entry.next(), entry.next(), entry.next()) {
+                (Some(kind), Some(address), None) => (kind, address),
+                _ => return Err(error()),
+            };
+            let address = match parse_values(address).as_deref() {
+                Ok([address]) => *address,
+                _ => return Err(error()),
+            };
+            let value = match parts.next().map(parse_values) {
+                Some(Ok(value)) if value.len() == 1 => value[0],
+                _ => return Err(error()),
+            };
+
+            match kind {
+                "coil" => {
+                    table.coils.insert(address, value != 0);
+                }
+                "discrete" => {
+                    table.discrete_inputs.insert(address, value != 0);
+                }
+                "holding" => {
+                    table.holding_registers.insert(address, value);
+                }
+                "input" => {
+                    table.input_registers.insert(address, value);
+                }
+                _ => return Err(error()),
+            }
+        }
+
+        Ok(table)
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (address, value) in &self.coils {
+            writeln!(f, "coil {} = {}", address, *value as u8)?;
+        }
+        for (address, value) in &self.discrete_inputs {
+            writeln!(f, "discrete {} = {}", address, *value as u8)?;
+        }
+        for (address, value) in &self.holding_registers {
+            writeln!(f, "holding {} = {}", address, value)?;
+        }
+        for (address, value) in &self.input_registers {
+            writeln!(f, "input {} = {}", address, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read `quantity` entries starting at `address`, `None` if one of them is not in `map`.
+fn read_range<T: Copy>(map: &BTreeMap<u16, T>, address: u16, quantity: u16) -> Option<Vec<T>> {
+    (0..quantity)
+        .map(|i| map.get(&address.checked_add(i)?).copied())
+        .collect()
+}
+
+/// Write `values` starting at `address`, nothing is written if one of them is not in `map`.
+fn write_range<T: Copy>(map: &mut BTreeMap<u16, T>, address: u16, values: &[T]) -> bool {
+    let addresses: Option<Vec<u16>> = (0..values.len() as u16)
+        .map(|i| address.checked_add(i))
+        .collect();
+
+    match addresses {
+        Some(addresses) if addresses.iter().all(|address| map.contains_key(address)) => {
+            for (address, value) in addresses.into_iter().zip(values) {
+                map.insert(address, *value);
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slave {
+    pub unit: u8,
+    pub table: Table,
+}
+
+impl Slave {
+    pub fn new(unit: u8, table: Table) -> Self {
+        Slave { unit, table }
+    }
+
+    /// Apply a request to the table and build the response. Nothing is returned for invalid
+    /// frames, requests addressed to another unit and broadcasts, broadcast reads are ignored.
+    pub fn respond(&mut self, frame: &[u8]) -> Option<Vec<u8>> {
+        let pdu = check_crc(frame)?;
+        let unit = pdu[0];
+        if unit != self.unit && unit != 0 {
+            return None;
+        }
+        // Only the writes can be broadcast
+        let read = matches!(
+            Function::from_code(pdu[1]),
+            Some(
+                Function::ReadCoils
+                    | Function::ReadDiscreteInputs
+                    | Function::ReadHoldingRegisters
+                    | Function::ReadInputRegisters
+            )
+        );
+        if unit == 0 && read {
+            return None;
+        }
+
+        let mut response = vec![self.unit, pdu[1]];
+        match self.execute(pdu) {
+            Ok(data) => response.extend_from_slice(&data),
+            Err(code) => {
+                response[1] |= 0x80;
+                response.push(code);
+            }
+        }
+
+        if unit == 0 {
+            return None;
+        }
+        append_crc(&mut response);
+        Some(response)
+    }
+
+    /// Execute the request, returning the data of the response or an exception code.
+    fn execute(&mut self, pdu: &[u8]) -> Result<Vec<u8>, u8> {
+        const ILLEGAL_FUNCTION: u8 = 0x01;
+        const ILLEGAL_DATA_ADDRESS: u8 = 0x02;
+        const ILLEGAL_DATA_VALUE: u8 = 0x03;
+
+        let function = Function::from_code(pdu[1]).ok_or(ILLEGAL_FUNCTION)?;
+        let address = read_u16(pdu, 2).ok_or(ILLEGAL_DATA_VALUE)?;
+        let value = read_u16(pdu, 4).ok_or(ILLEGAL_DATA_VALUE)?;
+        let echo = pdu[2..6].to_vec();
+
+        match function {
+            Function::ReadCoils | Function::ReadDiscreteInputs => {
+                if value == 0 || value > 2000 {
+                    return Err(ILLEGAL_DATA_VALUE);
+                }
+                let map = if function == Function::ReadCoils {
+                    &self.table.coils
+                } else {
+                    &self.table.discrete_inputs
+                };
+                let bytes =
+                    pack_bits(&read_range(map, address, value).ok_or(ILLEGAL_DATA_ADDRESS)?);
+                let mut data = vec![bytes.len() as u8];
+                data.extend_from_slice(&bytes);
+                Ok(data)
+            }
+            Function::ReadHoldingRegisters | Function::ReadInputRegisters => {
+                if value == 0 || value > 125 {
+                    return Err(ILLEGAL_DATA_VALUE);
+                }
+                let map = if function == Function::ReadHoldingRegisters {
+                    &self.table.holding_registers
+                } else {
+                    &self.table.input_registers
+                };
+                let registers = read_range(map, address, value).ok_or(ILLEGAL_DATA_ADDRESS)?;
+                let mut data = vec![(registers.len() * 2) as u8];
+                for register in registers {
+                    data.extend_from_slice(&register.to_be_bytes());
+                }
+                Ok(data)
+            }
+            Function::WriteSingleCoil => {
+                let coil = match value {
+                    0xFF00 => true,
+                    0x0000 => false,
+                    _ => return Err(ILLEGAL_DATA_VALUE),
+                };
+                if !write_range(&mut self.table.coils, address, &[coil]) {
+                    return Err(ILLEGAL_DATA_ADDRESS);
+                }
+                Ok(echo)
+            }
+            Function::WriteSingleRegister => {
+                if !write_range(&mut self.table.holding_registers, address, &[value]) {
+                    return Err(ILLEGAL_DATA_ADDRESS);
+                }
+                Ok(echo)
+            }
+            Function::WriteMultipleCoils => {
+                let bytes = pdu.get(7..).ok_or(ILLEGAL_DATA_VALUE)?;
+                let count = (value as usize).div_ceil(8);
+                if value == 0 || value > 1968 || pdu[6] as usize != count || bytes.len() != count {
+                    return Err(ILLEGAL_DATA_VALUE);
+                }
+                let coils = unpack_bits(bytes, value as usize);
+                if !write_range(&mut self.table.coils, address, &coils) {
+                    return Err(ILLEGAL_DATA_ADDRESS);
+                }
+                Ok(echo)
+            }
+            Function::WriteMultipleRegisters => {
+                let bytes = pdu.get(7..).ok_or(ILLEGAL_DATA_VALUE)?;
+                let count = value as usize * 2;
+                if value == 0 || value > 123 || pdu[6] as usize != count || bytes.len() != count {
+                    return Err(ILLEGAL_DATA_VALUE);
+                }
+                let values = registers(bytes);
+                if !write_range(&mut self.table.holding_registers, address, &values) {
+                    return Err(ILLEGAL_DATA_ADDRESS);
+                }
+                Ok(echo)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frame with its CRC appended.
+    fn with_crc(pdu: &[u8]) -> Vec<u8> {
+        let mut frame = pdu.to_vec();
+        append_crc(&mut frame);
+        frame
+    }
+
+    #[test]
+    fn crc_low_byte_first() {
+        assert_eq!(crc16(b"123456789"), 0x4B37);
+        assert_eq!(
+            with_crc(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]),
+            [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]
+        );
+        assert_eq!(
+            check_crc(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]).map(<[u8]>::len),
+            Some(6)
+        );
+        assert_eq!(
+            check_crc(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xCD, 0xC5]),
+            None
+        );
+        assert_eq!(check_crc(&[0x01, 0x03, 0x00]), None);
+    }
+
+    #[test]
+    fn read_request() {
+        let frame = build_request(1, Function::ReadHoldingRegisters, 0, 10, &[]).unwrap();
+        assert_eq!(frame, [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]);
+        assert!(build_request(1, Function::ReadHoldingRegisters, 0, 126, &[]).is_err());
+        assert!(build_request(1, Function::ReadCoils, 0, 0, &[]).is_err());
+    }
+
+    #[test]
+    fn write_requests() {
+        let frame = build_request(17, Function::WriteSingleCoil, 0xAC, 0, &[1]).unwrap();
+        assert_eq!(frame, with_crc(&[0x11, 0x05, 0x00, 0xAC, 0xFF, 0x00]));
+
+        let frame = build_request(
+            17,
+            Function::WriteMultipleCoils,
+            19,
+            0,
+            &[1, 0, 1, 1, 0, 0, 1, 1, 1, 0],
+        )
+        .unwrap();
+        assert_eq!(
+            frame,
+            with_crc(&[0x11, 0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01])
+        );
+
+        let frame = build_request(
+            17,
+            Function::WriteMultipleRegisters,
+            1,
+            0,
+            &[0x000A, 0x0102],
+        )
+        .unwrap();
+        assert_eq!(
+            frame,
+            with_crc(&[0x11, 0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02])
+        );
+        assert!(build_request(17, Function::WriteSingleRegister, 1, 0, &[1, 2]).is_err());
+    }
+
+    #[test]
+    fn register_response_named_by_request() {
+        let request = build_request(1, Function::ReadHoldingRegisters, 100, 2, &[]).unwrap();
+        let response = with_crc(&[0x01, 0x03, 0x04, 0x00, 0x0A, 0x01, 0x02]);
+        assert_eq!(
+            describe_response(Some(&request), &response).as_deref(),
+            Ok("Unit 1: Read holding registers at 100: 10, 258")
+        );
+        assert_eq!(
+            describe_response(None, &response).as_deref(),
+            Ok("Unit 1: Read holding registers: 10, 258")
+        );
+    }
+
+    #[test]
+    fn coil_response_cut_to_quantity() {
+        let request = build_request(1, Function::ReadCoils, 20, 3, &[]).unwrap();
+        let response = with_crc(&[0x01, 0x01, 0x01, 0x05]);
+        assert_eq!(
+            describe_response(Some(&request), &response).as_deref(),
+            Ok("Unit 1: Read coils at 20: 1 0 1")
+        );
+    }
+
+    #[test]
+    fn exception_response() {
+        let response = with_crc(&[0x01, 0x83, 0x02]);
+        assert_eq!(
+            describe_response(None, &response),
+            Err("Unit 1: exception 0x02 on function 0x03: Illegal data address".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_responses() {
+        assert_eq!(
+            describe_response(None, &[0x01, 0x03, 0x02, 0x00, 0x00, 0x00]),
+            Err("CRC error".to_string())
+        );
+        // Byte count beyond the frame
+        let response = with_crc(&[0x01, 0x03, 0x04, 0x00, 0x0A]);
+        assert_eq!(
+            describe_response(None, &response),
+            Err("Unit 1: malformed Read holding registers response".to_string())
+        );
+    }
+
+    fn slave() -> Slave {
+        let table = Table::parse(
+            "coil 0 = 1\ncoil 1 = 0\ncoil 2 = 1\n\
+             holding 10 = 0x1234\nholding 11 = 7\ninput 5 = 42",
+        )
+        .unwrap();
+        Slave::new(1, table)
+    }
+
+    #[test]
+    fn table_round_trip() {
+        let text = "coil 1 = 1\ndiscrete 2 = 0\nholding 3 = 65535\ninput 4 = 5\n";
+        let table = Table::parse(text).unwrap();
+        assert_eq!(table.holding_registers.get(&3), Some(&0xFFFF));
+        assert_eq!(table.to_string(), text);
+
+        let table = Table::parse("# comment\n\n  holding 0x10 = 0x20  \n").unwrap();
+        assert_eq!(table.to_string(), "holding 16 = 32\n");
+    }
+
+    #[test]
+    fn table_errors() {
+        assert_eq!(
+            Table::parse("coil 1 = 1\nregister 2 = 3"),
+            Err("Incorrect Modbus table line 2".to_string())
+        );
+        assert!(Table::parse("coil 1 2 = 1").is_err());
+        assert!(Table::parse("holding 1 = 1, 2").is_err());
+        assert!(Table::parse("holding 1").is_err());
+    }
+
+    #[test]
+    fn slave_reads() {
+        let mut slave = slave();
+        let request = build_request(1, Function::ReadHoldingRegisters, 10, 2, &[]).unwrap();
+        assert_eq!(
+            slave.respond(&request),
+            Some(with_crc(&[0x01, 0x03, 0x04, 0x12, 0x34, 0x00, 0x07]))
+        );
+        let request = build_request(1, Function::ReadCoils, 0, 3, &[]).unwrap();
+        assert_eq!(
+            slave.respond(&request),
+            Some(with_crc(&[0x01, 0x01, 0x01, 0x05]))
+        );
+    }
+
+    #[test]
+    fn slave_writes() {
+        let mut slave = slave();
+        let request = build_request(1, Function::WriteMultipleRegisters, 10, 0, &[1, 2]).unwrap();
+        assert_eq!(
+            slave.respond(&request),
+            Some(with_crc(&[0x01, 0x10, 0x00, 0x0A, 0x00, 0x02]))
+        );
+        assert_eq!(slave.table.holding_registers.get(&11), Some(&2));
+
+        let request = build_request(1, Function::WriteSingleCoil, 1, 0, &[1]).unwrap();
+        assert_eq!(slave.respond(&request), Some(request));
+        assert_eq!(slave.table.coils.get(&1), Some(&true));
+    }
+
+    #[test]
+    fn slave_exceptions() {
+        let mut slave = slave();
+        // Unknown function
+        let request = with_crc(&[0x01, 0x07, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(slave.respond(&request), Some(with_crc(&[0x01, 0x87, 0x01])));
+        // Address 12 is not in the table
+        let request = build_request(1, Function::ReadHoldingRegisters, 11, 2, &[]).unwrap();
+        assert_eq!(slave.respond(&request), Some(with_crc(&[0x01, 0x83, 0x02])));
+        let request = build_request(1, Function::WriteSingleRegister, 12, 0, &[1]).unwrap();
+        assert_eq!(slave.respond(&request), Some(with_crc(&[0x01, 0x86, 0x02])));
+        // Nothing is written when one of the addresses is missing
+        let request = build_request(1, Function::WriteMultipleRegisters, 11, 0, &[1, 2]).unwrap();
+        assert_eq!(slave.respond(&request), Some(with_crc(&[0x01, 0x90, 0x02])));
+        assert_eq!(slave.table.holding_registers.get(&11), Some(&7));
+    }
+
+    #[test]
+    fn slave_byte_count_checked() {
+        let mut slave = slave();
+        // Byte count of 3 for 2 registers
+        let request = with_crc(&[0x01, 0x10, 0x00, 0x0A, 0x00, 0x02, 0x03, 0, 1, 0, 2]);
+        assert_eq!(slave.respond(&request), Some(with_crc(&[0x01, 0x90, 0x03])));
+        // Trailing byte after the registers
+        let request = with_crc(&[0x01, 0x10, 0x00, 0x0A, 0x00, 0x02, 0x04, 0, 1, 0, 2, 0]);
+        assert_eq!(slave.respond(&request), Some(with_crc(&[0x01, 0x90, 0x03])));
+        // Two bytes for three coils
+        let request = with_crc(&[0x01, 0x0F, 0x00, 0x00, 0x00, 0x03, 0x02, 0x07, 0x00]);
+        assert_eq!(slave.respond(&request), Some(with_crc(&[0x01, 0x8F, 0x03])));
+        assert_eq!(slave.table.holding_registers.get(&10), Some(&0x1234));
+        assert_eq!(slave.table.coils.get(&1), Some(&false));
+    }
+
+    #[test]
+    fn slave_ignores_other_units() {
+        let mut slave = slave();
+        let request = build_request(2, Function::WriteSingleRegister, 10, 0, &[1]).unwrap();
+        assert_eq!(slave.respond(&request), None);
+        assert_eq!(slave.table.holding_registers.get(&10), Some(&0x1234));
+        // CRC error
+        let mut request = build_request(1, Function::ReadInputRegisters, 5, 1, &[]).unwrap();
+        request[7] ^= 0xFF;
+        assert_eq!(slave.respond(&request), None);
+    }
+
+    #[test]
+    fn slave_broadcasts() {
+        let mut slave = slave();
+        // Writes are applied without response
+        let request = build_request(0, Function::WriteSingleRegister, 10, 0, &[9]).unwrap();
+        assert_eq!(slave.respond(&request), None);
+        assert_eq!(slave.table.holding_registers.get(&10), Some(&9));
+        // Reads are ignored
+        let request = build_request(0, Function::ReadHoldingRegisters, 10, 1, &[]).unwrap();
+        assert_eq!(slave.respond(&request), None);
+    }
+}
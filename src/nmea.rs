@@ -0,0 +1,234 @@
+//! NMEA 0183 sentences: checksum validation and decoding of the usual GPS sentences.
+
+use crate::checksum::Algorithm;
+
+/// A sentence with a valid checksum, `$GPGGA,...` gives the talker `GP` and the kind `GGA`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sentence {
+    pub talker: String,
+    pub kind: String,
+    pub fields: Vec<String>,
+}
+
+impl Sentence {
+    /// Talker and kind, as written at the beginning of the sentence.
+    pub fn id(&self) -> String {
+        format!("{}{}", self.talker, self.kind)
+    }
+
+    /// GSV sentences are split in several messages, true for the ones after the first.
+    pub fn is_continuation(&self) -> bool {
+        self.kind == "GSV" && self.field(1).map_or(false, |number| number != "1")
+    }
+
+    fn field(&self, index: usize) -> Option<&str> {
+        self.fields
+            .get(index)
+            .map(String::as_str)
+            .filter(|field| !field.is_empty())
+    }
+}
+
+/// Parse a `$...*hh` sentence, the trailing CR LF being optional.
+pub fn parse(line: &str) -> Result<Sentence, &'static str> {
+    let line = line.trim_end_matches(|c| c == '\r' || c == '\n');
+    let body = line.strip_prefix('$').ok_or("Missing start of sentence")?;
+    let star = body.rfind('*').ok_or("Missing checksum")?;
+    let (body, checksum) = (&body[..star], &body[star + 1..]);
+
+    let expected = u8::from_str_radix(checksum, 16).map_err(|_| "Invalid checksum")?;
+    if checksum.len() != 2 || Algorithm::Xor8.compute(body.as_bytes()) != expected as u32 {
+        return Err("Wrong checksum");
+    }
+
+    let mut fields = body.split(',').map(str::to_string);
+    let address = fields.next().unwrap_or_default();
+    if address.len() < 5 || !address.is_ascii() {
+        return Err("Invalid address field");
+    }
+    let (talker, kind) = address.split_at(address.len() - 3);
+
+    Ok(Sentence {
+        talker: talker.to_string(),
+        kind: kind.to_string(),
+        fields: fields.collect(),
+    })
+}
+
+fn time(field: &str) -> Option<String> {
+    if field.len() < 6 || !field.is_ascii() {
+        return None;
+    }
+    Some(format!("{}:{}:{}", &field[0..2], &field[2..4], &field[4..]))
+}
+
+fn date(field: &str) -> Option<String> {
+    if field.len() != 6 || !field.is_ascii() {
+        return None;
+    }
+    Some(format!(
+        "{}/{}/{}",
+        &field[0..2],
+        &field[2..4],
+        &field[4..6]
+    ))
+}
+
+/// Convert a `ddmm.mmmm` coordinate and its hemisphere to decimal degrees.
+fn coordinate(value: &str, hemisphere: &str) -> Option<String> {
+    if !value.is_ascii() {
+        return None;
+    }
+    let dot = value.find('.').unwrap_or(value.len());
+    if dot < 2 {
+        return None;
+    }
+    let degrees: f64 = value[..dot - 2].parse().ok()?;
+    let minutes: f64 = value[dot - 2..].parse().ok()?;
+    Some(format!("{:.6}° {}", degrees + minutes / 60., hemisphere))
+}
+
+fn fix_quality(field: &str) -> &str {
+    match field {
+        "0" => "Invalid",
+        "1" => "GPS fix",
+        "2" => "DGPS fix",
+        "3" => "PPS fix",
+        "4" => "RTK",
+        "5" => "Float RTK",
+        "6" => "Estimated",
+        "7" => "Manual",
+        "8" => "Simulation",
+        _ => field,
+    }
+}
+
+/// Decoded fields of the GGA, RMC, GSV and VTG sentences as `(name, value)` pairs, `None` for
+/// the other kinds. Empty fields are left out.
+pub fn describe(sentence: &Sentence) -> Option<Vec<(String, String)>> {
+    let mut fields = Vec::new();
+    let mut push = |name: &str, value: Option<String>| {
+        if let Some(value) = value {
+            fields.push((name.to_string(), value));
+        }
+    };
+    let field = |index| sentence.field(index);
+    let text = |index| field(index).map(str::to_string);
+    let position = |index| coordinate(field(index)?, field(index + 1)?);
+
+    match sentence.kind.as_str() {
+        "GGA" => {
+            push("Time", field(0).and_then(time));
+            push("Latitude", position(1));
+            push("Longitude", position(3));
+            push(
+                "Fix",
+                field(5).map(|quality| fix_quality(quality).to_string()),
+            );
+            push("Satellites", text(6));
+            push("HDOP", text(7));
+            push(
+                "Altitude",
+                field(8).map(|altitude| format!("{} {}", altitude, field(9).unwrap_or(""))),
+            );
+        }
+        "RMC" => {
+            push("Time", field(0).and_then(time));
+            push(
+                "Status",
+                field(1).map(|status| if status == "A" { "Active" } else { "Void" }.to_string()),
+            );
+            push("Latitude", position(2));
+            push("Longitude", position(4));
+            push("Speed", field(6).map(|speed| format!("{} kn", speed)));
+            push("Course", field(7).map(|course| format!("{}°", course)));
+            push("Date", field(8).and_then(date));
+        }
+        "VTG" => {
+            push("Course", field(0).map(|course| format!("{}° true", course)));
+            push(
+                "Course",
+                field(2).map(|course| format!("{}° magnetic", course)),
+            );
+            push("Speed", field(4).map(|speed| format!("{} kn", speed)));
+            push("Speed", field(6).map(|speed| format!("{} km/h", speed)));
+        }
+        "GSV" => {
+            if !sentence.is_continuation() {
+                push("In view", text(2));
+            }
+            // Satellites come by groups of four fields: PRN, elevation, azimuth and SNR
+            for satellite in (3..sentence.fields.len()).step_by(4) {
+                if let Some(prn) = field(satellite) {
+                    push(
+                        &format!("PRN {}", prn),
+                        Some(format!(
+                            "elev {}° az {}° SNR {}",
+                            field(satellite + 1).unwrap_or("-"),
+                            field(satellite + 2).unwrap_or("-"),
+                            field(satellite + 3).unwrap_or("-"),
+                        )),
+                    );
+                }
+            }
+        }
+        _ => return None,
+    }
+
+    Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coordinate_in_degrees() {
+        assert_eq!(coordinate("4807.038", "N").as_deref(), Some("48.117300° N"));
+    }
+
+    #[test]
+    fn non_ascii_coordinate() {
+        assert_eq!(coordinate("é1.5", "N"), None);
+        assert_eq!(coordinate("01°30.5", "E"), None);
+    }
+
+    const GGA: &str = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+
+    #[test]
+    fn valid_sentence() {
+        let sentence = parse(&format!("{}\r\n", GGA)).unwrap();
+        assert_eq!(sentence.id(), "GPGGA");
+        assert_eq!(sentence.fields.len(), 14);
+        let fields = describe(&sentence).unwrap();
+        assert_eq!(fields[0], ("Time".to_string(), "12:35:19".to_string()));
+        assert_eq!(fields[3], ("Fix".to_string(), "GPS fix".to_string()));
+        assert_eq!(fields[6], ("Altitude".to_string(), "545.4 M".to_string()));
+    }
+
+    #[test]
+    fn checksum_rejected() {
+        let wrong = GGA.replace("*47", "*48");
+        assert_eq!(parse(&wrong), Err("Wrong checksum"));
+        // One digit changed in the body
+        let wrong = GGA.replace("4807", "4808");
+        assert_eq!(parse(&wrong), Err("Wrong checksum"));
+        assert_eq!(parse(&GGA.replace("*47", "*7")), Err("Wrong checksum"));
+        assert_eq!(parse(&GGA.replace("*47", "*4G")), Err("Invalid checksum"));
+        assert_eq!(parse(&GGA.replace("*47", "")), Err("Missing checksum"));
+        assert_eq!(parse(&GGA[1..]), Err("Missing start of sentence"));
+    }
+
+    #[test]
+    fn gsv_continuation() {
+        let first =
+            parse("$GPGSV,2,1,08,01,40,083,46,02,17,308,41,12,07,344,39,14,22,228,45*75").unwrap();
+        assert!(!first.is_continuation());
+        let fields = describe(&first).unwrap();
+        assert_eq!(fields[0], ("In view".to_string(), "08".to_string()));
+        assert_eq!(
+            fields[1],
+            ("PRN 01".to_string(), "elev 40° az 083° SNR 46".to_string())
+        );
+    }
+}
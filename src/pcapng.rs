@@ -0,0 +1,272 @@
+//! Export of the captures to pcapng, to open the serial sessions in Wireshark.
+//!
+//! The packets use the `USER0` link type, to be given to a dissector in the DLT_USER table of
+//! Wireshark, with an interface for each port and the direction in the flags of each packet.
+//! The received bytes are split again with the framing of the capture so each packet is a frame.
+
+use crate::capture::{Entry, Record};
+use crate::data::Framing;
+use crate::serial::{ByteDirection, FrameCodec};
+use bytes::BytesMut;
+use std::path::Path;
+use tokio_util::codec::Decoder;
+
+/// `LINKTYPE_USER0`, reserved for private use.
+pub const LINK_TYPE: u16 = 147;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 1;
+const ENHANCED_PACKET_BLOCK: u32 = 6;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const OPT_END: u16 = 0;
+const SHB_USERAPPL: u16 = 4;
+const IF_NAME: u16 = 2;
+const EPB_FLAGS: u16 = 2;
+
+const FLAG_INBOUND: u32 = 1;
+const FLAG_OUTBOUND: u32 = 2;
+
+fn push_option(block: &mut Vec<u8>, code: u16, value: &[u8]) {
+    block.extend_from_slice(&code.to_le_bytes());
+    block.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    block.extend_from_slice(value);
+    block.resize(block.len() + (4 - value.len() % 4) % 4, 0);
+}
+
+/// Append a block with its type and total length around `body`.
+fn push_block(file: &mut Vec<u8>, kind: u32, mut body: Vec<u8>) {
+    push_option(&mut body, OPT_END, &[]);
+    let len = (body.len() + 12) as u32;
+    file.extend_from_slice(&kind.to_le_bytes());
+    file.extend_from_slice(&len.to_le_bytes());
+    file.extend_from_slice(&body);
+    file.extend_from_slice(&len.to_le_bytes());
+}
+
+struct Writer {
+    file: Vec<u8>,
+    interfaces: Vec<String>,
+}
+
+impl Writer {
+    fn new() -> Self {
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // Unknown section length
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        push_option(&mut body, SHB_USERAPPL, b"stool");
+
+        let mut file = Vec::new();
+        push_block(&mut file, SECTION_HEADER_BLOCK, body);
+        Writer {
+            file,
+            interfaces: Vec::new(),
+        }
+    }
+
+    /// Identifier of the interface of `port_name`, described when it is first seen.
+    fn interface(&mut self, port_name: &str) -> u32 {
+        if let Some(id) = self.interfaces.iter().position(|name| name == port_name) {
+            return id as u32;
+        }
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&LINK_TYPE.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // No snapshot length limit
+        body.extend_from_slice(&0u32.to_le_bytes());
+        push_option(&mut body, IF_NAME, port_name.as_bytes());
+        push_block(&mut self.file, INTERFACE_DESCRIPTION_BLOCK, body);
+
+        self.interfaces.push(port_name.to_string());
+        self.interfaces.len() as u32 - 1
+    }
+
+    /// Packet at `time` in microseconds, the default resolution of the timestamps.
+    fn packet(&mut self, interface: u32, time: u64, direction: ByteDirection, data: &[u8]) {
+        let mut body = Vec::new();
+        body.extend_from_slice(&interface.to_le_bytes());
+        body.extend_from_slice(&((time >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(time as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        body.resize(body.len() + (4 - data.len() % 4) % 4, 0);
+        let flags = match direction {
+            ByteDirection::In => FLAG_INBOUND,
+            ByteDirection::Out => FLAG_OUTBOUND,
+        };
+        push_option(&mut body, EPB_FLAGS, &flags.to_le_bytes());
+        push_block(&mut self.file, ENHANCED_PACKET_BLOCK, body);
+    }
+}
+
+/// The capture as a pcapng file, the layout framing reads the layout file of the settings.
+pub fn export(entries: &[Entry]) -> Result<Vec<u8>, String> {
+    let mut writer = Writer::new();
+    let mut interface = None;
+    let mut codec = None;
+    let mut received = BytesMut::new();
+    // Time of the last data, given to the bytes left unframed
+    let mut last_time = 0;
+
+    for entry in entries {
+        match &entry.record {
+            Record::Settings(settings) => {
+                let id = writer.interface(&settings.port_name);
+                // The bytes left by the previous framing are given alone
+                if let (Some(previous), false) = (interface, received.is_empty()) {
+                    writer.packet(previous, last_time, ByteDirection::In, &received.split());
+                }
+                interface = Some(id);
+                let layout = if settings.framing == Framing::Layout {
+                    settings.load_layout()?
+                } else {
+                    None
+                };
+                codec = Some(FrameCodec::new(&settings.open_message(layout)));
+            }
+            Record::Data(direction, data) => {
+                let id = match interface {
+                    Some(id) => id,
+                    None => writer.interface(""),
+                };
+                interface = Some(id);
+                last_time = entry.time;
+                match (direction, &mut codec) {
+                    (ByteDirection::In, Some(codec)) => {
+                        received.extend_from_slice(data);
+                        while let Ok(Some(frame)) = codec.decode(&mut received) {
+                            writer.packet(id, entry.time, ByteDirection::In, &frame);
+                        }
+                    }
+                    _ => writer.packet(id, entry.time, *direction, data),
+                }
+            }
+        }
+    }
+    if let (Some(id), false) = (interface, received.is_empty()) {
+        writer.packet(id, last_time, ByteDirection::In, &received);
+    }
+
+    Ok(writer.file)
+}
+
+pub fn write(entries: &[Entry], path: &Path) -> Result<(), String> {
+    std::fs::write(path, export(entries)?).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{self, CaptureWriter};
+    use crate::data::Protocol;
+    use crate::profile::Profile;
+    use std::convert::TryInto;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("stool-pcapng-{}-{}", std::process::id(), name))
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    /// Enhanced packet blocks of `file` as their time, data and flags, the lengths of all the
+    /// blocks are checked.
+    fn packets(file: &[u8]) -> Vec<(u64, Vec<u8>, u32)> {
+        let mut packets = Vec::new();
+        let mut rest = file;
+        while !rest.is_empty() {
+            let len = u32_at(rest, 4) as usize;
+            assert_eq!(len % 4, 0);
+            assert_eq!(u32_at(rest, len - 4) as usize, len);
+            if u32_at(rest, 0) == ENHANCED_PACKET_BLOCK {
+                let body = &rest[8..len - 4];
+                let time = (u32_at(body, 4) as u64) << 32 | u32_at(body, 8) as u64;
+                let size = u32_at(body, 12) as usize;
+                let options = &body[20 + size.div_ceil(4) * 4..];
+                assert_eq!(u16::from_le_bytes([options[0], options[1]]), EPB_FLAGS);
+                packets.push((time, body[20..20 + size].to_vec(), u32_at(options, 4)));
+            }
+            rest = &rest[len..];
+        }
+        packets
+    }
+
+    /// Capture written then read back.
+    fn capture(name: &str, settings: &Profile, data: &[(ByteDirection, &[u8])]) -> Vec<Entry> {
+        let path = temp_path(name);
+        let mut writer = CaptureWriter::create(&path, settings).unwrap();
+        for (direction, data) in data {
+            writer.write_data(*direction, data).unwrap();
+        }
+        drop(writer);
+        let entries = capture::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        entries
+    }
+
+    #[test]
+    fn frames_split_with_the_capture_framing() {
+        let settings = Profile {
+            port_name: "COM1".to_string(),
+            framing: Framing::Delimiter,
+            ..Profile::default()
+        };
+        let entries = capture(
+            "delimiter",
+            &settings,
+            &[
+                (ByteDirection::In, b"AB\nC"),
+                (ByteDirection::Out, b"xyz"),
+                (ByteDirection::In, b"D\nE"),
+            ],
+        );
+        let file = export(&entries).unwrap();
+        assert_eq!(u32_at(&file, 0), SECTION_HEADER_BLOCK);
+
+        let packets = packets(&file);
+        let data: Vec<_> = packets
+            .iter()
+            .map(|(_, data, flags)| (data.as_slice(), *flags))
+            .collect();
+        assert_eq!(
+            data,
+            [
+                (&b"AB\n"[..], FLAG_INBOUND),
+                (b"xyz", FLAG_OUTBOUND),
+                (b"CD\n", FLAG_INBOUND),
+                (b"E", FLAG_INBOUND),
+            ]
+        );
+        // The bytes left at the end keep the time of the last data
+        assert_eq!(packets[3].0, entries.last().unwrap().time);
+    }
+
+    #[test]
+    fn layout_of_the_capture() {
+        let layout = temp_path("layout.toml");
+        std::fs::write(&layout, "size = 2").unwrap();
+        let settings = Profile {
+            protocol: Protocol::Raw,
+            framing: Framing::Layout,
+            layout: Some(layout.clone()),
+            ..Profile::default()
+        };
+        let entries = capture("layout", &settings, &[(ByteDirection::In, b"12345")]);
+        let exported = export(&entries);
+        let _ = std::fs::remove_file(&layout);
+
+        let data: Vec<_> = packets(&exported.unwrap())
+            .into_iter()
+            .map(|(_, data, _)| data)
+            .collect();
+        assert_eq!(data, [&b"12"[..], b"34", b"5"]);
+        assert!(export(&entries).is_err());
+    }
+}
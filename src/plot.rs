@@ -0,0 +1,253 @@
+//! Numbers parsed from the received lines, kept by channel to be plotted.
+//!
+//! A line gives samples as comma separated values (`23.4,41`), keys and values
+//! (`temp=23.4,hum=41`) or the capture groups of a regex (`T:(?P<temp>[\d.]+)`).
+//!
+//! Binary frames give the numeric fields of a list, one field by line as
+//! `<name> <type> <offset> [big|little]`, or the fields of the frame layout:
+//!
+//! ```text
+//! adc0 int16 4 little
+//! adc1 int16 6 little
+//! voltage float32 8
+//! ```
+
+use crate::checksum::Endianness;
+use crate::layout::{self, Field, FieldType};
+use druid::Data;
+use regex::Regex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Points kept by channel, the oldest are dropped.
+pub const MAX_POINTS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub enum PlotFormat {
+    Csv,
+    KeyValue,
+    Regex,
+    /// Fields of the binary frames.
+    Fields,
+}
+
+#[derive(Debug, Clone, Data)]
+pub struct Channel {
+    pub name: String,
+    /// Time in seconds since the plot started and value.
+    pub points: Arc<VecDeque<(f64, f64)>>,
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    text.trim()
+        .parse()
+        .ok()
+        .filter(|value: &f64| value.is_finite())
+}
+
+/// Samples of `line` with the name of their channel, `regex` is used by the regex format.
+pub fn parse_line(format: PlotFormat, line: &str, regex: Option<&Regex>) -> Vec<(String, f64)> {
+    let line = line.trim();
+    match format {
+        PlotFormat::Csv => line
+            .split(|c| c == ',' || c == ';' || c == '\t')
+            .enumerate()
+            .filter_map(|(index, value)| Some((format!("{}", index + 1), parse_number(value)?)))
+            .collect(),
+        PlotFormat::KeyValue => line
+            .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+            .filter_map(|pair| {
+                let (key, value) = pair.split_at(pair.find(|c| c == '=' || c == ':')?);
+                let key = key.trim();
+                if key.is_empty() {
+                    return None;
+                }
+                Some((key.to_string(), parse_number(&value[1..])?))
+            })
+            .collect(),
+        PlotFormat::Fields => Vec::new(),
+        PlotFormat::Regex => {
+            let regex = match regex {
+                Some(regex) => regex,
+                None => return Vec::new(),
+            };
+            let captures = match regex.captures(line) {
+                Some(captures) => captures,
+                None => return Vec::new(),
+            };
+            regex
+                .capture_names()
+                .enumerate()
+                .skip(1)
+                .filter_map(|(index, name)| {
+                    let value = parse_number(captures.get(index)?.as_str())?;
+                    let name = name.map_or_else(|| index.to_string(), |name| name.to_string());
+                    Some((name, value))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Fields listed one by line as `<name> <type> <offset> [big|little]`, big endian by default.
+pub fn parse_fields(list: &str) -> Result<Vec<Field>, String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (name, kind, offset, endianness) = match words.as_slice() {
+                [name, kind, offset] => (name, kind, offset, Endianness::Big),
+                [name, kind, offset, "big"] => (name, kind, offset, Endianness::Big),
+                [name, kind, offset, "little"] => (name, kind, offset, Endianness::Little),
+                _ => return Err(format!("Incorrect field {:?}", line)),
+            };
+            let kind: FieldType = kind.parse()?;
+            if matches!(kind, FieldType::Bytes | FieldType::Ascii) {
+                return Err(format!("The field {} is not a number", name));
+            }
+            Ok(Field {
+                name: name.to_string(),
+                kind,
+                offset: offset
+                    .parse()
+                    .map_err(|_| format!("Incorrect offset of the field {}", name))?,
+                size: None,
+                endianness,
+            })
+        })
+        .collect()
+}
+
+/// Samples of the numeric `fields` of `frame`, the fields out of the frame are skipped.
+pub fn parse_frame(fields: &[Field], frame: &[u8]) -> Vec<(String, f64)> {
+    fields
+        .iter()
+        .filter_map(|field| Some((field.name.clone(), layout::read_number(field, frame)?)))
+        .collect()
+}
+
+/// Add the samples taken at `time` to their channel, new channels are created as they come.
+pub fn add_samples(channels: &mut Arc<Vec<Channel>>, time: f64, samples: Vec<(String, f64)>) {
+    if samples.is_empty() {
+        return;
+    }
+    let channels = Arc::make_mut(channels);
+    for (name, value) in samples {
+        let index = match channels.iter().position(|channel| channel.name == name) {
+            Some(index) => index,
+            None => {
+                channels.push(Channel {
+                    name,
+                    points: Arc::new(VecDeque::new()),
+                });
+                channels.len() - 1
+            }
+        };
+        let points = Arc::make_mut(&mut channels[index].points);
+        points.push_back((time, value));
+        if points.len() > MAX_POINTS {
+            points.pop_front();
+        }
+    }
+}
+
+/// A row by time with a column by channel, empty when a channel has no sample at this time.
+pub fn to_csv(channels: &[Channel]) -> String {
+    let mut samples: Vec<(f64, usize, f64)> = channels
+        .iter()
+        .enumerate()
+        .flat_map(|(index, channel)| {
+            channel
+                .points
+                .iter()
+                .map(move |&(time, value)| (time, index, value))
+        })
+        .collect();
+    samples.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut csv = String::from("time");
+    for channel in channels {
+        csv.push_str(&format!(",\"{}\"", channel.name.replace('"', "\"\"")));
+    }
+    csv.push('\n');
+
+    let mut samples = samples.into_iter().peekable();
+    while let Some((time, index, value)) = samples.next() {
+        let mut row = vec![String::new(); channels.len()];
+        row[index] = value.to_string();
+        while let Some(&(next_time, index, value)) = samples.peek() {
+            if next_time != time {
+                break;
+            }
+            row[index] = value.to_string();
+            samples.next();
+        }
+        csv.push_str(&format!("{:.3},{}\n", time, row.join(",")));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(values: &[(&str, f64)]) -> Vec<(String, f64)> {
+        values
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value))
+            .collect()
+    }
+
+    #[test]
+    fn csv_line() {
+        assert_eq!(
+            parse_line(PlotFormat::Csv, "1.5, -2;x\t3e2\r\n", None),
+            samples(&[("1", 1.5), ("2", -2.0), ("4", 300.0)])
+        );
+        assert_eq!(parse_line(PlotFormat::Csv, "NaN,inf", None), Vec::new());
+    }
+
+    #[test]
+    fn key_value_line() {
+        assert_eq!(
+            parse_line(PlotFormat::KeyValue, "temp=21.5 hum:40, =3;bad=x", None),
+            samples(&[("temp", 21.5), ("hum", 40.0)])
+        );
+    }
+
+    #[test]
+    fn regex_line() {
+        let regex = Regex::new(r"T=(?P<temp>[-\d.]+) P=([\d.]+)").unwrap();
+        assert_eq!(
+            parse_line(PlotFormat::Regex, "T=-4.5 P=1013", Some(&regex)),
+            samples(&[("temp", -4.5), ("2", 1013.0)])
+        );
+        assert_eq!(
+            parse_line(PlotFormat::Regex, "no match", Some(&regex)),
+            Vec::new()
+        );
+        assert_eq!(parse_line(PlotFormat::Regex, "T=1 P=2", None), Vec::new());
+    }
+
+    #[test]
+    fn frame_fields() {
+        let fields =
+            parse_fields("adc0 int16 0 little\n\n  adc1 uint16 2\nvoltage f32 8\n").unwrap();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[0].endianness, Endianness::Little);
+        assert_eq!(
+            parse_frame(&fields, &[0xFE, 0xFF, 0x01, 0x00, 0xAA]),
+            samples(&[("adc0", -2.0), ("adc1", 256.0)])
+        );
+    }
+
+    #[test]
+    fn incorrect_fields() {
+        assert!(parse_fields("adc0 int16").is_err());
+        assert!(parse_fields("adc0 int24 0").is_err());
+        assert!(parse_fields("adc0 int16 -1").is_err());
+        assert!(parse_fields("adc0 int16 0 middle").is_err());
+        assert!(parse_fields("name ascii 0").is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! Named settings stored in the user config directory, in `stool/profiles/<name>.toml`. The
+//! last profile saved or loaded is restored when the GUI starts.
+
+use crate::checksum::{Algorithm, Endianness};
+use crate::data::{
+    AppData, ChecksumData, DruidDataBits, DruidFlowControl, DruidParity, DruidStopBits, Framing,
+    LineEnding, OpenMessage, Protocol,
+};
+use crate::layout::Layout;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub port_name: String,
+    pub baud_rate: u32,
+    pub data_bits: DruidDataBits,
+    pub flow_control: DruidFlowControl,
+    pub parity: DruidParity,
+    pub stop_bits: DruidStopBits,
+    pub protocol: Protocol,
+    pub line_ending: LineEnding,
+    pub framing: Framing,
+    pub delimiter: u32,
+    /// Layout file of the layout framing.
+    pub layout: Option<PathBuf>,
+    pub show_script: bool,
+    pub show_stats: bool,
+    pub show_log: bool,
+    pub show_capture: bool,
+    pub show_plot: bool,
+    // Tables come last in TOML
+    pub checksum: ChecksumData,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            port_name: "".to_string(),
+            baud_rate: 115_200,
+            data_bits: DruidDataBits::Eight,
+            flow_control: DruidFlowControl::None,
+            parity: DruidParity::None,
+            stop_bits: DruidStopBits::One,
+            protocol: Protocol::Raw,
+            line_ending: LineEnding::None,
+            framing: Framing::None,
+            delimiter: 0x0A,
+            layout: None,
+            show_script: false,
+            show_stats: false,
+            show_log: false,
+            show_capture: false,
+            show_plot: false,
+            checksum: ChecksumData {
+                algorithm: Algorithm::None,
+                endianness: Endianness::Big,
+                skip_start: 0,
+                skip_end: 0,
+            },
+        }
+    }
+}
+
+fn config_dir() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("stool"))
+}
+
+fn profile_path(name: &str) -> Option<PathBuf> {
+    Some(
+        config_dir()?
+            .join("profiles")
+            .join(format!("{}.toml", name)),
+    )
+}
+
+/// File keeping the name of the last profile used.
+fn last_profile_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("last_profile"))
+}
+
+impl Profile {
+    pub fn from_data(data: &AppData) -> Self {
+        Profile {
+            port_name: (*data.port_name).clone(),
+            baud_rate: data.baud_rate,
+            data_bits: data.data_bits,
+            flow_control: data.flow_control,
+            parity: data.parity,
+            stop_bits: data.stop_bits,
+            protocol: data.protocol,
+            line_ending: data.line_ending,
+            framing: data.framing,
+            delimiter: data.delimiter,
+            layout: data.layout.as_ref().and_then(|layout| layout.path.clone()),
+            show_script: data.script.show,
+            show_stats: data.show_stats,
+            show_log: data.session_log.show,
+            show_capture: data.capture.show,
+            show_plot: data.plot.show,
+            checksum: data.checksum.clone(),
+        }
+    }
+
+    /// Apply the settings of the profile to the GUI, the others are applied when the layout
+    /// cannot be loaded.
+    pub fn apply(&self, data: &mut AppData) -> Result<(), String> {
+        data.port_name = Arc::new(self.port_name.clone());
+        data.baud_rate = self.baud_rate;
+        data.data_bits = self.data_bits;
+        data.flow_control = self.flow_control;
+        data.parity = self.parity;
+        data.stop_bits = self.stop_bits;
+        data.protocol = self.protocol;
+        data.line_ending = self.line_ending;
+        data.framing = self.framing;
+        data.delimiter = self.delimiter;
+        data.script.show = self.show_script;
+        data.checksum = self.checksum.clone();
+        match self.load_layout() {
+            Ok(layout) => {
+                data.layout = layout;
+                Ok(())
+            }
+            Err(e) => {
+                data.layout = None;
+                Err(e)
+            }
+        }
+    }
+
+    /// Show the panels of the profile, left as they are by the replays.
+    pub fn apply_panels(&self, data: &mut AppData) {
+        data.show_stats = self.show_stats;
+        data.session_log.show = self.show_log;
+        data.capture.show = self.show_capture;
+        data.plot.show = self.show_plot;
+    }
+
+    /// Read the layout file of the profile, if any.
+    pub fn load_layout(&self) -> Result<Option<Arc<Layout>>, String> {
+        match &self.layout {
+            Some(path) => Layout::load(path)
+                .map(|layout| Some(Arc::new(layout)))
+                .map_err(|e| format!("Cannot load the frame layout {}: {}", path.display(), e)),
+            None => Ok(None),
+        }
+    }
+
+    /// Port opened with the profile, `layout` is used by the layout framing.
+    pub fn open_message(&self, layout: Option<Arc<Layout>>) -> OpenMessage {
+        OpenMessage {
+            port_name: self.port_name.clone(),
+            baud_rate: self.baud_rate,
+            data_bits: self.data_bits,
+            flow_control: self.flow_control,
+            parity: self.parity,
+            stop_bits: self.stop_bits,
+            protocol: self.protocol,
+            framing: self.framing,
+            delimiter: u8::try_from(self.delimiter).unwrap_or(b'\n'),
+            layout,
+        }
+    }
+
+    pub fn load(name: &str) -> Result<Self, String> {
+        check_name(name)?;
+        let path = profile_path(name).ok_or("No user config directory")?;
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("Incorrect profile {}: {}", name, e))
+    }
+
+    /// Save the profile as `name` and make it the last profile.
+    pub fn save(&self, name: &str) -> Result<(), String> {
+        check_name(name)?;
+        let path = profile_path(name).ok_or("No user config directory")?;
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(&path, text).map_err(|e| e.to_string())?;
+        set_last_name(name)
+    }
+}
+
+/// Profile names are file names in the profile directory, without path nor extension.
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(|c| matches!(c, '/' | '\\' | '.')) {
+        return Err("Incorrect profile name".to_string());
+    }
+    Ok(())
+}
+
+/// Names of the saved profiles in alphabetical order.
+pub fn names() -> Vec<String> {
+    let directory = match config_dir() {
+        Some(directory) => directory.join("profiles"),
+        None => return Vec::new(),
+    };
+    let mut names: Vec<String> = std::fs::read_dir(directory)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            match path.extension() {
+                Some(extension) if extension == "toml" => {
+                    Some(path.file_stem()?.to_string_lossy().to_string())
+                }
+                _ => None,
+            }
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+pub fn last_name() -> Option<String> {
+    let name = std::fs::read_to_string(last_profile_path()?).ok()?;
+    Some(name.trim().to_string()).filter(|name| !name.is_empty())
+}
+
+pub fn set_last_name(name: &str) -> Result<(), String> {
+    let path = last_profile_path().ok_or("No user config directory")?;
+    std::fs::write(path, name).map_err(|e| e.to_string())
+}
@@ -0,0 +1,79 @@
+//! SCPI commands of the bench instruments.
+
+use std::time::{Duration, Instant};
+
+/// Terminator of the commands sent to the instrument.
+pub const TERMINATOR: &str = "\n";
+pub const IDN_QUERY: &str = "*IDN?";
+pub const ERROR_QUERY: &str = "SYST:ERR?";
+
+/// Time given to the instrument to answer a query.
+pub const TIMEOUT: Duration = Duration::from_secs(2);
+/// Period of the error queue polling.
+pub const POLL_PERIOD: Duration = Duration::from_secs(1);
+
+/// A query sent to the instrument and waiting for its response.
+#[derive(Debug, Clone)]
+pub struct Query {
+    pub command: String,
+    pub sent: Instant,
+    /// Sent by the error poller, only shown when the instrument reports an error.
+    pub polled: bool,
+    /// A late response has been taken in place of its own, its timeout leaves no other one.
+    pub after_late: bool,
+}
+
+/// Whether the instrument answers `command`.
+pub fn is_query(command: &str) -> bool {
+    command.contains('?')
+}
+
+/// Values of a response made of comma separated numbers, `None` when one of them is not a
+/// number.
+pub fn parse_numbers(response: &str) -> Option<Vec<f64>> {
+    response
+        .split(',')
+        .map(|value| value.trim().parse::<f64>().ok())
+        .collect()
+}
+
+/// Code and message of a `SYST:ERR?` response such as `-113,"Undefined header"`.
+pub fn parse_error(response: &str) -> Option<(i32, String)> {
+    let (code, message) = response.split_once(',')?;
+    let code = code.trim().parse().ok()?;
+    Some((code, message.trim().trim_matches('"').to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries() {
+        assert!(is_query(IDN_QUERY));
+        assert!(is_query("MEAS:VOLT:DC?"));
+        assert!(!is_query("*RST"));
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse_numbers("+1.5E+00"), Some(vec![1.5]));
+        assert_eq!(parse_numbers("1, -2.5,3e3"), Some(vec![1.0, -2.5, 3000.0]));
+        assert_eq!(parse_numbers("1,OFF"), None);
+        assert_eq!(parse_numbers(""), None);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse_error("-113,\"Undefined header\""),
+            Some((-113, "Undefined header".to_string()))
+        );
+        assert_eq!(
+            parse_error("+0,\"No error\""),
+            Some((0, "No error".to_string()))
+        );
+        assert_eq!(parse_error("No error"), None);
+        assert_eq!(parse_error("X,\"Bad\""), None);
+    }
+}
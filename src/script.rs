@@ -0,0 +1,318 @@
+//! Rhai scripts automating a session, run on their own thread.
+//!
+//! The scripts give their `GuiMessage` to the GUI, which opens and closes the port as with its
+//! buttons, and read the bytes the GUI receives with `IO_DATA`:
+//!
+//! ```rhai
+//! open();
+//! write("AT\r");
+//! expect("OK|ERROR", 1000);
+//! log(read_until("\n", 500));
+//! sleep(100);
+//! ```
+
+use crate::data::OpenMessage;
+use crate::serial::ByteDirection;
+use crate::GuiMessage;
+use bytes::Bytes;
+use druid::{ExtEventSink, Selector, SingleUse, Target};
+use regex::Regex;
+use rhai::{Engine, EvalAltResult};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryIter};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const SCRIPT_MESSAGE: Selector<SingleUse<GuiMessage>> = Selector::new("event.script-message");
+
+/// Longest wait before checking if the job is stopped.
+const STOP_CHECK: Duration = Duration::from_millis(50);
+/// Longest wait for the serial thread to write the data of the job.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What happens on the port, given by the GUI to the job.
+enum Received {
+    Data(Bytes),
+    /// Data has been written on the port.
+    Written,
+    Error(String),
+}
+
+pub enum ScriptEvent {
+    Log(String),
+    /// End of the job with the message to show in the status bar.
+    Finished(Result<String, String>),
+}
+
+/// A job, script or test sequence, running on its own thread.
+pub struct Script {
+    stop: Arc<AtomicBool>,
+    received: Sender<Received>,
+    events: Receiver<ScriptEvent>,
+}
+
+impl Script {
+    /// Run `job` on a new thread with a session on the port.
+    pub fn spawn<F>(sink: ExtEventSink, job: F) -> Self
+    where
+        F: FnOnce(Session) -> Result<String, String> + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (received, receiver) = mpsc::channel();
+        let (events_sender, events) = mpsc::channel();
+
+        let session = Session {
+            buffer: String::new(),
+            written: 0,
+            error: None,
+            received: receiver,
+            stop: stop.clone(),
+            sink,
+            events: events_sender.clone(),
+        };
+        thread::spawn(move || {
+            let result = job(session);
+            let _ = events_sender.send(ScriptEvent::Finished(result));
+        });
+
+        Script {
+            stop,
+            received,
+            events,
+        }
+    }
+
+    /// Run the Rhai script `source`, `config` is the port opened by `open()`.
+    pub fn run(source: String, config: OpenMessage, sink: ExtEventSink) -> Self {
+        Script::spawn(sink, move |session| {
+            match make_engine(session, config).run(&source) {
+                Ok(()) => Ok("Script finished".to_string()),
+                Err(e) => Err(e.to_string()),
+            }
+        })
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Give the bytes received on the port to the job, the written ones confirm its writes.
+    pub fn receive(&self, direction: ByteDirection, data: &Bytes) {
+        let received = match direction {
+            ByteDirection::In => Received::Data(data.clone()),
+            ByteDirection::Out => Received::Written,
+        };
+        let _ = self.received.send(received);
+    }
+
+    /// Give the errors of the serial thread to the job, they fail its pending write.
+    pub fn error(&self, error: &str) {
+        let _ = self.received.send(Received::Error(error.to_string()));
+    }
+
+    pub fn events(&self) -> TryIter<'_, ScriptEvent> {
+        self.events.try_iter()
+    }
+}
+
+/// Access of a job to the port, keeping the received text not yet read.
+pub struct Session {
+    buffer: String,
+    /// Writes confirmed by the serial thread and not yet waited for.
+    written: usize,
+    /// Last error of the serial thread.
+    error: Option<String>,
+    received: Receiver<Received>,
+    stop: Arc<AtomicBool>,
+    sink: ExtEventSink,
+    events: Sender<ScriptEvent>,
+}
+
+impl Session {
+    pub fn send(&self, msg: GuiMessage) -> Result<(), String> {
+        self.sink
+            .submit_command(SCRIPT_MESSAGE, SingleUse::new(msg), Target::Global)
+            .map_err(|_| "The GUI has stopped".to_string())
+    }
+
+    /// Write `data` on the port and wait until it is written, failing on a port error.
+    pub fn write(&mut self, data: Bytes) -> Result<(), String> {
+        self.written = 0;
+        self.error = None;
+        self.send(GuiMessage::Write(data))?;
+
+        let deadline = Instant::now() + WRITE_TIMEOUT;
+        loop {
+            if let Some(error) = self.error.take() {
+                return Err(error);
+            }
+            if self.written > 0 {
+                self.written -= 1;
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err("Timeout waiting for the write".to_string());
+            }
+            self.wait(deadline - now)?;
+        }
+    }
+
+    /// Show `message` in the output.
+    pub fn log(&self, message: &str) {
+        let _ = self.events.send(ScriptEvent::Log(message.to_string()));
+    }
+
+    /// Received text up to the end of `pattern`.
+    pub fn read_until(&mut self, pattern: &str, timeout: Duration) -> Result<String, String> {
+        self.read(timeout, pattern, |buffer| {
+            buffer.find(pattern).map(|start| start + pattern.len())
+        })
+    }
+
+    /// First received text matching `regex`, what comes before it is dropped.
+    pub fn expect(&mut self, regex: &Regex, timeout: Duration) -> Result<String, String> {
+        let text = self.read(timeout, regex.as_str(), |buffer| {
+            regex.find(buffer).map(|found| found.end())
+        })?;
+        let start = regex.find(&text).map_or(0, |found| found.start());
+        Ok(text[start..].to_string())
+    }
+
+    pub fn sleep(&mut self, duration: Duration) -> Result<(), String> {
+        let deadline = Instant::now() + duration;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            self.wait(deadline - now)?;
+        }
+    }
+
+    /// Wait until `find` gives the end of what is read in the buffer, the read text is
+    /// removed from the buffer and returned.
+    fn read<F>(&mut self, timeout: Duration, what: &str, find: F) -> Result<String, String>
+    where
+        F: Fn(&str) -> Option<usize>,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(end) = find(&self.buffer) {
+                let rest = self.buffer.split_off(end);
+                return Ok(std::mem::replace(&mut self.buffer, rest));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(format!("Timeout waiting for {}", what));
+            }
+            self.wait(deadline - now)?;
+        }
+    }
+
+    /// Wait at most `duration` for received bytes.
+    fn wait(&mut self, duration: Duration) -> Result<(), String> {
+        if self.stop.load(Ordering::Relaxed) {
+            return Err("Stopped".to_string());
+        }
+        match self.received.recv_timeout(duration.min(STOP_CHECK)) {
+            Ok(Received::Data(data)) => self.buffer.push_str(&String::from_utf8_lossy(&data)),
+            Ok(Received::Written) => self.written += 1,
+            Ok(Received::Error(error)) => self.error = Some(error),
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return Err("Stopped".to_string()),
+        }
+        Ok(())
+    }
+}
+
+fn millis(duration: i64) -> Duration {
+    Duration::from_millis(duration.max(0) as u64)
+}
+
+fn make_engine(session: Session, config: OpenMessage) -> Engine {
+    let mut engine = Engine::new();
+    let session = Rc::new(RefCell::new(session));
+
+    let stop = session.borrow().stop.clone();
+    engine.on_progress(move |_| {
+        if stop.load(Ordering::Relaxed) {
+            Some("Script stopped".into())
+        } else {
+            None
+        }
+    });
+
+    let open = session.clone();
+    let open_config = config.clone();
+    engine.register_fn("open", move || -> Result<(), Box<EvalAltResult>> {
+        Ok(open.borrow().send(GuiMessage::Open(open_config.clone()))?)
+    });
+    let open = session.clone();
+    engine.register_fn(
+        "open",
+        move |port: &str, baud_rate: i64| -> Result<(), Box<EvalAltResult>> {
+            let baud_rate = u32::try_from(baud_rate).map_err(|_| "Incorrect baud rate")?;
+            Ok(open.borrow().send(GuiMessage::Open(OpenMessage {
+                port_name: port.to_string(),
+                baud_rate,
+                ..config.clone()
+            }))?)
+        },
+    );
+    let close = session.clone();
+    engine.register_fn("close", move || -> Result<(), Box<EvalAltResult>> {
+        Ok(close.borrow().send(GuiMessage::Close)?)
+    });
+    let write = session.clone();
+    engine.register_fn(
+        "write",
+        move |text: &str| -> Result<(), Box<EvalAltResult>> {
+            let bytes = Bytes::copy_from_slice(text.as_bytes());
+            Ok(write.borrow_mut().write(bytes)?)
+        },
+    );
+
+    let line_break = session.clone();
+    engine.register_fn(
+        "send_break",
+        move |duration: i64| -> Result<(), Box<EvalAltResult>> {
+            Ok(line_break
+                .borrow()
+                .send(GuiMessage::Break(millis(duration)))?)
+        },
+    );
+
+    let read = session.clone();
+    engine.register_fn(
+        "read_until",
+        move |pattern: &str, timeout: i64| -> Result<String, Box<EvalAltResult>> {
+            Ok(read.borrow_mut().read_until(pattern, millis(timeout))?)
+        },
+    );
+    let read = session.clone();
+    engine.register_fn(
+        "expect",
+        move |regex: &str, timeout: i64| -> Result<String, Box<EvalAltResult>> {
+            let regex = Regex::new(regex).map_err(|e| e.to_string())?;
+            Ok(read.borrow_mut().expect(&regex, millis(timeout))?)
+        },
+    );
+    let wait = session.clone();
+    engine.register_fn(
+        "sleep",
+        move |duration: i64| -> Result<(), Box<EvalAltResult>> {
+            Ok(wait.borrow_mut().sleep(millis(duration))?)
+        },
+    );
+
+    engine.register_fn("log", move |message: &str| session.borrow().log(message));
+
+    engine
+}
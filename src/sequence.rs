@@ -0,0 +1,322 @@
+//! Test sequences of send, expect, timeout and assert steps loaded from a TOML or YAML file,
+//! their result is written in a JUnit XML or JSON report next to the file.
+//!
+//! ```toml
+//! name = "Modem signal"
+//! report = "junit"
+//!
+//! [[steps]]
+//! send = "AT+CSQ\r"
+//! [[steps]]
+//! timeout = 2000
+//! [[steps]]
+//! expect = '\+CSQ: \d+,\d+'
+//! [[steps]]
+//! assert = '\+CSQ: ([1-9]|[12]\d|3[01]),'
+//! ```
+
+use crate::script::Session;
+use crate::session_log::utc;
+use bytes::Bytes;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Timeout of the expect steps until a timeout step changes it.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "StepFile")]
+pub enum Step {
+    /// Text written on the port.
+    Send(String),
+    /// Regex the received text must match before the timeout.
+    Expect(String),
+    /// Timeout in milliseconds of the following expect steps.
+    Timeout(u64),
+    /// Regex the text matched by the last expect step must match.
+    Assert(String),
+}
+
+/// A step in a file, a table with one of the keys set.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StepFile {
+    send: Option<String>,
+    expect: Option<String>,
+    timeout: Option<u64>,
+    assert: Option<String>,
+}
+
+impl TryFrom<StepFile> for Step {
+    type Error = String;
+
+    fn try_from(step: StepFile) -> Result<Self, Self::Error> {
+        match step {
+            StepFile {
+                send: Some(text),
+                expect: None,
+                timeout: None,
+                assert: None,
+            } => Ok(Step::Send(text)),
+            StepFile {
+                send: None,
+                expect: Some(regex),
+                timeout: None,
+                assert: None,
+            } => Ok(Step::Expect(regex)),
+            StepFile {
+                send: None,
+                expect: None,
+                timeout: Some(timeout),
+                assert: None,
+            } => Ok(Step::Timeout(timeout)),
+            StepFile {
+                send: None,
+                expect: None,
+                timeout: None,
+                assert: Some(regex),
+            } => Ok(Step::Assert(regex)),
+            _ => Err("A step has one of send, expect, timeout or assert".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Send(text) => write!(f, "send {:?}", text),
+            Step::Expect(regex) => write!(f, "expect {:?}", regex),
+            Step::Timeout(timeout) => write!(f, "timeout {} ms", timeout),
+            Step::Assert(regex) => write!(f, "assert {:?}", regex),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Junit,
+    Json,
+}
+
+impl Default for ReportFormat {
+    fn default() -> Self {
+        ReportFormat::Junit
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sequence {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub report: ReportFormat,
+    pub steps: Vec<Step>,
+}
+
+impl Sequence {
+    /// Read a sequence file, YAML when its extension is `yaml` or `yml` and TOML otherwise.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let yaml = matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some("yaml") | Some("yml")
+        );
+        let mut sequence: Sequence = if yaml {
+            serde_yaml::from_str(&text).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(&text).map_err(|e| e.to_string())?
+        };
+
+        for step in &sequence.steps {
+            if let Step::Expect(regex) | Step::Assert(regex) = step {
+                Regex::new(regex).map_err(|e| e.to_string())?;
+            }
+        }
+        if sequence.name.is_empty() {
+            if let Some(stem) = path.file_stem() {
+                sequence.name = stem.to_string_lossy().to_string();
+            }
+        }
+        Ok(sequence)
+    }
+
+    /// Where the report of the sequence loaded from `path` is written.
+    pub fn report_path(&self, path: &Path) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = match self.report {
+            ReportFormat::Junit => "xml",
+            ReportFormat::Json => "json",
+        };
+        path.with_file_name(format!("{}-report.{}", stem, extension))
+    }
+
+    /// Run the steps until one fails, the following ones are skipped.
+    pub fn run(&self, session: &mut Session) -> Report {
+        let started = Instant::now();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut timeout = DEFAULT_TIMEOUT;
+        let mut matched: Option<String> = None;
+        let mut failed = false;
+        let mut steps = Vec::new();
+
+        for step in &self.steps {
+            if failed {
+                steps.push(StepResult {
+                    step: step.to_string(),
+                    status: Status::Skipped,
+                    message: None,
+                    duration: 0.,
+                });
+                continue;
+            }
+
+            let step_started = Instant::now();
+            let result = match step {
+                Step::Send(text) => session.write(Bytes::copy_from_slice(text.as_bytes())),
+                Step::Expect(regex) => {
+                    let regex = Regex::new(regex).map_err(|e| e.to_string());
+                    regex
+                        .and_then(|regex| session.expect(&regex, timeout))
+                        .map(|text| matched = Some(text))
+                }
+                Step::Timeout(millis) => {
+                    timeout = Duration::from_millis(*millis);
+                    Ok(())
+                }
+                Step::Assert(regex) => match (Regex::new(regex), &matched) {
+                    (Err(e), _) => Err(e.to_string()),
+                    (Ok(_), None) => Err("Nothing expected before the assert".to_string()),
+                    (Ok(regex), Some(text)) if regex.is_match(text) => Ok(()),
+                    (Ok(_), Some(text)) => Err(format!("{:?} does not match", text)),
+                },
+            };
+
+            let (status, message) = match result {
+                Ok(()) => (Status::Passed, None),
+                Err(message) => (Status::Failed, Some(message)),
+            };
+            failed = status == Status::Failed;
+            session.log(&match &message {
+                None => format!("PASS {}", step),
+                Some(message) => format!("FAIL {}: {}", step, message),
+            });
+            steps.push(StepResult {
+                step: step.to_string(),
+                status,
+                message,
+                duration: step_started.elapsed().as_secs_f64(),
+            });
+        }
+
+        let count = |status| steps.iter().filter(|step| step.status == status).count();
+        Report {
+            name: self.name.clone(),
+            timestamp,
+            duration: started.elapsed().as_secs_f64(),
+            passed: count(Status::Passed),
+            failed: count(Status::Failed),
+            skipped: count(Status::Skipped),
+            steps,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+    pub step: String,
+    pub status: Status,
+    pub message: Option<String>,
+    /// Duration in seconds.
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub name: String,
+    /// Start of the sequence in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Duration in seconds.
+    pub duration: f64,
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub steps: Vec<StepResult>,
+}
+
+impl Report {
+    pub fn write(&self, format: ReportFormat, path: &Path) -> Result<(), String> {
+        let text = match format {
+            ReportFormat::Junit => self.to_junit(),
+            ReportFormat::Json => serde_json::to_string_pretty(self).map_err(|e| e.to_string())?,
+        };
+        std::fs::write(path, text).map_err(|e| e.to_string())
+    }
+
+    /// The sequence as a JUnit test suite with a test case for each step.
+    pub fn to_junit(&self) -> String {
+        let (year, month, day, hours, minutes, seconds, _) =
+            utc(UNIX_EPOCH + Duration::from_secs(self.timestamp));
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\" \
+             timestamp=\"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}\">\n",
+            escape_xml(&self.name),
+            self.steps.len(),
+            self.failed,
+            self.skipped,
+            self.duration,
+            year,
+            month,
+            day,
+            hours,
+            minutes,
+            seconds
+        ));
+
+        for (index, step) in self.steps.iter().enumerate() {
+            xml.push_str(&format!(
+                "  <testcase name=\"{}: {}\" classname=\"{}\" time=\"{:.3}\"",
+                index + 1,
+                escape_xml(&step.step),
+                escape_xml(&self.name),
+                step.duration
+            ));
+            match (step.status, &step.message) {
+                (Status::Failed, message) => xml.push_str(&format!(
+                    ">\n    <failure message=\"{}\"/>\n  </testcase>\n",
+                    escape_xml(message.as_deref().unwrap_or_default())
+                )),
+                (Status::Skipped, _) => xml.push_str(">\n    <skipped/>\n  </testcase>\n"),
+                (Status::Passed, _) => xml.push_str("/>\n"),
+            }
+        }
+
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
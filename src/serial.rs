@@ -1,44 +1,226 @@
-use crate::{data::OpenMessage, GuiMessage};
+use crate::data::{Framing, LineSettings, OpenMessage, Protocol};
+use crate::layout::Layout;
+use crate::session_log::SessionLog;
+use crate::stats::Stats;
+use crate::{mavlink, modbus, slcan, zmodem, GuiMessage};
 use bytes::{BufMut, Bytes, BytesMut};
-use druid::{ExtEventError, ExtEventSink, Selector, Target};
-use futures::{channel::mpsc::UnboundedReceiver, stream::StreamExt};
+use druid::{Data, ExtEventError, ExtEventSink, Selector, Target};
+use futures::{
+    channel::mpsc::UnboundedReceiver,
+    future,
+    stream::{SplitSink, SplitStream, StreamExt},
+};
 use futures_util::sink::SinkExt;
+use std::cell::RefCell;
 use std::io::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+use tokio::time::{interval, sleep_until, Instant, Interval, MissedTickBehavior};
 use tokio_serial::{
-    DataBits, FlowControl, Parity, SerialPortBuilder, SerialPortBuilderExt, SerialStream, StopBits,
+    DataBits, FlowControl, Parity, SerialPort, SerialPortBuilder, SerialPortBuilderExt,
+    SerialStream, StopBits,
 };
-use tokio_util::codec::{Decoder, Encoder};
+use tokio_util::codec::{Decoder, Encoder, Framed};
 
 pub const IO_DATA: Selector<(ByteDirection, Bytes)> = Selector::new("event.io-data");
 pub const IO_ERROR: Selector<&str> = Selector::new("event.io-error");
+pub const IO_OPEN_ERROR: Selector = Selector::new("event.io-open-error");
+pub const IO_TRANSFER: Selector<String> = Selector::new("event.io-transfer");
+pub const IO_MODBUS_TABLE: Selector<modbus::Table> = Selector::new("event.io-modbus-table");
+pub const IO_STATS: Selector<Stats> = Selector::new("event.io-stats");
+pub const IO_LOG_STOPPED: Selector = Selector::new("event.io-log-stopped");
+pub const IO_MODEM_STATUS: Selector<ModemStatus> = Selector::new("event.io-modem-status");
+pub const IO_CONTROL_LINES: Selector<ControlLines> = Selector::new("event.io-control-lines");
+pub const IO_BREAK: Selector<Duration> = Selector::new("event.io-break");
+pub const IO_SETTINGS: Selector<String> = Selector::new("event.io-settings");
+
+/// Period of the traffic statistics.
+const STATS_PERIOD: Duration = Duration::from_secs(1);
+/// Period of the polling of the modem status lines.
+const MODEM_STATUS_PERIOD: Duration = Duration::from_millis(100);
+
+/// Where the serial thread sends what happens on the port, the GUI or the console.
+pub trait IoSink {
+    fn data(&self, direction: ByteDirection, data: Bytes) -> Result<(), ExtEventError>;
+    fn error(&self, error: &'static str) -> Result<(), ExtEventError>;
+    /// The port given by `Open` cannot be opened.
+    fn open_error(&self) -> Result<(), ExtEventError>;
+    /// The session log cannot be written and has been stopped.
+    fn log_stopped(&self) -> Result<(), ExtEventError>;
+    fn transfer(&self, status: String) -> Result<(), ExtEventError>;
+    fn modbus_table(&self, table: modbus::Table) -> Result<(), ExtEventError>;
+    fn stats(&self, stats: Stats) -> Result<(), ExtEventError>;
+    fn modem_status(&self, status: ModemStatus) -> Result<(), ExtEventError>;
+    /// Levels of the output lines, sent when they are applied to the port or kept for its
+    /// opening.
+    fn control_lines(&self, lines: ControlLines) -> Result<(), ExtEventError>;
+    /// A break held for the duration has been sent.
+    fn line_break(&self, duration: Duration) -> Result<(), ExtEventError>;
+    /// Summary of the settings of the port, sent when it is opened or reconfigured.
+    fn settings(&self, settings: String) -> Result<(), ExtEventError>;
+}
+
+impl IoSink for ExtEventSink {
+    fn data(&self, direction: ByteDirection, data: Bytes) -> Result<(), ExtEventError> {
+        self.submit_command(IO_DATA, (direction, data), Target::Global)
+    }
+
+    fn error(&self, error: &'static str) -> Result<(), ExtEventError> {
+        self.submit_command(IO_ERROR, error, Target::Global)
+    }
+
+    fn open_error(&self) -> Result<(), ExtEventError> {
+        self.submit_command(IO_OPEN_ERROR, (), Target::Global)?;
+        self.error("Cannot open the port")
+    }
+
+    fn log_stopped(&self) -> Result<(), ExtEventError> {
+        self.submit_command(IO_LOG_STOPPED, (), Target::Global)
+    }
+
+    fn transfer(&self, status: String) -> Result<(), ExtEventError> {
+        self.submit_command(IO_TRANSFER, status, Target::Global)
+    }
+
+    fn modbus_table(&self, table: modbus::Table) -> Result<(), ExtEventError> {
+        self.submit_command(IO_MODBUS_TABLE, table, Target::Global)
+    }
+
+    fn stats(&self, stats: Stats) -> Result<(), ExtEventError> {
+        self.submit_command(IO_STATS, stats, Target::Global)
+    }
+
+    fn modem_status(&self, status: ModemStatus) -> Result<(), ExtEventError> {
+        self.submit_command(IO_MODEM_STATUS, status, Target::Global)
+    }
+
+    fn control_lines(&self, lines: ControlLines) -> Result<(), ExtEventError> {
+        self.submit_command(IO_CONTROL_LINES, lines, Target::Global)
+    }
+
+    fn line_break(&self, duration: Duration) -> Result<(), ExtEventError> {
+        self.submit_command(IO_BREAK, duration, Target::Global)
+    }
+
+    fn settings(&self, settings: String) -> Result<(), ExtEventError> {
+        self.submit_command(IO_SETTINGS, settings, Target::Global)
+    }
+}
+
+type PortSink = SplitSink<Framed<SerialStream, FrameCodec>, Bytes>;
+type PortStream = SplitStream<Framed<SerialStream, FrameCodec>>;
+
+/// Input lines of the modem control, sent to the GUI when one of them changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Data)]
+pub struct ModemStatus {
+    pub cts: bool,
+    pub dsr: bool,
+    pub ri: bool,
+    pub cd: bool,
+}
+
+/// Levels of the output lines of the modem control.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControlLines {
+    pub rts: bool,
+    pub dtr: bool,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ByteDirection {
     Out,
     In,
 }
-pub struct RawCodec;
 
-impl RawCodec {
-    pub fn new() -> Self {
-        RawCodec {}
+/// Frames longer than this are split even if their end has not been found.
+const MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// How `FrameCodec` splits the received bytes.
+enum Split {
+    Bytes,
+    Delimiter(u8),
+    Layout(Arc<Layout>),
+    Mavlink,
+    Slcan,
+}
+
+/// Split the received bytes into frames, or give them as they come when there is no framing.
+pub struct FrameCodec {
+    split: Split,
+    /// A file transfer is running, its bytes are given as they come.
+    transfer: bool,
+}
+
+impl FrameCodec {
+    pub fn new(config: &OpenMessage) -> Self {
+        let split = match (config.protocol, config.framing) {
+            (Protocol::Raw, Framing::Delimiter) => Split::Delimiter(config.delimiter),
+            (Protocol::Raw, Framing::Layout) => match &config.layout {
+                Some(layout) => Split::Layout(layout.clone()),
+                None => Split::Bytes,
+            },
+            (Protocol::Nmea, _) | (Protocol::At, _) | (Protocol::Scpi, _) => {
+                Split::Delimiter(b'\n')
+            }
+            (Protocol::Mavlink, _) => Split::Mavlink,
+            (Protocol::Slcan, _) => Split::Slcan,
+            _ => Split::Bytes,
+        };
+        FrameCodec {
+            split,
+            transfer: false,
+        }
+    }
+
+    /// Length of the frame at the beginning of `buf`, `None` until it is complete.
+    fn frame_len(&self, buf: &[u8]) -> Option<usize> {
+        let len = match &self.split {
+            Split::Delimiter(delimiter) => buf
+                .iter()
+                .position(|byte| byte == delimiter)
+                .map(|end| end + 1),
+            Split::Layout(layout) => layout.split(buf),
+            Split::Mavlink => return mavlink::split(buf),
+            // Each answer of the adapter ends with CR, or BELL on error
+            Split::Slcan => buf
+                .iter()
+                .position(|&byte| byte == slcan::CR || byte == slcan::BELL)
+                .map(|end| end + 1),
+            Split::Bytes => return Some(buf.len()),
+        };
+        len.or(Some(buf.len()).filter(|&len| len >= MAX_FRAME_SIZE))
     }
 }
 
-impl Decoder for RawCodec {
+impl Decoder for FrameCodec {
     type Item = BytesMut;
     type Error = std::io::Error;
 
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<BytesMut>, Error> {
-        if buf.len() > 0 {
-            Ok(Some(buf.clone()))
-        } else {
-            Ok(None)
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        if self.transfer {
+            return Ok(Some(buf.split()));
         }
+
+        // The header starting a ZMODEM transfer ends no frame, it is given at once so the
+        // transfer starts without waiting for the end of a frame
+        let auto_start = buf
+            .windows(zmodem::AUTO_START.len())
+            .position(|window| window == zmodem::AUTO_START)
+            .map(|start| start + zmodem::AUTO_START.len());
+        let len = match (self.frame_len(buf), auto_start) {
+            (Some(len), Some(end)) => Some(len.min(end)),
+            (len, end) => len.or(end),
+        };
+        Ok(len.map(|len| buf.split_to(len)))
     }
 }
 
-impl Encoder<Bytes> for RawCodec {
+impl Encoder<Bytes> for FrameCodec {
     type Error = std::io::Error;
 
     fn encode(&mut self, data: Bytes, buf: &mut BytesMut) -> Result<(), Error> {
@@ -49,6 +231,11 @@ impl Encoder<Bytes> for RawCodec {
     }
 }
 
+/// Bytes per second the line carries at the baud rate of `config`.
+fn line_capacity(config: &OpenMessage) -> f64 {
+    config.baud_rate as f64 / config.char_bits() as f64
+}
+
 fn port_from_config(config: &OpenMessage) -> SerialPortBuilder {
     tokio_serial::new(config.port_name.as_str(), config.baud_rate)
         .baud_rate(config.baud_rate)
@@ -58,39 +245,213 @@ fn port_from_config(config: &OpenMessage) -> SerialPortBuilder {
         .stop_bits(StopBits::from(config.stop_bits))
 }
 
-pub async fn serial_loop(
-    event_sink: ExtEventSink,
+/// Give the framed port behind the split halves to `f`, the halves are split again after.
+fn with_framed<R>(
+    sender_data: PortSink,
+    receiver_data: PortStream,
+    f: impl FnOnce(&mut Framed<SerialStream, FrameCodec>) -> R,
+) -> (PortSink, PortStream, R) {
+    let mut framed = receiver_data
+        .reunite(sender_data)
+        .expect("halves of the same port");
+    let result = f(&mut framed);
+    let (sender_data, receiver_data) = framed.split();
+    (sender_data, receiver_data, result)
+}
+
+/// Give the port behind the split halves to `f`, the halves are split again after.
+fn with_port<R>(
+    sender_data: PortSink,
+    receiver_data: PortStream,
+    f: impl FnOnce(&mut SerialStream) -> R,
+) -> (PortSink, PortStream, R) {
+    with_framed(sender_data, receiver_data, |framed| f(framed.get_mut()))
+}
+
+/// Stop splitting the received bytes into frames while a file transfer is running.
+fn set_transfer(
+    sender_data: PortSink,
+    receiver_data: PortStream,
+    transfer: bool,
+) -> (PortSink, PortStream) {
+    let (sender_data, receiver_data, ()) = with_framed(sender_data, receiver_data, |framed| {
+        framed.codec_mut().transfer = transfer
+    });
+    (sender_data, receiver_data)
+}
+
+fn apply_settings(port: &mut SerialStream, settings: LineSettings) -> tokio_serial::Result<()> {
+    port.set_baud_rate(settings.baud_rate)?;
+    port.set_data_bits(DataBits::from(settings.data_bits))?;
+    port.set_flow_control(FlowControl::from(settings.flow_control))?;
+    port.set_parity(Parity::from(settings.parity))?;
+    port.set_stop_bits(StopBits::from(settings.stop_bits))
+}
+
+/// Drive the output lines of a port just opened to `lines`, whatever the system raised.
+fn init_control_lines<S: IoSink>(
+    event_sink: &S,
+    port: &mut SerialStream,
+    lines: ControlLines,
+) -> Result<(), ExtEventError> {
+    let result = port
+        .write_request_to_send(lines.rts)
+        .and_then(|_| port.write_data_terminal_ready(lines.dtr));
+    if result.is_err() {
+        event_sink.error("Cannot set the control lines on the port")
+    } else {
+        event_sink.control_lines(lines)
+    }
+}
+
+fn read_modem_status(port: &mut SerialStream) -> Option<ModemStatus> {
+    Some(ModemStatus {
+        cts: port.read_clear_to_send().ok()?,
+        dsr: port.read_data_set_ready().ok()?,
+        ri: port.read_ring_indicator().ok()?,
+        cd: port.read_carrier_detect().ok()?,
+    })
+}
+
+/// Periodic timer, the ticks missed while the loop is busy are not caught up in a burst.
+fn timer(period: Duration) -> Interval {
+    let mut timer = interval(period);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    timer
+}
+
+/// The received bytes are split into frames, by the codec or on the silences.
+fn framed(config: &OpenMessage) -> bool {
+    frame_silence(config).is_some() || !matches!(FrameCodec::new(config).split, Split::Bytes)
+}
+
+/// Silence delimiting the received frames for the protocols framed on timing.
+fn frame_silence(config: &OpenMessage) -> Option<Duration> {
+    match config.protocol {
+        Protocol::Modbus | Protocol::ModbusSlave => {
+            Some(modbus::frame_silence(config.baud_rate, config.char_bits()))
+        }
+        Protocol::Raw if config.framing == Framing::Silence => {
+            Some(modbus::frame_silence(config.baud_rate, config.char_bits()))
+        }
+        Protocol::Text
+        | Protocol::Raw
+        | Protocol::Nmea
+        | Protocol::Mavlink
+        | Protocol::Slcan
+        | Protocol::At
+        | Protocol::Scpi => None,
+    }
+}
+
+/// Runtime of the serial thread. The port needs its IO driver, the statistics and modem status
+/// intervals, the break and the frame silences its time driver.
+pub fn runtime() -> std::io::Result<Runtime> {
+    Builder::new_current_thread().enable_all().build()
+}
+
+pub async fn serial_loop<S: IoSink>(
+    event_sink: S,
     mut receiver_gui: UnboundedReceiver<GuiMessage>,
 ) -> Result<(), ExtEventError> {
-    let send_err_gui = |data| event_sink.submit_command(IO_ERROR, data, Target::Global);
+    let send_err_gui = |data| event_sink.error(data);
+    let mut slave = modbus::Slave::new(1, modbus::Table::default());
+    let session_log = RefCell::new(None);
+    let mut lines = ControlLines {
+        rts: true,
+        dtr: true,
+    };
 
     while let Some(msg_gui) = receiver_gui.next().await {
         match msg_gui {
             GuiMessage::Open(config) => {
                 let build_port = port_from_config(&config);
-                if let Ok(port) = build_port.open_native_async() {
-                    open_loop(&event_sink, &mut receiver_gui, port, &config).await?;
+                if let Ok(mut port) = build_port.open_native_async() {
+                    event_sink.settings(config.summary())?;
+                    init_control_lines(&event_sink, &mut port, lines)?;
+                    let port_loop = open_loop(
+                        &event_sink,
+                        &mut receiver_gui,
+                        port,
+                        &config,
+                        &mut slave,
+                        &session_log,
+                        &mut lines,
+                    );
+                    port_loop.await?;
                 } else {
-                    send_err_gui("Cannot open the port")?;
+                    event_sink.open_error()?;
                 }
             }
             GuiMessage::Write(_) => send_err_gui("Cannot write data port not open")?,
+            GuiMessage::SendFile(_) => send_err_gui("Cannot send file port not open")?,
+            GuiMessage::Break(_) => send_err_gui("Cannot send break port not open")?,
+            GuiMessage::Reconfigure(_) => send_err_gui("Cannot reconfigure port not open")?,
+            // Kept for the next opening
+            GuiMessage::Rts(rts) => {
+                lines.rts = rts;
+                event_sink.control_lines(lines)?;
+            }
+            GuiMessage::Dtr(dtr) => {
+                lines.dtr = dtr;
+                event_sink.control_lines(lines)?;
+            }
+            GuiMessage::ModbusSlave(new_slave) => slave = new_slave,
+            GuiMessage::SessionLog(config) => {
+                session_log.replace(config.map(SessionLog::new));
+            }
             GuiMessage::Close => (),
         }
     }
     Ok(())
 }
 
-async fn open_loop(
-    event_sink: &ExtEventSink,
+async fn open_loop<S: IoSink>(
+    event_sink: &S,
     receiver_gui: &mut UnboundedReceiver<GuiMessage>,
     port: SerialStream,
     config: &OpenMessage,
+    slave: &mut modbus::Slave,
+    session_log: &RefCell<Option<SessionLog>>,
+    lines: &mut ControlLines,
 ) -> Result<(), ExtEventError> {
-    let send_err_gui = |data| event_sink.submit_command(IO_ERROR, data, Target::Global);
-    let send_data_gui = |dir, data| event_sink.submit_command(IO_DATA, (dir, data), Target::Global);
-    let (mut sender_data, mut receiver_data) = RawCodec::new().framed(port).split();
+    let send_err_gui = |data| event_sink.error(data);
+    // Settings the port is reopened with after an error
+    let mut active = config.clone();
+    let mut port_name = config.port_name.clone();
+    let stats = RefCell::new(Stats::new(line_capacity(config), framed(config)));
+    let mut opened = Instant::now();
+    let mut last_tick = Instant::now();
+    let mut stats_timer = timer(STATS_PERIOD);
+    let mut modem_timer = timer(MODEM_STATUS_PERIOD);
+    let mut modem_status = None;
+    // Count and log the bytes, the ones of a file transfer are not displayed nor frames
+    let record_data = |dir: ByteDirection, data: &[u8], port_name: &str, frame: bool| {
+        stats.borrow_mut().record(dir, data.len(), frame);
+        let mut log = session_log.borrow_mut();
+        if let Some(writer) = log.as_mut() {
+            if writer.write(port_name, dir, data).is_err() {
+                // Stop logging rather than report the error on every chunk
+                *log = None;
+                event_sink.log_stopped()?;
+            }
+        }
+        Ok(())
+    };
+    let send_data_gui = |dir: ByteDirection, data: Bytes, port_name: &str| {
+        record_data(dir, &data, port_name, true)?;
+        event_sink.data(dir, data)
+    };
+    let (mut sender_data, mut receiver_data) = FrameCodec::new(config).framed(port).split();
     let mut error_reading = false;
+    let mut protocol = config.protocol;
+    let mut silence = frame_silence(config);
+    let mut frame = BytesMut::new();
+    let mut frame_end = Instant::now();
+    // Duration of the break being sent, cleared at `break_end`
+    let mut break_duration = None;
+    let mut break_end = Instant::now();
+    let mut auto_start = zmodem::AutoStart::default();
 
     loop {
         tokio::select! {
@@ -99,40 +460,418 @@ async fn open_loop(
                     Some(GuiMessage::Open(config)) => {
                         let build_port = port_from_config(&config);
 
-                        if let Ok(port) = build_port.open_native_async() {
-                            (sender_data, receiver_data) = RawCodec::new().framed(port).split();
+                        if let Ok(mut port) = build_port.open_native_async() {
+                            init_control_lines(event_sink, &mut port, *lines)?;
+                            let codec = FrameCodec::new(&config);
+                            (sender_data, receiver_data) = codec.framed(port).split();
                             error_reading = false;
+                            protocol = config.protocol;
+                            port_name = config.port_name.clone();
+                            stats.replace(Stats::new(line_capacity(&config), framed(&config)));
+                            opened = Instant::now();
+                            last_tick = Instant::now();
+                            silence = frame_silence(&config);
+                            frame.clear();
+                            auto_start.clear();
+                            break_duration = None;
+                            modem_status = None;
+                            event_sink.settings(config.summary())?;
+                            active = config;
                         } else {
-                            send_err_gui("Cannot open the port")?;
+                            event_sink.open_error()?;
                         }
                     }
                     Some(GuiMessage::Write(data)) => {
                         if let Err(_) = sender_data.send(data.clone()).await {
                             send_err_gui("Cannot write data on the port")?;
                         } else {
-                            send_data_gui(ByteDirection::Out, data)?;
+                            send_data_gui(ByteDirection::Out, data, &port_name)?;
+                        }
+                    }
+                    Some(GuiMessage::SendFile(path)) => {
+                        (sender_data, receiver_data) = set_transfer(sender_data, receiver_data, true);
+                        let driven = *lines;
+                        let transfer = transfer_file(
+                            event_sink,
+                            receiver_gui,
+                            &mut sender_data,
+                            &mut receiver_data,
+                            |dir, data: &[u8]| record_data(dir, data, &port_name, false),
+                            |msg| apply_during_transfer(event_sink, msg, slave, session_log, lines),
+                            Transfer::Send(path),
+                        );
+                        let open = transfer.await?;
+                        (sender_data, receiver_data) = set_transfer(sender_data, receiver_data, false);
+                        if !open {
+                            return Ok(());
+                        }
+                        if *lines != driven {
+                            let result;
+                            (sender_data, receiver_data, result) =
+                                with_port(sender_data, receiver_data, |port| {
+                                    init_control_lines(event_sink, port, *lines)
+                                });
+                            result?;
+                        }
+                    }
+                    Some(GuiMessage::Rts(level)) => {
+                        let result;
+                        (sender_data, receiver_data, result) =
+                            with_port(sender_data, receiver_data, |port| {
+                                port.write_request_to_send(level)
+                            });
+                        if result.is_err() {
+                            send_err_gui("Cannot set RTS on the port")?;
+                        } else {
+                            lines.rts = level;
+                            event_sink.control_lines(*lines)?;
+                        }
+                    }
+                    Some(GuiMessage::Dtr(level)) => {
+                        let result;
+                        (sender_data, receiver_data, result) =
+                            with_port(sender_data, receiver_data, |port| {
+                                port.write_data_terminal_ready(level)
+                            });
+                        if result.is_err() {
+                            send_err_gui("Cannot set DTR on the port")?;
+                        } else {
+                            lines.dtr = level;
+                            event_sink.control_lines(*lines)?;
+                        }
+                    }
+                    Some(GuiMessage::Reconfigure(settings)) => {
+                        let mut result;
+                        (sender_data, receiver_data, result) =
+                            with_port(sender_data, receiver_data, |port| {
+                                apply_settings(port, settings)
+                            });
+                        if result.is_err() {
+                            // Put back the settings changed before the failure
+                            let previous = active.line_settings();
+                            (sender_data, receiver_data, result) =
+                                with_port(sender_data, receiver_data, |port| {
+                                    apply_settings(port, previous)
+                                });
+                            send_err_gui(if result.is_err() {
+                                "Cannot reconfigure the port nor restore its settings"
+                            } else {
+                                "Cannot reconfigure the port"
+                            })?;
+                        } else {
+                            active.reconfigure(settings);
+                            stats.borrow_mut().capacity = line_capacity(&active);
+                            silence = frame_silence(&active);
+                            event_sink.settings(active.summary())?;
+                        }
+                    }
+                    Some(GuiMessage::Break(_)) if break_duration.is_some() => {
+                        send_err_gui("Break already in progress")?;
+                    }
+                    Some(GuiMessage::Break(duration)) => {
+                        let result;
+                        (sender_data, receiver_data, result) =
+                            with_port(sender_data, receiver_data, |port| port.set_break());
+                        if result.is_err() {
+                            send_err_gui("Cannot send break on the port")?;
+                        } else {
+                            break_duration = Some(duration);
+                            break_end = Instant::now() + duration;
+                        }
+                    }
+                    Some(GuiMessage::ModbusSlave(new_slave)) => *slave = new_slave,
+                    Some(GuiMessage::SessionLog(config)) => {
+                        session_log.replace(config.map(SessionLog::new));
+                    }
+                    Some(GuiMessage::Close) => {
+                        if break_duration.is_some() {
+                            let _ = with_port(sender_data, receiver_data, |port| port.clear_break());
                         }
+                        return Ok(());
                     }
-                    Some(GuiMessage::Close) => return Ok(()),
                     None => return Err(ExtEventError),
                 };
             }
             data = receiver_data.next() => {
                 if let Some(Ok(data)) = data {
-                    send_data_gui(ByteDirection::In, data.freeze())?;
+                    let data = data.freeze();
+                    if let Some(silence) = silence {
+                        frame.extend_from_slice(&data);
+                        frame_end = Instant::now() + silence;
+                    } else if let Some((start, end)) = auto_start.find(&data) {
+                        if start > 0 {
+                            send_data_gui(ByteDirection::In, data.slice(..start), &port_name)?;
+                        }
+                        record_data(ByteDirection::In, &data[start..], &port_name, false)?;
+                        (sender_data, receiver_data) = set_transfer(sender_data, receiver_data, true);
+                        let driven = *lines;
+                        let transfer = transfer_file(
+                            event_sink,
+                            receiver_gui,
+                            &mut sender_data,
+                            &mut receiver_data,
+                            |dir, data: &[u8]| record_data(dir, data, &port_name, false),
+                            |msg| apply_during_transfer(event_sink, msg, slave, session_log, lines),
+                            Transfer::Receive(data.slice(end..)),
+                        );
+                        let open = transfer.await?;
+                        (sender_data, receiver_data) = set_transfer(sender_data, receiver_data, false);
+                        if !open {
+                            return Ok(());
+                        }
+                        if *lines != driven {
+                            let result;
+                            (sender_data, receiver_data, result) =
+                                with_port(sender_data, receiver_data, |port| {
+                                    init_control_lines(event_sink, port, *lines)
+                                });
+                            result?;
+                        }
+                    } else {
+                        send_data_gui(ByteDirection::In, data, &port_name)?;
+                    }
                 } else {
                     if !error_reading {
                         send_err_gui("Error while reading data")?;
+                        stats.borrow_mut().read_errors += 1;
                         error_reading = true;
                     }
 
-                    let build_port = port_from_config(&config);
-                    if let Ok(port) = build_port.open_native_async() {
-                        (sender_data, receiver_data) = RawCodec::new().framed(port).split();
+                    let build_port = port_from_config(&active);
+                    if let Ok(mut port) = build_port.open_native_async() {
+                        init_control_lines(event_sink, &mut port, *lines)?;
+                        (sender_data, receiver_data) = FrameCodec::new(&active).framed(port).split();
+                        stats.borrow_mut().reconnects += 1;
+                        modem_status = None;
                         error_reading = false;
                     };
                 }
             }
+            _ = stats_timer.tick() => {
+                let mut stats = stats.borrow_mut();
+                stats.tick(last_tick.elapsed(), opened.elapsed());
+                last_tick = Instant::now();
+                event_sink.stats(stats.clone())?;
+            }
+            _ = modem_timer.tick() => {
+                let status;
+                (sender_data, receiver_data, status) =
+                    with_port(sender_data, receiver_data, read_modem_status);
+                // Ports without modem lines, as the virtual ones, have no status
+                if let Some(status) = status {
+                    if modem_status != Some(status) {
+                        modem_status = Some(status);
+                        event_sink.modem_status(status)?;
+                    }
+                }
+            }
+            _ = sleep_until(break_end), if break_duration.is_some() => {
+                let result;
+                (sender_data, receiver_data, result) =
+                    with_port(sender_data, receiver_data, |port| port.clear_break());
+                let duration = break_duration.take().unwrap_or_default();
+                if result.is_err() {
+                    send_err_gui("Cannot send break on the port")?;
+                } else {
+                    event_sink.line_break(duration)?;
+                }
+            }
+            _ = sleep_until(frame_end), if !frame.is_empty() => {
+                let request = frame.split().freeze();
+                send_data_gui(ByteDirection::In, request.clone(), &port_name)?;
+
+                if protocol == Protocol::ModbusSlave {
+                    let table = slave.table.clone();
+                    if let Some(response) = slave.respond(&request) {
+                        let response = Bytes::from(response);
+                        if let Err(_) = sender_data.send(response.clone()).await {
+                            send_err_gui("Cannot write data on the port")?;
+                        } else {
+                            send_data_gui(ByteDirection::Out, response, &port_name)?;
+                        }
+                    }
+                    if slave.table != table {
+                        event_sink.modbus_table(slave.table.clone())?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// ZMODEM transfer run on the open port.
+enum Transfer {
+    /// Send the file at the path.
+    Send(PathBuf),
+    /// Receive into the current directory, starting with the bytes read after the auto start
+    /// sequence.
+    Receive(Bytes),
+}
+
+/// Apply a message of the GUI received during a file transfer. The control lines are only
+/// kept in `lines`, the port is busy until the transfer ends.
+fn apply_during_transfer<S: IoSink>(
+    event_sink: &S,
+    msg: GuiMessage,
+    slave: &mut modbus::Slave,
+    session_log: &RefCell<Option<SessionLog>>,
+    lines: &mut ControlLines,
+) -> Result<(), ExtEventError> {
+    match msg {
+        GuiMessage::Rts(rts) => lines.rts = rts,
+        GuiMessage::Dtr(dtr) => lines.dtr = dtr,
+        GuiMessage::ModbusSlave(new_slave) => *slave = new_slave,
+        GuiMessage::SessionLog(config) => {
+            session_log.replace(config.map(SessionLog::new));
+        }
+        GuiMessage::Open(_)
+        | GuiMessage::Write(_)
+        | GuiMessage::SendFile(_)
+        | GuiMessage::Break(_)
+        | GuiMessage::Reconfigure(_) => event_sink.error("Transfer in progress")?,
+        // Aborts the transfer, see `transfer_file`
+        GuiMessage::Close => (),
+    }
+    Ok(())
+}
+
+/// Run the ZMODEM transfer of `file` on the open port. The display is paused until the transfer
+/// ends, the bytes exchanged are given to `record` and the messages of the GUI but Close
+/// to `apply`.
+///
+/// Return `false` if the port has been closed during the transfer.
+async fn transfer_file<S: IoSink>(
+    event_sink: &S,
+    receiver_gui: &mut UnboundedReceiver<GuiMessage>,
+    sender_data: &mut PortSink,
+    receiver_data: &mut PortStream,
+    record: impl Fn(ByteDirection, &[u8]) -> Result<(), ExtEventError>,
+    mut apply: impl FnMut(GuiMessage) -> Result<(), ExtEventError>,
+    file: Transfer,
+) -> Result<bool, ExtEventError> {
+    // Recording fails only once the GUI is gone, which the next message reports
+    let mut sender_data = (&mut *sender_data).with(|data: Bytes| {
+        let _ = record(ByteDirection::Out, &data);
+        future::ready(Ok::<_, Error>(data))
+    });
+    let mut receiver_data = (&mut *receiver_data).inspect(|data| {
+        if let Ok(data) = data {
+            let _ = record(ByteDirection::In, data);
+        }
+    });
+    let send_transfer_gui = |status: String| event_sink.transfer(status);
+    let mut progress = |name: &str, done: u64, size: u64| {
+        let _ = send_transfer_gui(format!("Transferring {}: {}/{} bytes", name, done, size));
+    };
+
+    let result = {
+        let transfer = async {
+            match &file {
+                Transfer::Send(path) => {
+                    zmodem::send_file(&mut sender_data, &mut receiver_data, path, &mut progress)
+                        .await
+                }
+                Transfer::Receive(received) => {
+                    let directory = std::env::current_dir()?;
+                    zmodem::receive_file(
+                        &mut sender_data,
+                        &mut receiver_data,
+                        received,
+                        &directory,
+                        &mut progress,
+                    )
+                    .await
+                }
+            }
+        };
+        tokio::pin!(transfer);
+
+        loop {
+            tokio::select! {
+                result = &mut transfer => break Some(result),
+                msg_gui = receiver_gui.next() => {
+                    match msg_gui {
+                        Some(GuiMessage::Close) => break None,
+                        Some(msg) => apply(msg)?,
+                        None => return Err(ExtEventError),
+                    }
+                }
+            }
+        }
+    };
+
+    match result {
+        Some(Ok(())) => send_transfer_gui("Transfer complete".to_string())?,
+        Some(Err(e)) => {
+            let _ = sender_data
+                .send(Bytes::from_static(zmodem::ABORT_SEQUENCE))
+                .await;
+            send_transfer_gui(format!("Transfer failed: {}", e))?;
+        }
+        None => {
+            let _ = sender_data
+                .send(Bytes::from_static(zmodem::ABORT_SEQUENCE))
+                .await;
+            return Ok(false);
         }
     }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delimiter_codec() -> FrameCodec {
+        FrameCodec {
+            split: Split::Delimiter(b'\n'),
+            transfer: false,
+        }
+    }
+
+    #[test]
+    fn delimiter_frames() {
+        let mut codec = delimiter_codec();
+        let mut buf = BytesMut::from(&b"AT\r\nOK"[..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap().as_deref(),
+            Some(&b"AT\r\n"[..])
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert_eq!(&buf[..], b"OK");
+    }
+
+    /// `sz` ends its ZRQINIT header with `\r\x8a\x11`, without the delimiter.
+    #[test]
+    fn auto_start_without_delimiter() {
+        let mut codec = delimiter_codec();
+        let mut buf = BytesMut::from(&b"OK\n**\x18B00"[..]);
+        buf.extend_from_slice(b"00000000000000\r\x8a\x11");
+        assert_eq!(
+            codec.decode(&mut buf).unwrap().as_deref(),
+            Some(&b"OK\n"[..])
+        );
+        assert_eq!(
+            codec.decode(&mut buf).unwrap().as_deref(),
+            Some(zmodem::AUTO_START)
+        );
+
+        codec.transfer = true;
+        assert_eq!(
+            codec.decode(&mut buf).unwrap().as_deref(),
+            Some(&b"00000000000000\r\x8a\x11"[..])
+        );
+    }
+
+    #[test]
+    fn auto_start_split_between_reads() {
+        let mut codec = delimiter_codec();
+        let mut buf = BytesMut::from(&b"**\x18"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"B0000");
+        assert_eq!(
+            codec.decode(&mut buf).unwrap().as_deref(),
+            Some(zmodem::AUTO_START)
+        );
+    }
 }
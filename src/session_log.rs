@@ -0,0 +1,218 @@
+//! Logging of everything written and received on the port, done by the serial thread so the
+//! log is complete whatever the GUI keeps in its output.
+//!
+//! Each chunk is written on its own line with its UTC time, direction, bytes in hexadecimal and
+//! text:
+//!
+//! ```text
+//! 2021-06-14T08:15:02.318Z OUT 41 54 0D "AT\r"
+//! 2021-06-14T08:15:02.341Z IN  4F 4B 0D 0A "OK\r\n"
+//! ```
+
+use crate::serial::ByteDirection;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_TEMPLATE: &str = "stool-{port}-{date}-{time}.log";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogConfig {
+    pub directory: PathBuf,
+    /// Name of the log files, `{port}`, `{date}` and `{time}` are replaced when a file is
+    /// created.
+    pub template: String,
+    /// A new file is started when the current one would be larger.
+    pub max_size: Option<u64>,
+    /// A new file is started when the current one is older.
+    pub period: Option<Duration>,
+}
+
+/// The file being written.
+struct LogFile {
+    file: File,
+    port_name: String,
+    size: u64,
+    created: Instant,
+}
+
+pub struct SessionLog {
+    config: LogConfig,
+    current: Option<LogFile>,
+}
+
+/// UTC date and time of `time` as `(year, month, day, hours, minutes, seconds, millis)`.
+pub fn utc(time: SystemTime) -> (i64, u32, u32, u32, u32, u32, u32) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let days = (seconds / 86400) as i64;
+    let seconds = (seconds % 86400) as u32;
+
+    // Civil date from the days since the epoch, Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+/// `time` in the RFC 3339 format with milliseconds.
+pub fn timestamp(time: SystemTime) -> String {
+    let (year, month, day, hours, minutes, seconds, millis) = utc(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year, month, day, hours, minutes, seconds, millis
+    )
+}
+
+/// Port name usable in a file name, `/dev/ttyUSB0` gives `ttyUSB0`.
+fn port_file_name(port_name: &str) -> String {
+    let name = port_name
+        .rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .unwrap_or_default();
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+impl LogConfig {
+    /// File name given by the template for `port_name` at `time`.
+    pub fn file_name(&self, port_name: &str, time: SystemTime) -> String {
+        let (year, month, day, hours, minutes, seconds, _) = utc(time);
+        self.template
+            .replace("{port}", &port_file_name(port_name))
+            .replace("{date}", &format!("{:04}-{:02}-{:02}", year, month, day))
+            .replace(
+                "{time}",
+                &format!("{:02}{:02}{:02}", hours, minutes, seconds),
+            )
+    }
+}
+
+impl SessionLog {
+    pub fn new(config: LogConfig) -> Self {
+        SessionLog {
+            config,
+            current: None,
+        }
+    }
+
+    /// Whether the current file is done before writing `len` more bytes for `port_name`.
+    fn rotate(&self, port_name: &str, len: u64) -> bool {
+        match &self.current {
+            None => true,
+            Some(current) => {
+                current.port_name != port_name
+                    || self.config.max_size.map_or(false, |max_size| {
+                        current.size > 0 && current.size + len > max_size
+                    })
+                    || self
+                        .config
+                        .period
+                        .map_or(false, |period| current.created.elapsed() >= period)
+            }
+        }
+    }
+
+    /// Path of the next file, numbered when the template gives the name of an existing file.
+    fn next_path(&self, port_name: &str) -> PathBuf {
+        let name = self.config.file_name(port_name, SystemTime::now());
+        let path = self.config.directory.join(&name);
+        // The first file may continue an existing log, a rotation always starts a new file
+        let reusable = match (&self.current, fs::metadata(&path)) {
+            (_, Err(_)) => true,
+            (None, Ok(metadata)) => match self.config.max_size {
+                Some(max_size) => metadata.len() < max_size,
+                None => true,
+            },
+            (Some(_), Ok(_)) => false,
+        };
+        if reusable {
+            return path;
+        }
+
+        let (stem, extension) = match name.rfind('.') {
+            Some(dot) if dot > 0 => name.split_at(dot),
+            _ => (name.as_str(), ""),
+        };
+        let mut number = 1;
+        loop {
+            let path = self
+                .config
+                .directory
+                .join(format!("{}-{}{}", stem, number, extension));
+            if !path.exists() {
+                return path;
+            }
+            number += 1;
+        }
+    }
+
+    fn create(&self, port_name: &str) -> io::Result<LogFile> {
+        fs::create_dir_all(&self.config.directory)?;
+        let path = self.next_path(port_name);
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(LogFile {
+            size: file.metadata()?.len(),
+            file,
+            port_name: port_name.to_string(),
+            created: Instant::now(),
+        })
+    }
+
+    pub fn write(
+        &mut self,
+        port_name: &str,
+        direction: ByteDirection,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let hex: Vec<_> = data.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let direction = match direction {
+            ByteDirection::In => "IN ",
+            ByteDirection::Out => "OUT",
+        };
+        let line = format!(
+            "{} {} {} {:?}\n",
+            timestamp(SystemTime::now()),
+            direction,
+            hex.join(" "),
+            String::from_utf8_lossy(data)
+        );
+
+        if self.rotate(port_name, line.len() as u64) {
+            self.current = Some(self.create(port_name)?);
+        }
+        if let Some(current) = &mut self.current {
+            current.file.write_all(line.as_bytes())?;
+            current.size += line.len() as u64;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+//! SLCAN (Lawicel) ASCII protocol used by the CAN adapters seen as a serial port.
+
+use druid::Data;
+use std::fmt;
+
+/// Command terminator, the adapter answers it alone when a command succeeds.
+pub const CR: u8 = b'\r';
+/// Answer of the adapter when a command fails.
+pub const BELL: u8 = 0x07;
+
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub enum Bitrate {
+    K10,
+    K20,
+    K50,
+    K100,
+    K125,
+    K250,
+    K500,
+    K800,
+    M1,
+}
+
+impl Bitrate {
+    fn command(self) -> &'static str {
+        match self {
+            Bitrate::K10 => "S0",
+            Bitrate::K20 => "S1",
+            Bitrate::K50 => "S2",
+            Bitrate::K100 => "S3",
+            Bitrate::K125 => "S4",
+            Bitrate::K250 => "S5",
+            Bitrate::K500 => "S6",
+            Bitrate::K800 => "S7",
+            Bitrate::M1 => "S8",
+        }
+    }
+}
+
+impl fmt::Display for Bitrate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bitrate::K10 => write!(f, "10 kbit/s"),
+            Bitrate::K20 => write!(f, "20 kbit/s"),
+            Bitrate::K50 => write!(f, "50 kbit/s"),
+            Bitrate::K100 => write!(f, "100 kbit/s"),
+            Bitrate::K125 => write!(f, "125 kbit/s"),
+            Bitrate::K250 => write!(f, "250 kbit/s"),
+            Bitrate::K500 => write!(f, "500 kbit/s"),
+            Bitrate::K800 => write!(f, "800 kbit/s"),
+            Bitrate::M1 => write!(f, "1 Mbit/s"),
+        }
+    }
+}
+
+/// Commands setting the bitrate and the timestamps then opening the CAN channel.
+pub fn open_commands(bitrate: Bitrate, timestamps: bool) -> String {
+    format!(
+        "{}\rZ{}\rO\r",
+        bitrate.command(),
+        if timestamps { 1 } else { 0 }
+    )
+}
+
+pub fn close_command() -> String {
+    "C\r".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanFrame {
+    pub id: u32,
+    pub extended: bool,
+    pub remote: bool,
+    pub dlc: u8,
+    pub data: Vec<u8>,
+    /// Milliseconds within the minute, given by the adapter when timestamps are enabled.
+    pub timestamp: Option<u16>,
+}
+
+impl CanFrame {
+    /// Build a frame to transmit, the DLC of a data frame is the length of its data.
+    pub fn new(
+        id: u32,
+        extended: bool,
+        remote: bool,
+        dlc: u8,
+        data: Vec<u8>,
+    ) -> Result<Self, &'static str> {
+        let max_id = if extended { 0x1FFF_FFFF } else { 0x7FF };
+        if id > max_id {
+            return Err("Incorrect CAN identifier");
+        }
+        if data.len() > 8 || dlc > 8 {
+            return Err("A CAN frame holds at most 8 bytes");
+        }
+
+        let (dlc, data) = if remote {
+            (dlc, Vec::new())
+        } else {
+            (data.len() as u8, data)
+        };
+        Ok(CanFrame {
+            id,
+            extended,
+            remote,
+            dlc,
+            data,
+            timestamp: None,
+        })
+    }
+
+    /// Transmit command of the frame, terminated by CR.
+    pub fn encode(&self) -> String {
+        let command = match (self.extended, self.remote) {
+            (false, false) => 't',
+            (true, false) => 'T',
+            (false, true) => 'r',
+            (true, true) => 'R',
+        };
+        let id = if self.extended {
+            format!("{:08X}", self.id)
+        } else {
+            format!("{:03X}", self.id)
+        };
+
+        format!(
+            "{}{}{}{}\r",
+            command,
+            id,
+            self.dlc,
+            hex::encode_upper(&self.data)
+        )
+    }
+}
+
+/// Parse a `t`, `T`, `r` or `R` line received from the adapter, `None` for the other lines.
+pub fn parse_frame(line: &str) -> Option<Result<CanFrame, &'static str>> {
+    let (extended, remote) = match line.chars().next()? {
+        't' => (false, false),
+        'T' => (true, false),
+        'r' => (false, true),
+        'R' => (true, true),
+        _ => return None,
+    };
+    Some(parse_fields(&line[1..], extended, remote))
+}
+
+fn parse_fields(fields: &str, extended: bool, remote: bool) -> Result<CanFrame, &'static str> {
+    let id_len = if extended { 8 } else { 3 };
+    let id = fields.get(..id_len).ok_or("Frame too short")?;
+    let id = u32::from_str_radix(id, 16).map_err(|_| "Incorrect CAN identifier")?;
+
+    let dlc = fields.get(id_len..id_len + 1).ok_or("Frame too short")?;
+    let dlc = dlc
+        .parse::<u8>()
+        .ok()
+        .filter(|&dlc| dlc <= 8)
+        .ok_or("Incorrect DLC")?;
+
+    let data_len = if remote { 0 } else { dlc as usize * 2 };
+    let data_end = id_len + 1 + data_len;
+    let data = fields.get(id_len + 1..data_end).ok_or("Frame too short")?;
+    let data = hex::decode(data).map_err(|_| "Incorrect data")?;
+
+    let timestamp = match &fields[data_end..] {
+        "" => None,
+        timestamp if timestamp.len() == 4 => {
+            Some(u16::from_str_radix(timestamp, 16).map_err(|_| "Incorrect timestamp")?)
+        }
+        _ => return Err("Unexpected characters after the data"),
+    };
+
+    Ok(CanFrame {
+        id,
+        extended,
+        remote,
+        dlc,
+        data,
+        timestamp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_frame() {
+        let frame = parse_frame("t1232AABB").unwrap().unwrap();
+        assert_eq!(
+            frame,
+            CanFrame::new(0x123, false, false, 2, vec![0xAA, 0xBB]).unwrap()
+        );
+        assert_eq!(frame.encode(), "t1232AABB\r");
+    }
+
+    #[test]
+    fn extended_remote_frame_with_timestamp() {
+        let frame = parse_frame("R1ABCDEF8403E8").unwrap().unwrap();
+        assert_eq!(frame.id, 0x1ABC_DEF8);
+        assert!(frame.extended && frame.remote);
+        assert_eq!(frame.dlc, 4);
+        assert!(frame.data.is_empty());
+        assert_eq!(frame.timestamp, Some(1000));
+    }
+
+    #[test]
+    fn other_lines_ignored() {
+        assert_eq!(parse_frame(""), None);
+        assert_eq!(parse_frame("V1013"), None);
+        assert_eq!(parse_frame("z"), None);
+    }
+
+    #[test]
+    fn malformed_frames() {
+        assert_eq!(parse_frame("t12"), Some(Err("Frame too short")));
+        assert_eq!(parse_frame("t12G0"), Some(Err("Incorrect CAN identifier")));
+        assert_eq!(parse_frame("t1239"), Some(Err("Incorrect DLC")));
+        assert_eq!(parse_frame("t1232AA"), Some(Err("Frame too short")));
+        assert_eq!(parse_frame("t1231ZZ"), Some(Err("Incorrect data")));
+        assert_eq!(parse_frame("t1230XYZW"), Some(Err("Incorrect timestamp")));
+        assert_eq!(
+            parse_frame("t1230123"),
+            Some(Err("Unexpected characters after the data"))
+        );
+    }
+
+    #[test]
+    fn frame_limits() {
+        assert!(CanFrame::new(0x800, false, false, 0, Vec::new()).is_err());
+        assert!(CanFrame::new(0x800, true, false, 0, Vec::new()).is_ok());
+        assert!(CanFrame::new(1, false, false, 0, vec![0; 9]).is_err());
+        let remote = CanFrame::new(0x7FF, false, true, 8, Vec::new()).unwrap();
+        assert_eq!(remote.encode(), "r7FF8\r");
+    }
+
+    #[test]
+    fn open_and_close() {
+        assert_eq!(open_commands(Bitrate::K500, true), "S6\rZ1\rO\r");
+        assert_eq!(close_command(), "C\r");
+    }
+}
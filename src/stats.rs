@@ -0,0 +1,88 @@
+//! Traffic counters of the open port, kept by the serial thread and sent to the GUI every
+//! second.
+
+use crate::serial::ByteDirection;
+use druid::Data;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, PartialEq, Data)]
+pub struct Stats {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Frames counted when the received bytes are split into frames.
+    pub framed: bool,
+    pub frames_in: u64,
+    pub frames_out: u64,
+    /// Throughputs in bytes per second over the last period.
+    pub throughput_in: f64,
+    pub throughput_out: f64,
+    pub peak_in: f64,
+    pub peak_out: f64,
+    pub read_errors: u64,
+    pub reconnects: u64,
+    /// Seconds since the port has been opened.
+    pub duration: f64,
+    /// Bytes per second the line can carry at its baud rate.
+    pub capacity: f64,
+    /// Counters at the start of the period.
+    last_in: u64,
+    last_out: u64,
+}
+
+impl Stats {
+    pub fn new(capacity: f64, framed: bool) -> Self {
+        Stats {
+            capacity,
+            framed,
+            ..Stats::default()
+        }
+    }
+
+    /// Count the bytes given to the display, a frame when `frame` is one.
+    pub fn record(&mut self, direction: ByteDirection, len: usize, frame: bool) {
+        let frame = (self.framed && frame) as u64;
+        match direction {
+            ByteDirection::In => {
+                self.bytes_in += len as u64;
+                self.frames_in += frame;
+            }
+            ByteDirection::Out => {
+                self.bytes_out += len as u64;
+                self.frames_out += frame;
+            }
+        }
+    }
+
+    /// End of a period of `period`, `duration` since the port has been opened.
+    pub fn tick(&mut self, period: Duration, duration: Duration) {
+        let seconds = period.as_secs_f64().max(f64::EPSILON);
+        self.throughput_in = (self.bytes_in - self.last_in) as f64 / seconds;
+        self.throughput_out = (self.bytes_out - self.last_out) as f64 / seconds;
+        self.peak_in = self.peak_in.max(self.throughput_in);
+        self.peak_out = self.peak_out.max(self.throughput_out);
+        self.last_in = self.bytes_in;
+        self.last_out = self.bytes_out;
+        self.duration = duration.as_secs_f64();
+    }
+
+    /// Part of the capacity of the line used by `throughput`, in percent.
+    pub fn load(&self, throughput: f64) -> f64 {
+        if self.capacity > 0. {
+            throughput / self.capacity * 100.
+        } else {
+            0.
+        }
+    }
+
+    /// Short summary for the status bar.
+    pub fn summary(&self) -> String {
+        format!(
+            "In {:.0} B/s ({:.0} %), out {:.0} B/s ({:.0} %), {} errors",
+            self.throughput_in,
+            self.load(self.throughput_in),
+            self.throughput_out,
+            self.load(self.throughput_out),
+            self.read_errors
+        )
+    }
+}
@@ -1,42 +1,1189 @@
-use crate::event::{EventHandler, CLOSE_PORT, OPEN_PORT, WRITE_PORT};
-use crate::widgets::NumericFormatter;
+use crate::checksum::{Algorithm, Endianness};
+use crate::event::{
+    get_tag_color, EventHandler, APPLY_MODBUS_TABLE, CLOSE_CAN_CHANNEL, CLOSE_PORT, LOAD_LAYOUT,
+    LOAD_PROFILE, OPEN_CAN_CHANNEL, OPEN_PORT, RECONFIGURE_PORT, REPLAY_CAPTURE, RUN_SCRIPT,
+    RUN_SEQUENCE, SAVE_PROFILE, SEND_BREAK, START_CAPTURE, STOP_CAPTURE, STOP_REPLAY, STOP_SCRIPT,
+    TOGGLE_DTR, TOGGLE_RTS, TOGGLE_SESSION_LOG, WRITE_PORT,
+};
+use crate::layout::Layout;
+use crate::modbus::Function;
+use crate::plot::PlotFormat;
+use crate::serial::ModemStatus;
+use crate::slcan::Bitrate;
+use crate::stats::Stats;
+use crate::widgets::{zoom, NumericFormatter, Plot};
 use crate::{
     data::{
-        AppData, DruidDataBits, DruidFlowControl, DruidParity, DruidStopBits, PortNameLens,
-        Protocol, ToWriteLens,
+        AppData, AtExchange, AtStatus, CanRow, CaptureData, ChecksumData, DruidDataBits,
+        DruidFlowControl, DruidParity, DruidStopBits, Framing, LineEnding, LogData, ModbusData,
+        NmeaData, NmeaRow, OutputTag, PlotData, PortNameLens, ProfileData, Protocol, ReplaySpeed,
+        ScpiData, ScriptData, SlcanData, ToWriteLens,
     },
     widgets::{ContextMenuController, PortTextBoxController, TextBoxController},
 };
 
+use druid::kurbo::Circle;
 use druid::widget::{
-    Button, CrossAxisAlignment, Flex, Label, LineBreaking, RadioGroup, RawLabel, Scroll, SizedBox,
-    TextBox, WidgetExt,
+    Button, Checkbox, CrossAxisAlignment, Either, Flex, Label, LineBreaking, List, Painter,
+    RadioGroup, RawLabel, Scroll, SizedBox, TextBox, ViewSwitcher, WidgetExt,
+};
+use druid::{
+    commands, Application, Color, Command, FileDialogOptions, FileSpec, FontDescriptor, FontFamily,
+    LensExt, LocalizedString, RenderContext, Target, Widget,
 };
-use druid::{Color, FontDescriptor, FontFamily, LocalizedString, Widget};
+use std::sync::Arc;
 
-pub fn make_ui() -> impl Widget<AppData> {
-    let write_panel = Flex::column()
-        .with_child(SizedBox::empty().height(8.))
+fn make_send_button() -> impl Widget<AppData> {
+    Button::new(LocalizedString::new("Send"))
+        .on_click(|ctx, _data, _env| {
+            ctx.submit_command(WRITE_PORT);
+        })
+        .fix_width(110.0)
+}
+
+fn make_modbus_form() -> impl Widget<AppData> {
+    let field = |name: &'static str| {
+        Flex::column()
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .with_child(Label::new(LocalizedString::new(name)))
+            .with_spacer(3.)
+    };
+
+    Flex::row()
         .with_child(
-            Flex::row()
-                .with_flex_child(
-                    TextBox::multiline()
-                        .expand_width()
-                        .lens(ToWriteLens)
+            RadioGroup::new(vec![
+                (LocalizedString::new("Read coils"), Function::ReadCoils),
+                (
+                    LocalizedString::new("Read discrete inputs"),
+                    Function::ReadDiscreteInputs,
+                ),
+                (
+                    LocalizedString::new("Read holding registers"),
+                    Function::ReadHoldingRegisters,
+                ),
+                (
+                    LocalizedString::new("Read input registers"),
+                    Function::ReadInputRegisters,
+                ),
+                (
+                    LocalizedString::new("Write single coil"),
+                    Function::WriteSingleCoil,
+                ),
+                (
+                    LocalizedString::new("Write single register"),
+                    Function::WriteSingleRegister,
+                ),
+                (
+                    LocalizedString::new("Write multiple coils"),
+                    Function::WriteMultipleCoils,
+                ),
+                (
+                    LocalizedString::new("Write multiple registers"),
+                    Function::WriteMultipleRegisters,
+                ),
+            ])
+            .border(Color::grey(0.6), 2.0)
+            .rounded(5.0)
+            .lens(AppData::modbus.then(ModbusData::function)),
+        )
+        .with_spacer(6.)
+        .with_flex_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(
+                    Flex::row()
+                        .with_child(
+                            field("Unit:").with_child(
+                                TextBox::new()
+                                    .with_formatter(NumericFormatter)
+                                    .fix_width(80.0)
+                                    .lens(AppData::modbus.then(ModbusData::unit))
+                                    .controller(TextBoxController::default()),
+                            ),
+                        )
+                        .with_spacer(6.)
+                        .with_child(
+                            field("Address:").with_child(
+                                TextBox::new()
+                                    .with_formatter(NumericFormatter)
+                                    .fix_width(80.0)
+                                    .lens(AppData::modbus.then(ModbusData::address))
+                                    .controller(TextBoxController::default()),
+                            ),
+                        )
+                        .with_spacer(6.)
+                        .with_child(
+                            field("Quantity:").with_child(
+                                TextBox::new()
+                                    .with_formatter(NumericFormatter)
+                                    .fix_width(80.0)
+                                    .lens(AppData::modbus.then(ModbusData::quantity))
+                                    .controller(TextBoxController::default()),
+                            ),
+                        ),
+                )
+                .with_spacer(6.)
+                .with_child(
+                    field("Values to write:").with_child(
+                        TextBox::new()
+                            .with_placeholder("1, 0x10, 42")
+                            .expand_width()
+                            .lens(AppData::modbus.then(ModbusData::values))
+                            .controller(TextBoxController::default()),
+                    ),
+                ),
+            1.0,
+        )
+        .with_spacer(6.)
+        .with_child(make_send_button())
+        .with_child(SizedBox::empty().width(6.))
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+}
+
+fn make_modbus_slave_form() -> impl Widget<AppData> {
+    Flex::row()
+        .with_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(Label::new(LocalizedString::new("Unit:")))
+                .with_spacer(3.)
+                .with_child(
+                    TextBox::new()
+                        .with_formatter(NumericFormatter)
+                        .fix_width(80.0)
+                        .lens(AppData::modbus.then(ModbusData::slave_unit))
                         .controller(TextBoxController::default()),
-                    1.0,
+                ),
+        )
+        .with_spacer(6.)
+        .with_flex_child(
+            TextBox::multiline()
+                .with_placeholder("coil 0 = 1\nholding 100 = 0x2A")
+                .expand_width()
+                .fix_height(120.0)
+                .lens(AppData::modbus.then(ModbusData::table))
+                .controller(TextBoxController::default()),
+            1.0,
+        )
+        .with_spacer(6.)
+        .with_child(
+            Button::new(LocalizedString::new("Apply table"))
+                .on_click(|ctx, _data, _env| {
+                    ctx.submit_command(APPLY_MODBUS_TABLE);
+                })
+                .fix_width(110.0),
+        )
+        .with_child(SizedBox::empty().width(6.))
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+}
+
+fn make_raw_options() -> impl Widget<AppData> {
+    let title = |name: &'static str| Label::new(LocalizedString::new(name));
+
+    Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(title("Checksum:"))
+                .with_spacer(3.)
+                .with_child(
+                    RadioGroup::new(vec![
+                        (LocalizedString::new("None"), Algorithm::None),
+                        (LocalizedString::new("Sum 8"), Algorithm::Sum8),
+                        (LocalizedString::new("XOR 8"), Algorithm::Xor8),
+                        (LocalizedString::new("CRC-8"), Algorithm::Crc8),
+                        (LocalizedString::new("CRC-8/MAXIM"), Algorithm::Crc8Maxim),
+                        (
+                            LocalizedString::new("CRC-8/SAE-J1850"),
+                            Algorithm::Crc8SaeJ1850,
+                        ),
+                        (
+                            LocalizedString::new("CRC-16/MODBUS"),
+                            Algorithm::Crc16Modbus,
+                        ),
+                        (LocalizedString::new("CRC-16/CCITT"), Algorithm::Crc16Ccitt),
+                        (
+                            LocalizedString::new("CRC-16/XMODEM"),
+                            Algorithm::Crc16Xmodem,
+                        ),
+                        (
+                            LocalizedString::new("CRC-16/MCRF4XX"),
+                            Algorithm::Crc16Mcrf4xx,
+                        ),
+                        (LocalizedString::new("CRC-32"), Algorithm::Crc32),
+                    ])
+                    .border(Color::grey(0.6), 2.0)
+                    .rounded(5.0)
+                    .lens(AppData::checksum.then(ChecksumData::algorithm)),
+                ),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(title("Byte order:"))
+                .with_spacer(3.)
+                .with_child(
+                    RadioGroup::new(vec![
+                        (LocalizedString::new("Big endian"), Endianness::Big),
+                        (LocalizedString::new("Little endian"), Endianness::Little),
+                    ])
+                    .fix_width(110.0)
+                    .border(Color::grey(0.6), 2.0)
+                    .rounded(5.0)
+                    .lens(AppData::checksum.then(ChecksumData::endianness)),
+                )
+                .with_spacer(6.)
+                .with_child(title("Skip first bytes:"))
+                .with_spacer(3.)
+                .with_child(
+                    TextBox::new()
+                        .with_formatter(NumericFormatter)
+                        .fix_width(110.0)
+                        .lens(AppData::checksum.then(ChecksumData::skip_start))
+                        .controller(TextBoxController::default()),
+                )
+                .with_spacer(6.)
+                .with_child(title("Skip last bytes:"))
+                .with_spacer(3.)
+                .with_child(
+                    TextBox::new()
+                        .with_formatter(NumericFormatter)
+                        .fix_width(110.0)
+                        .lens(AppData::checksum.then(ChecksumData::skip_end))
+                        .controller(TextBoxController::default()),
+                ),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(title("Framing:"))
+                .with_spacer(3.)
+                .with_child(
+                    RadioGroup::new(vec![
+                        (LocalizedString::new("None"), Framing::None),
+                        (LocalizedString::new("Silence"), Framing::Silence),
+                        (LocalizedString::new("Delimiter"), Framing::Delimiter),
+                        (LocalizedString::new("Layout"), Framing::Layout),
+                    ])
+                    .fix_width(110.0)
+                    .border(Color::grey(0.6), 2.0)
+                    .rounded(5.0)
+                    .lens(AppData::framing),
+                )
+                .with_spacer(6.)
+                .with_child(title("Delimiter:"))
+                .with_spacer(3.)
+                .with_child(
+                    TextBox::new()
+                        .with_formatter(NumericFormatter)
+                        .fix_width(110.0)
+                        .lens(AppData::delimiter)
+                        .controller(TextBoxController::default()),
+                )
+                .with_spacer(6.)
+                .with_child(
+                    Button::new(LocalizedString::new("Load layout"))
+                        .on_click(|ctx, _data, _env| {
+                            let open_dialog_options = FileDialogOptions::new()
+                                .title("Choose a frame layout")
+                                .allowed_types(vec![FileSpec::new("TOML", &["toml"])])
+                                .accept_command(LOAD_LAYOUT);
+
+                            ctx.submit_command(Command::new(
+                                commands::SHOW_OPEN_PANEL,
+                                open_dialog_options,
+                                Target::Auto,
+                            ))
+                        })
+                        .fix_width(110.0),
+                )
+                .with_child(
+                    Label::new(|layout: &Option<Arc<Layout>>, _env: &_| match layout {
+                        Some(layout) => layout.name.clone(),
+                        None => "No layout".to_string(),
+                    })
+                    .with_text_size(12.0)
+                    .fix_width(110.0)
+                    .lens(AppData::layout),
+                ),
+        )
+}
+
+fn make_raw_form() -> impl Widget<AppData> {
+    Flex::row()
+        .with_flex_child(
+            TextBox::multiline()
+                .expand_width()
+                .lens(ToWriteLens)
+                .controller(TextBoxController::default()),
+            1.0,
+        )
+        .with_spacer(6.)
+        .with_child(make_raw_options())
+        .with_spacer(6.)
+        .with_child(make_send_button())
+        .with_child(SizedBox::empty().width(6.))
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+}
+
+/// Last decoded fields of each kind of NMEA sentence, shown next to the output.
+fn make_nmea_table() -> impl Widget<AppData> {
+    let cell = || Label::new(|text: &String, _env: &_| text.to_string());
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Label::new(|invalid: &u32, _env: &_| format!("Invalid sentences: {}", invalid))
+                .with_text_color(get_tag_color(OutputTag::Invalid))
+                .lens(AppData::nmea.then(NmeaData::invalid)),
+        )
+        .with_spacer(6.)
+        .with_flex_child(
+            Scroll::new(
+                List::new(|| {
+                    Flex::row()
+                        .with_child(
+                            cell()
+                                .with_text_color(get_tag_color(OutputTag::Decoded))
+                                .lens(NmeaRow::sentence)
+                                .fix_width(60.),
+                        )
+                        .with_child(cell().lens(NmeaRow::field).fix_width(80.))
+                        .with_flex_child(cell().lens(NmeaRow::value).expand_width(), 1.0)
+                })
+                .lens(AppData::nmea.then(NmeaData::rows)),
+            )
+            .vertical()
+            .expand_height(),
+            1.0,
+        )
+        .padding(6.)
+        .fix_width(340.)
+        .background(Color::rgb8(0x1a, 0x1a, 0x1a))
+}
+
+fn make_slcan_form() -> impl Widget<AppData> {
+    let field = |name: &'static str| {
+        Flex::column()
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .with_child(Label::new(LocalizedString::new(name)))
+            .with_spacer(3.)
+    };
+
+    Flex::row()
+        .with_child(
+            RadioGroup::new(vec![
+                ("10 kbit/s", Bitrate::K10),
+                ("20 kbit/s", Bitrate::K20),
+                ("50 kbit/s", Bitrate::K50),
+                ("100 kbit/s", Bitrate::K100),
+                ("125 kbit/s", Bitrate::K125),
+                ("250 kbit/s", Bitrate::K250),
+                ("500 kbit/s", Bitrate::K500),
+                ("800 kbit/s", Bitrate::K800),
+                ("1 Mbit/s", Bitrate::M1),
+            ])
+            .border(Color::grey(0.6), 2.0)
+            .rounded(5.0)
+            .lens(AppData::slcan.then(SlcanData::bitrate)),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(
+                    Checkbox::new(LocalizedString::new("Timestamps"))
+                        .lens(AppData::slcan.then(SlcanData::timestamps)),
+                )
+                .with_spacer(6.)
+                .with_child(
+                    Button::new(LocalizedString::new("Open channel"))
+                        .on_click(|ctx, _data, _env| ctx.submit_command(OPEN_CAN_CHANNEL))
+                        .fix_width(110.0),
+                )
+                .with_spacer(6.)
+                .with_child(
+                    Button::new(LocalizedString::new("Close channel"))
+                        .on_click(|ctx, _data, _env| ctx.submit_command(CLOSE_CAN_CHANNEL))
+                        .fix_width(110.0),
+                ),
+        )
+        .with_spacer(6.)
+        .with_flex_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(
+                    Flex::row()
+                        .with_child(
+                            field("ID (hex):").with_child(
+                                TextBox::new()
+                                    .fix_width(90.0)
+                                    .lens(AppData::slcan.then(SlcanData::id))
+                                    .controller(TextBoxController::default()),
+                            ),
+                        )
+                        .with_spacer(6.)
+                        .with_child(
+                            field("DLC:").with_child(
+                                TextBox::new()
+                                    .with_formatter(NumericFormatter)
+                                    .fix_width(50.0)
+                                    .lens(AppData::slcan.then(SlcanData::dlc))
+                                    .controller(TextBoxController::default()),
+                            ),
+                        )
+                        .with_spacer(6.)
+                        .with_child(
+                            Flex::column()
+                                .cross_axis_alignment(CrossAxisAlignment::Start)
+                                .with_child(
+                                    Checkbox::new(LocalizedString::new("Extended"))
+                                        .lens(AppData::slcan.then(SlcanData::extended)),
+                                )
+                                .with_spacer(3.)
+                                .with_child(
+                                    Checkbox::new(LocalizedString::new("Remote"))
+                                        .lens(AppData::slcan.then(SlcanData::remote)),
+                                ),
+                        ),
                 )
                 .with_spacer(6.)
                 .with_child(
-                    Button::new(LocalizedString::new("Send"))
+                    field("Data (hex):").with_child(
+                        TextBox::new()
+                            .with_placeholder("11 22 33")
+                            .expand_width()
+                            .lens(AppData::slcan.then(SlcanData::data))
+                            .controller(TextBoxController::default()),
+                    ),
+                ),
+            1.0,
+        )
+        .with_spacer(6.)
+        .with_child(make_send_button())
+        .with_child(SizedBox::empty().width(6.))
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+}
+
+/// CAN frames received by the SLCAN adapter, the last one first.
+fn make_can_table() -> impl Widget<AppData> {
+    let cell = || Label::new(|text: &String, _env: &_| text.to_string());
+    let title = |name: &'static str, width: f64| {
+        Label::new(LocalizedString::new(name))
+            .with_text_color(get_tag_color(OutputTag::Decoded))
+            .fix_width(width)
+    };
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Flex::row()
+                .with_child(title("Time", 100.))
+                .with_child(title("ID", 80.))
+                .with_child(title("DLC", 40.))
+                .with_child(title("Data", 120.)),
+        )
+        .with_spacer(6.)
+        .with_flex_child(
+            Scroll::new(
+                List::new(move || {
+                    Flex::row()
+                        .with_child(cell().lens(CanRow::timestamp).fix_width(100.))
+                        .with_child(cell().lens(CanRow::id).fix_width(80.))
+                        .with_child(cell().lens(CanRow::dlc).fix_width(40.))
+                        .with_flex_child(cell().lens(CanRow::data).expand_width(), 1.0)
+                })
+                .lens(AppData::slcan.then(SlcanData::frames)),
+            )
+            .vertical()
+            .expand_height(),
+            1.0,
+        )
+        .padding(6.)
+        .fix_width(380.)
+        .background(Color::rgb8(0x1a, 0x1a, 0x1a))
+}
+
+/// AT commands with their response, each response can be collapsed.
+fn make_at_transcript() -> impl Widget<AppData> {
+    let status = |tag: OutputTag| {
+        Label::new(|exchange: &AtExchange, _env: &_| match exchange.status {
+            AtStatus::Pending => "...".to_string(),
+            AtStatus::Ok => format!("OK {}", exchange.duration),
+            AtStatus::Error => format!("ERROR {}", exchange.duration),
+            AtStatus::Unsolicited => format!("Unsolicited {}", exchange.duration),
+        })
+        .with_text_color(get_tag_color(tag))
+    };
+
+    Scroll::new(
+        List::new(move || {
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(
+                    Flex::row()
+                        .with_child(
+                            Button::dynamic(|exchange: &AtExchange, _env| {
+                                if exchange.expanded { "-" } else { "+" }.to_string()
+                            })
+                            .on_click(|_ctx, exchange: &mut AtExchange, _env| {
+                                exchange.expanded = !exchange.expanded
+                            })
+                            .fix_width(30.),
+                        )
+                        .with_spacer(6.)
+                        .with_child(
+                            Label::new(|command: &String, _env: &_| command.to_string())
+                                .with_font(
+                                    FontDescriptor::new(FontFamily::MONOSPACE).with_size(18.),
+                                )
+                                .with_text_color(get_tag_color(OutputTag::TextOut))
+                                .lens(AtExchange::command),
+                        )
+                        .with_spacer(12.)
+                        .with_child(Either::new(
+                            |exchange: &AtExchange, _env| exchange.status == AtStatus::Error,
+                            status(OutputTag::Invalid),
+                            status(OutputTag::Decoded),
+                        )),
+                )
+                .with_child(Either::new(
+                    |exchange: &AtExchange, _env| exchange.expanded,
+                    Label::new(|response: &String, _env: &_| response.to_string())
+                        .with_font(FontDescriptor::new(FontFamily::MONOSPACE).with_size(18.))
+                        .with_text_color(get_tag_color(OutputTag::TextIn))
+                        .with_line_break_mode(LineBreaking::WordWrap)
+                        .lens(AtExchange::response)
+                        .padding((36., 0., 0., 6.)),
+                    SizedBox::empty(),
+                ))
+        })
+        .lens(AppData::at_exchanges),
+    )
+    .vertical()
+    .expand()
+}
+
+/// Identification of the SCPI instrument, error polling and numeric readings.
+fn make_scpi_panel() -> impl Widget<AppData> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Label::new(|idn: &String, _env: &_| format!("Instrument: {}", idn))
+                .with_line_break_mode(LineBreaking::WordWrap)
+                .lens(AppData::scpi.then(ScpiData::idn)),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Checkbox::new(LocalizedString::new("Poll errors (SYST:ERR?)"))
+                .lens(AppData::scpi.then(ScpiData::poll_errors)),
+        )
+        .with_spacer(6.)
+        .with_child(Label::new(LocalizedString::new(
+            "Readings (time, query, values):",
+        )))
+        .with_spacer(3.)
+        .with_flex_child(
+            TextBox::multiline()
+                .expand()
+                .lens(AppData::scpi.then(ScpiData::readings)),
+            1.0,
+        )
+        .with_spacer(6.)
+        .with_child(
+            Button::new(LocalizedString::new("Copy readings"))
+                .on_click(|_ctx, data: &mut AppData, _env| {
+                    Application::global()
+                        .clipboard()
+                        .put_string(&data.scpi.readings);
+                })
+                .fix_width(110.0),
+        )
+        .padding(6.)
+        .fix_width(340.)
+        .background(Color::rgb8(0x1a, 0x1a, 0x1a))
+}
+
+/// Editor of the Rhai script automating the session.
+fn make_script_panel() -> impl Widget<AppData> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(LocalizedString::new(
+            "Script (open, close, write, send_break, read_until, expect, sleep, log):",
+        )))
+        .with_spacer(3.)
+        .with_flex_child(
+            TextBox::multiline()
+                .with_font(FontDescriptor::new(FontFamily::MONOSPACE))
+                .expand()
+                .lens(AppData::script.then(ScriptData::source)),
+            1.0,
+        )
+        .with_spacer(6.)
+        .with_child(
+            Flex::row()
+                .with_child(
+                    Button::new(LocalizedString::new("Run"))
                         .on_click(|ctx, _data, _env| {
-                            ctx.submit_command(WRITE_PORT);
+                            ctx.submit_command(RUN_SCRIPT);
                         })
+                        .disabled_if(|data: &AppData, _env| data.script.running)
                         .fix_width(110.0),
                 )
-                .with_child(SizedBox::empty().width(6.))
-                .cross_axis_alignment(CrossAxisAlignment::Center),
+                .with_spacer(6.)
+                .with_child(
+                    Button::new(LocalizedString::new("Stop"))
+                        .on_click(|ctx, _data, _env| {
+                            ctx.submit_command(STOP_SCRIPT);
+                        })
+                        .disabled_if(|data: &AppData, _env| !data.script.running)
+                        .fix_width(110.0),
+                ),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Button::new(LocalizedString::new("Run test sequence"))
+                .on_click(|ctx, _data, _env| {
+                    let open_dialog_options = FileDialogOptions::new()
+                        .title("Choose a test sequence")
+                        .allowed_types(vec![
+                            FileSpec::new("TOML", &["toml"]),
+                            FileSpec::new("YAML", &["yaml", "yml"]),
+                        ])
+                        .accept_command(RUN_SEQUENCE);
+
+                    ctx.submit_command(Command::new(
+                        commands::SHOW_OPEN_PANEL,
+                        open_dialog_options,
+                        Target::Auto,
+                    ))
+                })
+                .disabled_if(|data: &AppData, _env| data.script.running)
+                .fix_width(226.0),
+        )
+        .padding(6.)
+        .fix_width(340.)
+        .background(Color::rgb8(0x1a, 0x1a, 0x1a))
+}
+
+/// Saved profiles, clicking one applies its settings.
+fn make_profile_panel() -> impl Widget<AppData> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(LocalizedString::new("Profile name:")))
+        .with_spacer(3.)
+        .with_child(
+            TextBox::new()
+                .fix_width(150.0)
+                .lens(AppData::profiles.then(ProfileData::name)),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Button::new(LocalizedString::new("Save profile"))
+                .on_click(|ctx, _data, _env| {
+                    ctx.submit_command(SAVE_PROFILE);
+                })
+                .fix_width(150.0),
+        )
+        .with_spacer(6.)
+        .with_child(Label::new(LocalizedString::new("Saved profiles:")))
+        .with_spacer(3.)
+        .with_flex_child(
+            Scroll::new(
+                List::new(|| {
+                    Button::dynamic(|name: &String, _env| name.clone())
+                        .on_click(|ctx, name: &mut String, _env| {
+                            ctx.submit_command(LOAD_PROFILE.with(name.clone()));
+                        })
+                        .fix_width(150.0)
+                        .padding((0., 0., 0., 3.))
+                })
+                .lens(AppData::profiles.then(ProfileData::names)),
+            )
+            .vertical(),
+            1.0,
+        )
+        .padding(6.)
+        .fix_width(180.)
+        .background(Color::rgb8(0x1a, 0x1a, 0x1a))
+}
+
+/// Settings of the session log, they are locked while logging.
+fn make_session_log_panel() -> impl Widget<AppData> {
+    let settings = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(LocalizedString::new("Directory:")))
+        .with_spacer(3.)
+        .with_child(TextBox::new().fix_width(230.0).lens(LogData::directory))
+        .with_spacer(6.)
+        .with_child(Label::new(LocalizedString::new(
+            "File name ({port}, {date}, {time}):",
+        )))
+        .with_spacer(3.)
+        .with_child(TextBox::new().fix_width(230.0).lens(LogData::template))
+        .with_spacer(6.)
+        .with_child(Label::new(LocalizedString::new(
+            "New file after MB (0 = never):",
+        )))
+        .with_spacer(3.)
+        .with_child(
+            TextBox::new()
+                .with_formatter(NumericFormatter)
+                .fix_width(110.0)
+                .lens(LogData::max_size)
+                .controller(TextBoxController::default()),
+        )
+        .with_spacer(6.)
+        .with_child(Label::new(LocalizedString::new(
+            "New file after minutes (0 = never):",
+        )))
+        .with_spacer(3.)
+        .with_child(
+            TextBox::new()
+                .with_formatter(NumericFormatter)
+                .fix_width(110.0)
+                .lens(LogData::period)
+                .controller(TextBoxController::default()),
+        )
+        .disabled_if(|data: &LogData, _env| data.active)
+        .lens(AppData::session_log);
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(settings)
+        .with_spacer(6.)
+        .with_child(
+            Button::dynamic(|data: &AppData, _env| {
+                if data.session_log.active {
+                    "Stop logging".to_string()
+                } else {
+                    "Start logging".to_string()
+                }
+            })
+            .on_click(|ctx, _data, _env| {
+                ctx.submit_command(TOGGLE_SESSION_LOG);
+            })
+            .fix_width(110.0),
+        )
+        .with_flex_spacer(1.0)
+        .padding(6.)
+        .fix_width(260.)
+        .background(Color::rgb8(0x1a, 0x1a, 0x1a))
+}
+
+/// Recording and replay of the captures.
+fn make_capture_panel() -> impl Widget<AppData> {
+    let capture_spec = || FileSpec::new("Stool capture", &["stcap"]);
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Button::dynamic(|data: &AppData, _env| {
+                if data.capture.recording {
+                    "Stop recording".to_string()
+                } else {
+                    "Record capture".to_string()
+                }
+            })
+            .on_click(move |ctx, data: &mut AppData, _env| {
+                if data.capture.recording {
+                    ctx.submit_command(STOP_CAPTURE);
+                    return;
+                }
+                let save_dialog_options = FileDialogOptions::new()
+                    .allowed_types(vec![capture_spec()])
+                    .default_type(capture_spec())
+                    .default_name(String::from("capture.stcap"))
+                    .title("Choose where to record the capture")
+                    .button_text("Record")
+                    .accept_command(START_CAPTURE);
+
+                ctx.submit_command(Command::new(
+                    commands::SHOW_SAVE_PANEL,
+                    save_dialog_options,
+                    Target::Auto,
+                ))
+            })
+            .fix_width(150.0),
+        )
+        .with_spacer(6.)
+        .with_child(Label::new(LocalizedString::new("Replay speed:")))
+        .with_spacer(3.)
+        .with_child(
+            RadioGroup::new(vec![
+                (LocalizedString::new("Original"), ReplaySpeed::Original),
+                (LocalizedString::new("x10"), ReplaySpeed::Times10),
+                (LocalizedString::new("x100"), ReplaySpeed::Times100),
+                (LocalizedString::new("Max"), ReplaySpeed::Max),
+            ])
+            .fix_width(150.0)
+            .border(Color::grey(0.6), 2.0)
+            .rounded(5.0)
+            .lens(CaptureData::speed)
+            .disabled_if(|data: &CaptureData, _env| data.replaying)
+            .lens(AppData::capture),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Button::dynamic(|data: &AppData, _env| {
+                if data.capture.replaying {
+                    "Stop replay".to_string()
+                } else {
+                    "Replay capture".to_string()
+                }
+            })
+            .on_click(move |ctx, data: &mut AppData, _env| {
+                if data.capture.replaying {
+                    ctx.submit_command(STOP_REPLAY);
+                    return;
+                }
+                let open_dialog_options = FileDialogOptions::new()
+                    .allowed_types(vec![capture_spec()])
+                    .title("Choose a capture to replay")
+                    .button_text("Replay")
+                    .accept_command(REPLAY_CAPTURE);
+
+                ctx.submit_command(Command::new(
+                    commands::SHOW_OPEN_PANEL,
+                    open_dialog_options,
+                    Target::Auto,
+                ))
+            })
+            .fix_width(150.0),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Button::new(LocalizedString::new("Export to pcapng"))
+                .on_click(move |ctx, _data, _env| {
+                    let open_dialog_options = FileDialogOptions::new()
+                        .allowed_types(vec![capture_spec()])
+                        .title("Choose a capture to export to pcapng")
+                        .button_text("Export")
+                        .accept_command(EXPORT_PCAPNG);
+
+                    ctx.submit_command(Command::new(
+                        commands::SHOW_OPEN_PANEL,
+                        open_dialog_options,
+                        Target::Auto,
+                    ))
+                })
+                .fix_width(150.0),
+        )
+        .with_flex_spacer(1.0)
+        .padding(6.)
+        .fix_width(180.)
+        .background(Color::rgb8(0x1a, 0x1a, 0x1a))
+}
+
+/// Plot of the numbers in the received lines, zoomed with the buttons or the mouse wheel.
+fn make_plot_panel() -> impl Widget<AppData> {
+    let options = Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            RadioGroup::new(vec![
+                (LocalizedString::new("CSV"), PlotFormat::Csv),
+                (LocalizedString::new("key=value"), PlotFormat::KeyValue),
+                (LocalizedString::new("Regex"), PlotFormat::Regex),
+                (LocalizedString::new("Frame fields"), PlotFormat::Fields),
+            ])
+            .fix_width(110.0)
+            .border(Color::grey(0.6), 2.0)
+            .rounded(5.0)
+            .lens(PlotData::format),
+        )
+        .with_spacer(6.)
+        .with_flex_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(Either::new(
+                    |data: &PlotData, _env| data.format == PlotFormat::Fields,
+                    Flex::column()
+                        .cross_axis_alignment(CrossAxisAlignment::Start)
+                        .with_child(Label::new(LocalizedString::new(
+                            "Fields (name type offset [little]), the layout ones if empty:",
+                        )))
+                        .with_spacer(3.)
+                        .with_child(
+                            TextBox::multiline()
+                                .with_font(FontDescriptor::new(FontFamily::MONOSPACE))
+                                .expand_width()
+                                .fix_height(80.)
+                                .lens(PlotData::fields),
+                        ),
+                    Flex::column()
+                        .cross_axis_alignment(CrossAxisAlignment::Start)
+                        .with_child(Label::new(LocalizedString::new(
+                            "Regex with capture groups:",
+                        )))
+                        .with_spacer(3.)
+                        .with_child(
+                            TextBox::new()
+                                .with_font(FontDescriptor::new(FontFamily::MONOSPACE))
+                                .expand_width()
+                                .lens(PlotData::regex)
+                                .disabled_if(|data: &PlotData, _env| {
+                                    data.format != PlotFormat::Regex
+                                }),
+                        ),
+                ))
+                .with_spacer(6.)
+                .with_child(Checkbox::new(LocalizedString::new("Pause")).lens(PlotData::paused)),
+            1.0,
+        );
+
+    let buttons = Flex::row()
+        .with_child(
+            Button::new(LocalizedString::new("Zoom in"))
+                .on_click(|_ctx, data: &mut PlotData, _env| data.window = zoom(data.window, true)),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Button::new(LocalizedString::new("Zoom out"))
+                .on_click(|_ctx, data: &mut PlotData, _env| data.window = zoom(data.window, false)),
+        )
+        .with_spacer(6.)
+        .with_child(Label::new(|data: &PlotData, _env: &_| {
+            format!("{} s", data.window.round())
+        }))
+        .with_flex_spacer(1.0)
+        .with_child(
+            Button::new(LocalizedString::new("Clear"))
+                .on_click(|_ctx, data: &mut PlotData, _env| data.channels = Arc::new(Vec::new())),
+        )
+        .with_spacer(6.)
+        .with_child(Button::new(LocalizedString::new("Export CSV")).on_click(
+            |ctx, _data, _env| {
+                let save_dialog_options = FileDialogOptions::new()
+                    .allowed_types(vec![FileSpec::new("CSV", &["csv"])])
+                    .default_type(FileSpec::new("CSV", &["csv"]))
+                    .default_name(String::from("plot.csv"))
+                    .title("Choose where to export the plot")
+                    .button_text("Export")
+                    .accept_command(EXPORT_PLOT);
+
+                ctx.submit_command(Command::new(
+                    commands::SHOW_SAVE_PANEL,
+                    save_dialog_options,
+                    Target::Auto,
+                ))
+            },
+        ));
+
+    Flex::column()
+        .with_child(options)
+        .with_spacer(6.)
+        .with_child(buttons)
+        .with_spacer(6.)
+        .with_flex_child(Plot, 1.0)
+        .padding(6.)
+        .fix_width(480.)
+        .background(Color::rgb8(0x1a, 0x1a, 0x1a))
+        .lens(AppData::plot)
+}
+
+/// A light on when the modem status line given by `line` is active.
+fn make_led(name: &'static str, line: fn(&ModemStatus) -> bool) -> impl Widget<ModemStatus> {
+    let light = Painter::new(move |ctx, status: &ModemStatus, _env| {
+        let bounds = ctx.size().to_rect();
+        let color = if line(status) {
+            Color::rgb8(25, 200, 35)
+        } else {
+            Color::grey(0.25)
+        };
+        ctx.fill(Circle::new(bounds.center(), bounds.width() / 2.), &color);
+    });
+    Flex::row()
+        .with_child(light.fix_size(10.0, 10.0))
+        .with_spacer(3.)
+        .with_child(Label::new(name).with_text_size(14.0))
+        .padding((0., 0., 8., 0.))
+}
+
+/// Toggles of the output control lines and lights of the input ones.
+fn make_modem_lines() -> impl Widget<AppData> {
+    let toggle = |name: &'static str, level: fn(&AppData) -> bool| {
+        Button::dynamic(move |data: &AppData, _env| {
+            format!("{} {}", name, if level(data) { "on" } else { "off" })
+        })
+    };
+
+    Flex::row()
+        .with_child(toggle("RTS", |data| data.rts).on_click(|ctx, _data, _env| {
+            ctx.submit_command(TOGGLE_RTS);
+        }))
+        .with_spacer(3.)
+        .with_child(toggle("DTR", |data| data.dtr).on_click(|ctx, _data, _env| {
+            ctx.submit_command(TOGGLE_DTR);
+        }))
+        .with_spacer(8.)
+        .with_child(
+            Flex::row()
+                .with_child(make_led("CTS", |status| status.cts))
+                .with_child(make_led("DSR", |status| status.dsr))
+                .with_child(make_led("RI", |status| status.ri))
+                .with_child(make_led("CD", |status| status.cd))
+                .lens(AppData::modem_status),
+        )
+}
+
+/// Frame counter of the stats panel, none without framing as the bytes come in chunks.
+fn frames(stats: &Stats, count: u64) -> String {
+    if stats.framed {
+        count.to_string()
+    } else {
+        "-".to_string()
+    }
+}
+
+/// Traffic counters of the open port.
+fn make_stats_panel() -> impl Widget<AppData> {
+    let row = |name: &'static str, value: fn(&Stats) -> String| {
+        Flex::row()
+            .with_child(Label::new(name).fix_width(160.0))
+            .with_child(Label::new(move |stats: &Stats, _env: &_| value(stats)))
+    };
+    let duration = |stats: &Stats| {
+        let seconds = stats.duration as u64;
+        format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    };
+
+    let received = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(row("Bytes received", |stats| stats.bytes_in.to_string()))
+        .with_child(row("Frames received", |stats| {
+            frames(stats, stats.frames_in)
+        }))
+        .with_child(row("Throughput in", |stats| {
+            format!(
+                "{:.0} B/s ({:.1} %)",
+                stats.throughput_in,
+                stats.load(stats.throughput_in)
+            )
+        }))
+        .with_child(row("Peak in", |stats| {
+            format!(
+                "{:.0} B/s ({:.1} %)",
+                stats.peak_in,
+                stats.load(stats.peak_in)
+            )
+        }));
+    let sent = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(row("Bytes sent", |stats| stats.bytes_out.to_string()))
+        .with_child(row("Frames sent", |stats| frames(stats, stats.frames_out)))
+        .with_child(row("Throughput out", |stats| {
+            format!(
+                "{:.0} B/s ({:.1} %)",
+                stats.throughput_out,
+                stats.load(stats.throughput_out)
+            )
+        }))
+        .with_child(row("Peak out", |stats| {
+            format!(
+                "{:.0} B/s ({:.1} %)",
+                stats.peak_out,
+                stats.load(stats.peak_out)
+            )
+        }));
+    let session = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(row("Read errors", |stats| stats.read_errors.to_string()))
+        .with_child(row("Reconnects", |stats| stats.reconnects.to_string()))
+        .with_child(row("Session duration", duration))
+        .with_child(row("Line capacity", |stats| {
+            format!("{:.0} B/s", stats.capacity)
+        }));
+
+    Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(received)
+        .with_spacer(20.)
+        .with_child(sent)
+        .with_spacer(20.)
+        .with_child(session)
+        .with_flex_spacer(1.0)
+        .padding(6.)
+        .background(Color::rgb8(0x1a, 0x1a, 0x1a))
+        .lens(AppData::stats)
+}
+
+/// Write form of the text protocols, the line ending is appended to the text.
+fn make_text_form() -> impl Widget<AppData> {
+    Flex::row()
+        .with_flex_child(
+            TextBox::multiline()
+                .expand_width()
+                .lens(ToWriteLens)
+                .controller(TextBoxController::default()),
+            1.0,
         )
+        .with_spacer(6.)
+        .with_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(Label::new(LocalizedString::new("Line ending:")))
+                .with_spacer(3.)
+                .with_child(
+                    RadioGroup::new(vec![
+                        (LocalizedString::new("None"), LineEnding::None),
+                        (LocalizedString::new("LF"), LineEnding::Lf),
+                        (LocalizedString::new("CR"), LineEnding::Cr),
+                        (LocalizedString::new("CR LF"), LineEnding::CrLf),
+                    ])
+                    .fix_width(110.0)
+                    .border(Color::grey(0.6), 2.0)
+                    .rounded(5.0)
+                    .lens(AppData::line_ending),
+                ),
+        )
+        .with_spacer(6.)
+        .with_child(make_send_button())
+        .with_child(SizedBox::empty().width(6.))
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+}
+
+fn make_write_form() -> impl Widget<AppData> {
+    Flex::row()
+        .with_flex_child(
+            TextBox::multiline()
+                .expand_width()
+                .lens(ToWriteLens)
+                .controller(TextBoxController::default()),
+            1.0,
+        )
+        .with_spacer(6.)
+        .with_child(make_send_button())
+        .with_child(SizedBox::empty().width(6.))
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+}
+
+/// The GUI, `open_on_start` opens the port once it is shown.
+pub fn make_ui(open_on_start: bool) -> impl Widget<AppData> {
+    let write_panel = Flex::column()
+        .with_child(SizedBox::empty().height(8.))
+        .with_child(ViewSwitcher::new(
+            |data: &AppData, _env| data.protocol,
+            |protocol, _data, _env| -> Box<dyn Widget<AppData>> {
+                match protocol {
+                    Protocol::Modbus => Box::new(make_modbus_form()),
+                    Protocol::ModbusSlave => Box::new(make_modbus_slave_form()),
+                    Protocol::Raw => Box::new(make_raw_form()),
+                    Protocol::Slcan => Box::new(make_slcan_form()),
+                    Protocol::Text | Protocol::Nmea => Box::new(make_text_form()),
+                    Protocol::Mavlink | Protocol::At | Protocol::Scpi => {
+                        Box::new(make_write_form())
+                    }
+                }
+            },
+        ))
         .with_child(SizedBox::empty().height(8.))
         .background(Color::rgb8(0x1a, 0x1a, 0x1a));
 
@@ -123,6 +1270,13 @@ pub fn make_ui() -> impl Widget<AppData> {
             RadioGroup::new(vec![
                 (LocalizedString::new("Text"), Protocol::Text),
                 (LocalizedString::new("Raw"), Protocol::Raw),
+                (LocalizedString::new("Modbus RTU"), Protocol::Modbus),
+                (LocalizedString::new("Modbus slave"), Protocol::ModbusSlave),
+                (LocalizedString::new("NMEA 0183"), Protocol::Nmea),
+                (LocalizedString::new("MAVLink"), Protocol::Mavlink),
+                (LocalizedString::new("SLCAN"), Protocol::Slcan),
+                (LocalizedString::new("AT commands"), Protocol::At),
+                (LocalizedString::new("SCPI"), Protocol::Scpi),
             ])
             .fix_width(110.0)
             .border(Color::grey(0.6), 2.0)
@@ -145,28 +1299,149 @@ pub fn make_ui() -> impl Widget<AppData> {
                 })
                 .fix_width(110.0),
         )
-        .with_flex_spacer(1.0)
+        .with_spacer(6.)
+        .with_child(
+            Button::new(LocalizedString::new("Apply settings"))
+                .on_click(|ctx, _data, _env| {
+                    ctx.submit_command(RECONFIGURE_PORT);
+                })
+                .fix_width(110.0),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Checkbox::new(LocalizedString::new("Script"))
+                .lens(AppData::script.then(ScriptData::show)),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Checkbox::new(LocalizedString::new("Profiles"))
+                .lens(AppData::profiles.then(ProfileData::show)),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Checkbox::new(LocalizedString::new("Session log"))
+                .lens(AppData::session_log.then(LogData::show)),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Checkbox::new(LocalizedString::new("Capture"))
+                .lens(AppData::capture.then(CaptureData::show)),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Checkbox::new(LocalizedString::new("Plot")).lens(AppData::plot.then(PlotData::show)),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Button::new(LocalizedString::new("Send file"))
+                .on_click(|ctx, _data, _env| {
+                    let open_dialog_options = FileDialogOptions::new()
+                        .title("Choose a file to send with ZMODEM")
+                        .button_text("Send");
+
+                    ctx.submit_command(Command::new(
+                        commands::SHOW_OPEN_PANEL,
+                        open_dialog_options,
+                        Target::Auto,
+                    ))
+                })
+                .fix_width(110.0),
+        )
+        .with_spacer(6.)
+        .with_child(
+            Flex::row()
+                .with_child(
+                    Button::new(LocalizedString::new("Break"))
+                        .on_click(|ctx, _data, _env| {
+                            ctx.submit_command(SEND_BREAK);
+                        })
+                        .fix_width(60.0),
+                )
+                .with_spacer(4.)
+                .with_child(
+                    TextBox::new()
+                        .with_formatter(NumericFormatter)
+                        .fix_width(46.0)
+                        .lens(AppData::break_duration)
+                        .controller(TextBoxController::default()),
+                )
+                .with_child(Label::new("ms")),
+        )
+        .with_spacer(6.);
+    // Scrolled for the window to fit on small screens
+    let control_panel = Scroll::new(control_panel)
+        .vertical()
+        .expand_height()
         .background(Color::rgb8(0x1a, 0x1a, 0x1a))
         .fix_width(150.0);
 
     Flex::column()
-        .with_child(EventHandler::new().fix_width(0.0).fix_height(0.0))
+        .with_child(
+            EventHandler::new(open_on_start)
+                .fix_width(0.0)
+                .fix_height(0.0),
+        )
         .with_flex_child(
             Flex::row().with_child(control_panel).with_flex_child(
                 Flex::column()
                     .with_flex_child(
-                        Scroll::new(
-                            RawLabel::new()
-                                .with_font(
-                                    FontDescriptor::new(FontFamily::MONOSPACE).with_size(18.),
+                        Flex::row()
+                            .with_flex_child(
+                                Either::new(
+                                    |data: &AppData, _env| data.protocol == Protocol::At,
+                                    make_at_transcript(),
+                                    Scroll::new(
+                                        RawLabel::new()
+                                            .with_font(
+                                                FontDescriptor::new(FontFamily::MONOSPACE)
+                                                    .with_size(18.),
+                                            )
+                                            .with_line_break_mode(LineBreaking::WordWrap)
+                                            .lens(AppData::output)
+                                            .expand_width(),
+                                    )
+                                    .vertical()
+                                    .expand(),
                                 )
-                                .with_line_break_mode(LineBreaking::WordWrap)
-                                .lens(AppData::output)
-                                .expand_width(),
-                        )
-                        .vertical()
-                        .expand()
-                        .controller(ContextMenuController::default()),
+                                .controller(ContextMenuController::default()),
+                                1.0,
+                            )
+                            .with_child(ViewSwitcher::new(
+                                |data: &AppData, _env| data.protocol,
+                                |protocol, _data, _env| -> Box<dyn Widget<AppData>> {
+                                    match protocol {
+                                        Protocol::Nmea => Box::new(make_nmea_table()),
+                                        Protocol::Slcan => Box::new(make_can_table()),
+                                        Protocol::Scpi => Box::new(make_scpi_panel()),
+                                        _ => Box::new(SizedBox::empty()),
+                                    }
+                                },
+                            ))
+                            .with_child(Either::new(
+                                |data: &AppData, _env| data.script.show,
+                                make_script_panel(),
+                                SizedBox::empty(),
+                            ))
+                            .with_child(Either::new(
+                                |data: &AppData, _env| data.profiles.show,
+                                make_profile_panel(),
+                                SizedBox::empty(),
+                            ))
+                            .with_child(Either::new(
+                                |data: &AppData, _env| data.session_log.show,
+                                make_session_log_panel(),
+                                SizedBox::empty(),
+                            ))
+                            .with_child(Either::new(
+                                |data: &AppData, _env| data.capture.show,
+                                make_capture_panel(),
+                                SizedBox::empty(),
+                            ))
+                            .with_child(Either::new(
+                                |data: &AppData, _env| data.plot.show,
+                                make_plot_panel(),
+                                SizedBox::empty(),
+                            )),
                         1.0,
                     )
                     .with_child(write_panel),
@@ -174,11 +1449,45 @@ pub fn make_ui() -> impl Widget<AppData> {
             ),
             1.0,
         )
+        .with_child(Either::new(
+            |data: &AppData, _env| data.show_stats,
+            make_stats_panel(),
+            SizedBox::empty(),
+        ))
         .with_child(
-            Label::new(|item: &String, _env: &_| item.to_string())
-                .with_text_size(14.0)
-                .fix_height(18.0)
-                .padding((0., 0., 0., 2.))
-                .lens(AppData::status),
+            Flex::row()
+                .with_flex_child(
+                    Label::new(|item: &String, _env: &_| item.to_string())
+                        .with_text_size(14.0)
+                        .expand_width()
+                        .lens(AppData::status),
+                    1.0,
+                )
+                .with_child(
+                    Label::new(|settings: &String, _env: &_| settings.to_string())
+                        .with_text_size(14.0)
+                        .lens(AppData::active_settings),
+                )
+                .with_spacer(8.)
+                .with_child(make_modem_lines())
+                .with_child(
+                    Label::new(|stats: &Stats, _env: &_| stats.summary())
+                        .with_text_size(14.0)
+                        .lens(AppData::stats),
+                )
+                .with_spacer(6.)
+                .with_child(
+                    Button::dynamic(|show: &bool, _env| {
+                        if *show {
+                            "Hide statistics".to_string()
+                        } else {
+                            "Statistics".to_string()
+                        }
+                    })
+                    .on_click(|_ctx, show: &mut bool, _env| *show = !*show)
+                    .lens(AppData::show_stats),
+                )
+                .fix_height(24.0)
+                .padding((0., 0., 0., 2.)),
         )
 }
@@ -38,7 +38,12 @@ fn make_context_menu<T: Data>() -> Menu<T> {
         .entry(
             MenuItem::new(LocalizedString::new("Export")).on_activate(|ctx, _data, _env| {
                 let save_dialog_options = FileDialogOptions::new()
-                    .allowed_types(vec![FileSpec::new("Text file", &["txt"])])
+                    .allowed_types(vec![
+                        FileSpec::new("Text file", &["txt"]),
+                        FileSpec::new("CSV", &["csv"]),
+                        FileSpec::new("JSON Lines", &["jsonl"]),
+                        FileSpec::new("HTML", &["html"]),
+                    ])
                     .default_type(FileSpec::new("Text file", &["txt"]))
                     .default_name(String::from("MyFile.txt"))
                     .name_label("Target")
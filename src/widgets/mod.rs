@@ -2,6 +2,8 @@
 
 mod controllers;
 mod formatters;
+mod plot;
 
 pub use controllers::{ContextMenuController, PortTextBoxController, TextBoxController};
 pub use formatters::NumericFormatter;
+pub use plot::{zoom, Plot};
@@ -0,0 +1,188 @@
+//! A widget drawing the channels of the plotter as scrolling time series.
+
+use crate::data::PlotData;
+use druid::kurbo::{BezPath, Line, Rect};
+use druid::piet::{FontFamily, Text, TextLayout, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::{Color, Point};
+
+/// Colors of the channels, in their order of appearance.
+const COLORS: [(u8, u8, u8); 6] = [
+    (50, 190, 220),
+    (240, 160, 25),
+    (25, 155, 35),
+    (230, 40, 40),
+    (128, 0, 255),
+    (220, 220, 60),
+];
+
+/// Shortest and longest time windows in seconds.
+const MIN_WINDOW: f64 = 1.;
+const MAX_WINDOW: f64 = 3600.;
+
+/// Space left for the labels of the axes.
+const MARGIN_LEFT: f64 = 60.;
+const MARGIN_BOTTOM: f64 = 20.;
+
+fn channel_color(index: usize) -> Color {
+    let (red, green, blue) = COLORS[index % COLORS.len()];
+    Color::rgb8(red, green, blue)
+}
+
+/// Zoom the time window in or out with the mouse wheel.
+pub fn zoom(window: f64, zoom_in: bool) -> f64 {
+    let window = if zoom_in { window / 1.5 } else { window * 1.5 };
+    window.max(MIN_WINDOW).min(MAX_WINDOW)
+}
+
+pub struct Plot;
+
+impl Plot {
+    fn draw_label(ctx: &mut PaintCtx, text: String, color: Color, position: Point) -> f64 {
+        let layout = ctx
+            .text()
+            .new_text_layout(text)
+            .font(FontFamily::SYSTEM_UI, 11.)
+            .text_color(color)
+            .build();
+        match layout {
+            Ok(layout) => {
+                let width = layout.size().width;
+                ctx.draw_text(&layout, position);
+                width
+            }
+            Err(_) => 0.,
+        }
+    }
+}
+
+impl Widget<PlotData> for Plot {
+    fn event(&mut self, _ctx: &mut EventCtx, event: &Event, data: &mut PlotData, _env: &Env) {
+        if let Event::Wheel(wheel) = event {
+            data.window = zoom(data.window, wheel.wheel_delta.y < 0.);
+        }
+    }
+
+    fn lifecycle(&mut self, _: &mut LifeCycleCtx, _: &LifeCycle, _: &PlotData, _: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &PlotData, data: &PlotData, _: &Env) {
+        if !old_data.same(data) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &PlotData, _: &Env) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &PlotData, _env: &Env) {
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &Color::rgb8(0x10, 0x10, 0x10));
+        let area = Rect::new(
+            MARGIN_LEFT,
+            4.,
+            size.width - 4.,
+            size.height - MARGIN_BOTTOM,
+        );
+        if area.width() <= 0. || area.height() <= 0. {
+            return;
+        }
+        ctx.stroke(area, &Color::grey(0.4), 1.);
+
+        // The window ends at the last sample
+        let end = data
+            .channels
+            .iter()
+            .filter_map(|channel| channel.points.back().map(|point| point.0))
+            .fold(data.window, f64::max);
+        let start = end - data.window;
+
+        let visible = data.channels.iter().flat_map(|channel| {
+            channel
+                .points
+                .iter()
+                .filter(move |point| point.0 >= start)
+                .map(|point| point.1)
+        });
+        let (mut min, mut max) = visible
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |range, value| {
+                (range.0.min(value), range.1.max(value))
+            });
+        if !min.is_finite() {
+            min = 0.;
+            max = 1.;
+        } else if max - min < f64::EPSILON {
+            min -= 1.;
+            max += 1.;
+        }
+
+        let x = |time: f64| area.x0 + (time - start) / data.window * area.width();
+        let y = |value: f64| area.y1 - (value - min) / (max - min) * area.height();
+
+        // Horizontal grid with the values
+        for step in 0..=4 {
+            let value = min + (max - min) * step as f64 / 4.;
+            let height = y(value);
+            ctx.stroke(
+                Line::new((area.x0, height), (area.x1, height)),
+                &Color::grey(0.2),
+                1.,
+            );
+            Plot::draw_label(
+                ctx,
+                format!("{:.3}", value),
+                Color::grey(0.7),
+                Point::new(2., height - 7.),
+            );
+        }
+        Plot::draw_label(
+            ctx,
+            format!("{:.1} s", start.max(0.)),
+            Color::grey(0.7),
+            Point::new(area.x0, area.y1 + 3.),
+        );
+        Plot::draw_label(
+            ctx,
+            format!("{:.1} s", end),
+            Color::grey(0.7),
+            Point::new(area.x1 - 50., area.y1 + 3.),
+        );
+
+        ctx.with_save(|ctx| {
+            ctx.clip(area);
+            for (index, channel) in data.channels.iter().enumerate() {
+                let mut path = BezPath::new();
+                // Start from the last point before the window to draw the line entering it
+                let first = channel
+                    .points
+                    .iter()
+                    .rposition(|point| point.0 < start)
+                    .unwrap_or(0);
+                for (n, &(time, value)) in channel.points.iter().skip(first).enumerate() {
+                    if n == 0 {
+                        path.move_to((x(time), y(value)));
+                    } else {
+                        path.line_to((x(time), y(value)));
+                    }
+                }
+                ctx.stroke(path, &channel_color(index), 1.5);
+            }
+        });
+
+        // Legend with the last value of each channel
+        let mut legend_x = area.x0 + 6.;
+        for (index, channel) in data.channels.iter().enumerate() {
+            let last = channel
+                .points
+                .back()
+                .map_or_else(String::new, |point| format!(" {}", point.1));
+            let width = Plot::draw_label(
+                ctx,
+                format!("{}{}", channel.name, last),
+                channel_color(index),
+                Point::new(legend_x, area.y0 + 4.),
+            );
+            legend_x += width + 12.;
+        }
+    }
+}
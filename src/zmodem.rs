@@ -0,0 +1,974 @@
+//! ZMODEM file transfer over an already framed serial stream.
+//!
+//! Only what is needed to exchange a single file with `sz`/`rz` is implemented: hex and
+//! binary (CRC-16 and CRC-32) headers, escaped data subpackets and resuming a transfer
+//! from the offset requested by the receiver.
+//!
+//! A file being received is written as `<name>.part` and renamed once complete, so only
+//! those are resumed and the files already present are never written.
+
+use crate::checksum::Algorithm;
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{FutureExt, Sink, SinkExt, Stream, StreamExt};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::time::timeout;
+
+/// Sequence sent by `sz` to start the remote receiver: `ZPAD ZPAD ZDLE ZHEX` followed by
+/// the ZRQINIT frame type.
+pub const AUTO_START: &[u8] = b"**\x18B00";
+
+/// Eight CAN followed by eight backspaces, aborts the transfer on the remote side.
+pub const ABORT_SEQUENCE: &[u8] =
+    b"\x18\x18\x18\x18\x18\x18\x18\x18\x08\x08\x08\x08\x08\x08\x08\x08";
+
+const ZPAD: u8 = b'*';
+const ZDLE: u8 = 0x18;
+const ZBIN: u8 = b'A';
+const ZHEX: u8 = b'B';
+const ZBIN32: u8 = b'C';
+
+const ZRQINIT: u8 = 0;
+const ZRINIT: u8 = 1;
+const ZSINIT: u8 = 2;
+const ZACK: u8 = 3;
+const ZFILE: u8 = 4;
+const ZSKIP: u8 = 5;
+const ZNAK: u8 = 6;
+const ZABORT: u8 = 7;
+const ZFIN: u8 = 8;
+const ZRPOS: u8 = 9;
+const ZDATA: u8 = 10;
+const ZEOF: u8 = 11;
+const ZFERR: u8 = 12;
+const ZCRC: u8 = 13;
+const ZCHALLENGE: u8 = 14;
+
+const ZCRCE: u8 = b'h';
+const ZCRCG: u8 = b'i';
+const ZCRCQ: u8 = b'j';
+const ZCRCW: u8 = b'k';
+const ZRUB0: u8 = b'l';
+const ZRUB1: u8 = b'm';
+
+const CANFDX: u8 = 0x01;
+const CANOVIO: u8 = 0x02;
+const CANFC32: u8 = 0x20;
+const ZCRESUM: u8 = 3;
+
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+
+const SUBPACKET_SIZE: usize = 1024;
+const MAX_SUBPACKET_SIZE: usize = 8192;
+const MAX_GARBAGE: usize = 4096;
+const MAX_RETRIES: usize = 10;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Suffix of the files being received.
+const PARTIAL_SUFFIX: &str = ".part";
+
+fn crc16(data: &[u8]) -> u16 {
+    Algorithm::Crc16Xmodem.compute(data) as u16
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    Algorithm::Crc32.compute(data)
+}
+
+/// Look for the [`AUTO_START`] sequence in the received data, even split between two reads.
+#[derive(Debug, Default)]
+pub struct AutoStart {
+    /// End of the previous data, too short to hold the whole sequence.
+    tail: Vec<u8>,
+}
+
+impl AutoStart {
+    /// Return the number of bytes of `data` before the sequence, which may start in the
+    /// previous data, and the position of the first byte after it.
+    pub fn find(&mut self, data: &[u8]) -> Option<(usize, usize)> {
+        let kept = self.tail.len();
+        self.tail.extend_from_slice(data);
+        match self
+            .tail
+            .windows(AUTO_START.len())
+            .position(|window| window == AUTO_START)
+        {
+            Some(start) => {
+                self.tail.clear();
+                Some((start.saturating_sub(kept), start + AUTO_START.len() - kept))
+            }
+            None => {
+                let end = self.tail.len().saturating_sub(AUTO_START.len() - 1);
+                self.tail.drain(..end);
+                None
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tail.clear();
+    }
+}
+
+/// `name` in `directory`, numbered when a file with this name already exists.
+fn free_path(directory: &Path, name: &str) -> PathBuf {
+    let path = directory.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    };
+    let mut number = 1;
+    loop {
+        let path = directory.join(format!("{}-{}{}", stem, number, extension));
+        if !path.exists() {
+            return path;
+        }
+        number += 1;
+    }
+}
+
+/// Modification time in seconds since the epoch, as given in the ZFILE header.
+fn modified(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+fn protocol_error(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Hex,
+    Bin16,
+    Bin32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    kind: u8,
+    data: [u8; 4],
+    encoding: Encoding,
+}
+
+impl Header {
+    fn new(kind: u8, data: [u8; 4]) -> Self {
+        Header {
+            kind,
+            data,
+            encoding: Encoding::Hex,
+        }
+    }
+
+    fn with_position(kind: u8, position: u64) -> Self {
+        Header::new(kind, (position as u32).to_le_bytes())
+    }
+
+    fn with_flags(kind: u8, zf0: u8) -> Self {
+        Header::new(kind, [0, 0, 0, zf0])
+    }
+
+    fn position(&self) -> u64 {
+        u32::from_le_bytes(self.data) as u64
+    }
+
+    fn zf0(&self) -> u8 {
+        self.data[3]
+    }
+}
+
+enum Escaped {
+    Byte(u8),
+    FrameEnd(u8),
+}
+
+fn escape_into(out: &mut Vec<u8>, data: &[u8]) {
+    for &byte in data {
+        match byte {
+            ZDLE | 0x10 | 0x90 | XON | 0x91 | XOFF | 0x93 | 0x0d | 0x8d => {
+                out.push(ZDLE);
+                out.push(byte ^ 0x40);
+            }
+            _ => out.push(byte),
+        }
+    }
+}
+
+/// Progress of a running transfer: file name, bytes transferred and file size.
+pub type Progress<'a> = &'a mut dyn FnMut(&str, u64, u64);
+
+struct Session<'a, S, R> {
+    sink: &'a mut S,
+    stream: &'a mut R,
+    buffer: BytesMut,
+}
+
+impl<'a, S, R> Session<'a, S, R>
+where
+    S: Sink<Bytes, Error = io::Error> + Unpin,
+    R: Stream<Item = Result<BytesMut, io::Error>> + Unpin,
+{
+    /// Session starting with `received`, bytes already read from `stream`.
+    fn new(sink: &'a mut S, stream: &'a mut R, received: &[u8]) -> Self {
+        Session {
+            sink,
+            stream,
+            buffer: BytesMut::from(received),
+        }
+    }
+
+    async fn write(&mut self, data: Vec<u8>) -> io::Result<()> {
+        self.sink.send(data.into()).await
+    }
+
+    async fn read_byte(&mut self) -> io::Result<u8> {
+        while self.buffer.is_empty() {
+            match timeout(READ_TIMEOUT, self.stream.next()).await {
+                Ok(Some(Ok(data))) => self.buffer.extend_from_slice(&data),
+                Ok(Some(Err(e))) => return Err(e),
+                Ok(None) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Err(_) => return Err(io::ErrorKind::TimedOut.into()),
+            }
+        }
+        Ok(self.buffer.get_u8())
+    }
+
+    /// Pull whatever is already available on the stream without waiting.
+    fn poll_pending(&mut self) -> io::Result<()> {
+        while let Some(data) = self.stream.next().now_or_never() {
+            match data {
+                Some(Ok(data)) => self.buffer.extend_from_slice(&data),
+                Some(Err(e)) => return Err(e),
+                None => return Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        }
+        Ok(())
+    }
+
+    async fn read_escaped(&mut self) -> io::Result<Escaped> {
+        loop {
+            match self.read_byte().await? {
+                XON | XOFF | 0x91 | 0x93 => continue,
+                ZDLE => break,
+                byte => return Ok(Escaped::Byte(byte)),
+            }
+        }
+
+        let mut cancels = 1;
+        loop {
+            match self.read_byte().await? {
+                XON | XOFF | 0x91 | 0x93 => continue,
+                ZDLE => {
+                    cancels += 1;
+                    if cancels >= 5 {
+                        return Err(protocol_error("Transfer cancelled by remote"));
+                    }
+                }
+                end @ (ZCRCE | ZCRCG | ZCRCQ | ZCRCW) => return Ok(Escaped::FrameEnd(end)),
+                ZRUB0 => return Ok(Escaped::Byte(0x7f)),
+                ZRUB1 => return Ok(Escaped::Byte(0xff)),
+                byte if byte & 0x60 == 0x40 => return Ok(Escaped::Byte(byte ^ 0x40)),
+                _ => return Err(protocol_error("Bad escape sequence")),
+            }
+        }
+    }
+
+    async fn read_escaped_byte(&mut self) -> io::Result<u8> {
+        match self.read_escaped().await? {
+            Escaped::Byte(byte) => Ok(byte),
+            Escaped::FrameEnd(_) => Err(protocol_error("Unexpected end of frame")),
+        }
+    }
+
+    async fn read_hex_byte(&mut self) -> io::Result<u8> {
+        let high = self.read_byte().await? & 0x7f;
+        let low = self.read_byte().await? & 0x7f;
+        hex::decode([high, low])
+            .ok()
+            .and_then(|byte| byte.first().copied())
+            .ok_or_else(|| protocol_error("Bad hex header"))
+    }
+
+    async fn read_header(&mut self) -> io::Result<Header> {
+        let mut garbage = 0;
+        let encoding = loop {
+            if self.read_byte().await? != ZPAD {
+                garbage += 1;
+                if garbage > MAX_GARBAGE {
+                    return Err(protocol_error("No header found"));
+                }
+                continue;
+            }
+            let mut byte = self.read_byte().await?;
+            while byte == ZPAD {
+                byte = self.read_byte().await?;
+            }
+            if byte != ZDLE {
+                continue;
+            }
+            match self.read_byte().await? {
+                ZHEX => break Encoding::Hex,
+                ZBIN => break Encoding::Bin16,
+                ZBIN32 => break Encoding::Bin32,
+                _ => continue,
+            }
+        };
+
+        let mut frame = [0u8; 5];
+        match encoding {
+            Encoding::Hex => {
+                for byte in frame.iter_mut() {
+                    *byte = self.read_hex_byte().await?;
+                }
+                let crc =
+                    u16::from_be_bytes([self.read_hex_byte().await?, self.read_hex_byte().await?]);
+                if crc != crc16(&frame) {
+                    return Err(protocol_error("Bad header CRC"));
+                }
+                // Hex headers end with CR LF and, except for ZACK and ZFIN, an XON
+                if self.read_byte().await? & 0x7f == b'\r' {
+                    self.read_byte().await?;
+                }
+                if frame[0] != ZACK && frame[0] != ZFIN {
+                    self.poll_pending()?;
+                    if self.buffer.first() == Some(&XON) {
+                        self.buffer.advance(1);
+                    }
+                }
+            }
+            Encoding::Bin16 => {
+                for byte in frame.iter_mut() {
+                    *byte = self.read_escaped_byte().await?;
+                }
+                let crc = u16::from_be_bytes([
+                    self.read_escaped_byte().await?,
+                    self.read_escaped_byte().await?,
+                ]);
+                if crc != crc16(&frame) {
+                    return Err(protocol_error("Bad header CRC"));
+                }
+            }
+            Encoding::Bin32 => {
+                for byte in frame.iter_mut() {
+                    *byte = self.read_escaped_byte().await?;
+                }
+                let mut crc = [0u8; 4];
+                for byte in crc.iter_mut() {
+                    *byte = self.read_escaped_byte().await?;
+                }
+                if u32::from_le_bytes(crc) != crc32(&frame) {
+                    return Err(protocol_error("Bad header CRC"));
+                }
+            }
+        }
+
+        Ok(Header {
+            kind: frame[0],
+            data: [frame[1], frame[2], frame[3], frame[4]],
+            encoding,
+        })
+    }
+
+    /// Read a data subpacket, returning its content and the frame end marker.
+    async fn read_subpacket(&mut self, encoding: Encoding) -> io::Result<(Vec<u8>, u8)> {
+        let mut data = Vec::with_capacity(SUBPACKET_SIZE);
+        let end = loop {
+            match self.read_escaped().await? {
+                Escaped::Byte(byte) => {
+                    if data.len() >= MAX_SUBPACKET_SIZE {
+                        return Err(protocol_error("Subpacket too long"));
+                    }
+                    data.push(byte);
+                }
+                Escaped::FrameEnd(end) => break end,
+            }
+        };
+
+        data.push(end);
+        let valid = if encoding == Encoding::Bin32 {
+            let mut crc = [0u8; 4];
+            for byte in crc.iter_mut() {
+                *byte = self.read_escaped_byte().await?;
+            }
+            u32::from_le_bytes(crc) == crc32(&data)
+        } else {
+            let crc = u16::from_be_bytes([
+                self.read_escaped_byte().await?,
+                self.read_escaped_byte().await?,
+            ]);
+            crc == crc16(&data)
+        };
+        data.pop();
+
+        if valid {
+            Ok((data, end))
+        } else {
+            Err(protocol_error("Bad subpacket CRC"))
+        }
+    }
+
+    async fn write_hex_header(&mut self, header: Header) -> io::Result<()> {
+        let mut frame = vec![header.kind];
+        frame.extend_from_slice(&header.data);
+        let crc = crc16(&frame);
+
+        let mut out = vec![ZPAD, ZPAD, ZDLE, ZHEX];
+        for byte in frame.iter().chain(crc.to_be_bytes().iter()) {
+            out.extend_from_slice(format!("{:02x}", byte).as_bytes());
+        }
+        out.extend_from_slice(b"\r\x8a");
+        if header.kind != ZACK && header.kind != ZFIN {
+            out.push(XON);
+        }
+        self.write(out).await
+    }
+
+    async fn write_bin_header(&mut self, header: Header, crc32_mode: bool) -> io::Result<()> {
+        let mut frame = vec![header.kind];
+        frame.extend_from_slice(&header.data);
+
+        let mut out = vec![ZPAD, ZDLE];
+        if crc32_mode {
+            out.push(ZBIN32);
+            escape_into(&mut out, &frame);
+            escape_into(&mut out, &crc32(&frame).to_le_bytes());
+        } else {
+            out.push(ZBIN);
+            escape_into(&mut out, &frame);
+            escape_into(&mut out, &crc16(&frame).to_be_bytes());
+        }
+        self.write(out).await
+    }
+
+    async fn write_subpacket(&mut self, data: &[u8], end: u8, crc32_mode: bool) -> io::Result<()> {
+        let mut out = Vec::with_capacity(data.len() * 2 + 12);
+        escape_into(&mut out, data);
+        out.push(ZDLE);
+        out.push(end);
+
+        let mut crc_data = data.to_vec();
+        crc_data.push(end);
+        if crc32_mode {
+            escape_into(&mut out, &crc32(&crc_data).to_le_bytes());
+        } else {
+            escape_into(&mut out, &crc16(&crc_data).to_be_bytes());
+        }
+        if end == ZCRCW {
+            out.push(XON);
+        }
+        self.write(out).await
+    }
+
+    async fn send_data(
+        &mut self,
+        file: &mut File,
+        name: &str,
+        size: u64,
+        mut offset: u64,
+        crc32_mode: bool,
+        progress: Progress<'_>,
+    ) -> io::Result<()> {
+        let mut chunk = vec![0u8; SUBPACKET_SIZE];
+        let mut retries = 0;
+
+        'data: loop {
+            file.seek(SeekFrom::Start(offset))?;
+            self.write_bin_header(Header::with_position(ZDATA, offset), crc32_mode)
+                .await?;
+
+            loop {
+                let read = file.read(&mut chunk)?;
+                offset += read as u64;
+                let end = if read == 0 || offset >= size {
+                    ZCRCE
+                } else {
+                    ZCRCG
+                };
+                self.write_subpacket(&chunk[..read], end, crc32_mode)
+                    .await?;
+                progress(name, offset, size);
+
+                if end == ZCRCE {
+                    break;
+                }
+
+                // The receiver asks for a new position when it detects an error
+                self.poll_pending()?;
+                if self.buffer.contains(&ZPAD) {
+                    let header = self.read_header().await?;
+                    match header.kind {
+                        ZRPOS => {
+                            offset = header.position();
+                            continue 'data;
+                        }
+                        ZABORT | ZFERR | ZSKIP => {
+                            return Err(protocol_error("Transfer refused by remote"))
+                        }
+                        _ => (),
+                    }
+                }
+            }
+
+            loop {
+                self.write_bin_header(Header::with_position(ZEOF, offset), crc32_mode)
+                    .await?;
+                match self.read_header().await {
+                    Ok(header) if header.kind == ZRINIT => return Ok(()),
+                    Ok(header) if header.kind == ZRPOS => {
+                        offset = header.position();
+                        continue 'data;
+                    }
+                    Ok(header) if header.kind == ZABORT || header.kind == ZFERR => {
+                        return Err(protocol_error("Transfer refused by remote"))
+                    }
+                    Ok(_) => (),
+                    Err(e) if e.kind() != io::ErrorKind::TimedOut => return Err(e),
+                    Err(e) => {
+                        retries += 1;
+                        if retries > MAX_RETRIES {
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn send_file(&mut self, path: &Path, progress: Progress<'_>) -> io::Result<()> {
+        let mut file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let size = metadata.len();
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not a file"))?;
+
+        self.write(b"rz\r".to_vec()).await?;
+        let mut retries = 0;
+        let receiver_flags = loop {
+            self.write_hex_header(Header::with_flags(ZRQINIT, 0))
+                .await?;
+            match self.read_header().await {
+                Ok(header) if header.kind == ZRINIT => break header.zf0(),
+                Ok(header) if header.kind == ZCHALLENGE => {
+                    self.write_hex_header(Header::new(ZACK, header.data))
+                        .await?
+                }
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => (),
+                Err(e) if e.kind() != io::ErrorKind::TimedOut => return Err(e),
+                Err(e) => {
+                    retries += 1;
+                    if retries > MAX_RETRIES {
+                        return Err(e);
+                    }
+                }
+            }
+        };
+        let crc32_mode = receiver_flags & CANFC32 != 0;
+
+        let mut file_info = name.as_bytes().to_vec();
+        file_info.push(0);
+        file_info.extend_from_slice(format!("{} {:o} 100644 0 1 {}", size, mtime, size).as_bytes());
+        file_info.push(0);
+
+        let mut retries = 0;
+        let mut resend = true;
+        loop {
+            if resend {
+                self.write_bin_header(Header::with_flags(ZFILE, ZCRESUM), crc32_mode)
+                    .await?;
+                self.write_subpacket(&file_info, ZCRCW, crc32_mode).await?;
+            }
+            resend = true;
+
+            match self.read_header().await {
+                Ok(header) if header.kind == ZRPOS => {
+                    self.send_data(
+                        &mut file,
+                        &name,
+                        size,
+                        header.position(),
+                        crc32_mode,
+                        &mut *progress,
+                    )
+                    .await?;
+                    break;
+                }
+                Ok(header) if header.kind == ZSKIP => break,
+                Ok(header) if header.kind == ZCRC => {
+                    let mut content = Vec::with_capacity(size as usize);
+                    file.seek(SeekFrom::Start(0))?;
+                    file.read_to_end(&mut content)?;
+                    self.write_hex_header(Header::new(ZCRC, crc32(&content).to_le_bytes()))
+                        .await?;
+                    resend = false;
+                }
+                // Late answer to one of our ZRQINIT
+                Ok(header) if header.kind == ZRINIT => resend = false,
+                Ok(header) if header.kind == ZABORT || header.kind == ZFERR => {
+                    return Err(protocol_error("Transfer refused by remote"))
+                }
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => (),
+                Err(e) if e.kind() != io::ErrorKind::TimedOut => return Err(e),
+                Err(e) => {
+                    retries += 1;
+                    if retries > MAX_RETRIES {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        for _ in 0..MAX_RETRIES {
+            self.write_hex_header(Header::new(ZFIN, [0; 4])).await?;
+            match self.read_header().await {
+                Ok(header) if header.kind == ZFIN => break,
+                Err(e) if e.kind() != io::ErrorKind::TimedOut => return Err(e),
+                _ => (),
+            }
+        }
+        self.write(b"OO".to_vec()).await
+    }
+
+    async fn receive_data(
+        &mut self,
+        file: &mut File,
+        name: &str,
+        size: u64,
+        mut offset: u64,
+        progress: Progress<'_>,
+    ) -> io::Result<()> {
+        let mut retries = 0;
+        self.write_hex_header(Header::with_position(ZRPOS, offset))
+            .await?;
+
+        loop {
+            let header = match self.read_header().await {
+                Ok(header) => header,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(e),
+                Err(e) => {
+                    retries += 1;
+                    if retries > MAX_RETRIES {
+                        return Err(e);
+                    }
+                    self.write_hex_header(Header::with_position(ZRPOS, offset))
+                        .await?;
+                    continue;
+                }
+            };
+
+            match header.kind {
+                ZDATA if header.position() != offset => {
+                    self.write_hex_header(Header::with_position(ZRPOS, offset))
+                        .await?
+                }
+                ZDATA => loop {
+                    match self.read_subpacket(header.encoding).await {
+                        Ok((data, end)) => {
+                            file.write_all(&data)?;
+                            offset += data.len() as u64;
+                            progress(name, offset, size);
+                            retries = 0;
+
+                            match end {
+                                ZCRCW => {
+                                    self.write_hex_header(Header::with_position(ZACK, offset))
+                                        .await?;
+                                    break;
+                                }
+                                ZCRCQ => {
+                                    self.write_hex_header(Header::with_position(ZACK, offset))
+                                        .await?
+                                }
+                                ZCRCE => break,
+                                _ => (),
+                            }
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(e),
+                        Err(e) => {
+                            retries += 1;
+                            if retries > MAX_RETRIES {
+                                return Err(e);
+                            }
+                            self.write_hex_header(Header::with_position(ZRPOS, offset))
+                                .await?;
+                            break;
+                        }
+                    }
+                },
+                ZEOF if header.position() == offset => {
+                    file.flush()?;
+                    return Ok(());
+                }
+                ZFILE => {
+                    let _ = self.read_subpacket(header.encoding).await;
+                    self.write_hex_header(Header::with_position(ZRPOS, offset))
+                        .await?
+                }
+                ZFIN | ZABORT => return Err(protocol_error("Transfer aborted by remote")),
+                _ => (),
+            }
+        }
+    }
+
+    async fn receive_file(&mut self, directory: &Path, progress: Progress<'_>) -> io::Result<()> {
+        let mut retries = 0;
+
+        loop {
+            self.write_hex_header(Header::with_flags(ZRINIT, CANFDX | CANOVIO | CANFC32))
+                .await?;
+
+            let header = match self.read_header().await {
+                Ok(header) => header,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(e),
+                Err(e) => {
+                    retries += 1;
+                    if retries > MAX_RETRIES {
+                        return Err(e);
+                    }
+                    continue;
+                }
+            };
+
+            match header.kind {
+                ZSINIT => {
+                    if self.read_subpacket(header.encoding).await.is_ok() {
+                        self.write_hex_header(Header::new(ZACK, [0; 4])).await?;
+                    }
+                }
+                ZFILE => {
+                    let (file_info, _) = match self.read_subpacket(header.encoding).await {
+                        Ok(subpacket) => subpacket,
+                        Err(_) => {
+                            self.write_hex_header(Header::new(ZNAK, [0; 4])).await?;
+                            continue;
+                        }
+                    };
+                    let mut fields = file_info.split(|&byte| byte == 0);
+                    let name = fields
+                        .next()
+                        .map(|name| String::from_utf8_lossy(name).into_owned())
+                        .unwrap_or_default();
+                    // Never write outside of the target directory
+                    let name = match Path::new(&name).file_name() {
+                        Some(name) => name.to_string_lossy().into_owned(),
+                        None => return Err(protocol_error("Invalid file name")),
+                    };
+                    // Size and modification time in octal, both optional
+                    let info = fields
+                        .next()
+                        .map(|info| String::from_utf8_lossy(info).into_owned())
+                        .unwrap_or_default();
+                    let mut info = info.split(' ');
+                    let size = info
+                        .next()
+                        .and_then(|size| size.parse().ok())
+                        .unwrap_or(0u64);
+                    let mtime = info
+                        .next()
+                        .and_then(|mtime| u64::from_str_radix(mtime, 8).ok())
+                        .unwrap_or(0);
+
+                    // The same file has already been received
+                    let path = directory.join(&name);
+                    let received = std::fs::metadata(&path).is_ok_and(|m| m.len() == size);
+                    if received && mtime != 0 && modified(&path) == Some(mtime) {
+                        self.write_hex_header(Header::new(ZSKIP, [0; 4])).await?;
+                        continue;
+                    }
+
+                    let partial = directory.join(format!("{}{}", name, PARTIAL_SUFFIX));
+                    let mut offset = std::fs::metadata(&partial).map_or(0, |m| m.len());
+                    if size != 0 && offset > size {
+                        offset = 0;
+                    }
+                    let mut file = OpenOptions::new()
+                        .create(true)
+                        .write(true)
+                        .truncate(false)
+                        .open(&partial)?;
+                    file.set_len(offset)?;
+                    file.seek(SeekFrom::Start(offset))?;
+                    progress(&name, offset, size);
+                    self.receive_data(&mut file, &name, size, offset, &mut *progress)
+                        .await?;
+                    if mtime != 0 {
+                        file.set_modified(UNIX_EPOCH + Duration::from_secs(mtime))?;
+                    }
+                    drop(file);
+                    std::fs::rename(&partial, free_path(directory, &name))?;
+                    retries = 0;
+                }
+                ZFIN => {
+                    self.write_hex_header(Header::new(ZFIN, [0; 4])).await?;
+                    // The sender ends with "OO", nothing to do if it's missing
+                    let _ = self.read_byte().await;
+                    let _ = self.read_byte().await;
+                    return Ok(());
+                }
+                ZABORT | ZFERR => return Err(protocol_error("Transfer aborted by remote")),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Send the file at `path` to a remote ZMODEM receiver, resuming at the offset it asks for.
+pub async fn send_file<S, R>(
+    sink: &mut S,
+    stream: &mut R,
+    path: &Path,
+    progress: Progress<'_>,
+) -> io::Result<()>
+where
+    S: Sink<Bytes, Error = io::Error> + Unpin,
+    R: Stream<Item = Result<BytesMut, io::Error>> + Unpin,
+{
+    Session::new(sink, stream, &[])
+        .send_file(path, progress)
+        .await
+}
+
+/// Receive files from a remote ZMODEM sender into `directory`, `received` are the bytes
+/// already read from `stream` after the auto start sequence.
+///
+/// A file already present in `directory` is skipped when its size and modification time
+/// are the ones of the sent file, else the received file is given a new name.
+pub async fn receive_file<S, R>(
+    sink: &mut S,
+    stream: &mut R,
+    received: &[u8],
+    directory: &Path,
+    progress: Progress<'_>,
+) -> io::Result<()>
+where
+    S: Sink<Bytes, Error = io::Error> + Unpin,
+    R: Stream<Item = Result<BytesMut, io::Error>> + Unpin,
+{
+    Session::new(sink, stream, received)
+        .receive_file(directory, progress)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial;
+    use futures::channel::mpsc;
+    use futures::future;
+
+    /// One end of an in-memory link.
+    fn link_end(
+        sender: mpsc::UnboundedSender<Bytes>,
+        receiver: mpsc::UnboundedReceiver<Bytes>,
+    ) -> (
+        impl Sink<Bytes, Error = io::Error> + Unpin,
+        impl Stream<Item = io::Result<BytesMut>> + Unpin,
+    ) {
+        (
+            sender.sink_map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe)),
+            receiver.map(|data| Ok(BytesMut::from(&data[..]))),
+        )
+    }
+
+    fn empty_dir(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("stool-zmodem-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// Send `content` as `name` from one end of a link to a receiver writing in `target`.
+    fn transfer(name: &str, content: &[u8], target: &Path) {
+        let source = empty_dir(&format!("{}-source", name));
+        let path = source.join(name);
+        std::fs::write(&path, content).unwrap();
+
+        let (to_receiver, from_sender) = mpsc::unbounded();
+        let (to_sender, from_receiver) = mpsc::unbounded();
+        let (mut sender_sink, mut sender_stream) = link_end(to_receiver, from_receiver);
+        let (mut receiver_sink, mut receiver_stream) = link_end(to_sender, from_sender);
+
+        let runtime = serial::runtime().unwrap();
+        let (sent, received) = runtime.block_on(async {
+            let mut sender_progress = |_: &str, _, _| ();
+            let mut receiver_progress = |_: &str, _, _| ();
+            future::join(
+                send_file(
+                    &mut sender_sink,
+                    &mut sender_stream,
+                    &path,
+                    &mut sender_progress,
+                ),
+                receive_file(
+                    &mut receiver_sink,
+                    &mut receiver_stream,
+                    &[],
+                    target,
+                    &mut receiver_progress,
+                ),
+            )
+            .await
+        });
+        sent.unwrap();
+        received.unwrap();
+    }
+
+    #[test]
+    fn transfer_file() {
+        let content: Vec<u8> = (0..5000).map(|n| (n * 7 % 256) as u8).collect();
+        let target = empty_dir("transfer");
+        transfer("data.bin", &content, &target);
+        assert_eq!(std::fs::read(target.join("data.bin")).unwrap(), content);
+        assert!(!target.join("data.bin.part").exists());
+    }
+
+    #[test]
+    fn keep_existing_file() {
+        let target = empty_dir("existing");
+        std::fs::write(target.join("data.bin"), b"unrelated").unwrap();
+        transfer("data.bin", b"received content", &target);
+        assert_eq!(
+            std::fs::read(target.join("data.bin")).unwrap(),
+            b"unrelated"
+        );
+        assert_eq!(
+            std::fs::read(target.join("data-1.bin")).unwrap(),
+            b"received content"
+        );
+    }
+
+    #[test]
+    fn resume_partial_file() {
+        let content: Vec<u8> = (0..3000).map(|n| (n % 251) as u8).collect();
+        let target = empty_dir("resume");
+        std::fs::write(target.join("data.bin.part"), &content[..1000]).unwrap();
+        transfer("data.bin", &content, &target);
+        assert_eq!(std::fs::read(target.join("data.bin")).unwrap(), content);
+    }
+
+    #[test]
+    fn auto_start_split_between_reads() {
+        let mut auto_start = AutoStart::default();
+        assert_eq!(auto_start.find(b"hello **\x18"), None);
+        assert_eq!(auto_start.find(b"B00000000"), Some((0, 3)));
+
+        let mut auto_start = AutoStart::default();
+        assert_eq!(auto_start.find(b"rz\r**\x18B0000"), Some((3, 9)));
+    }
+}